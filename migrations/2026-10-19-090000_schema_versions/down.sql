@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE fields DROP COLUMN IF EXISTS version;
+ALTER TABLE tables DROP COLUMN IF EXISTS version;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE tables ADD COLUMN IF NOT EXISTS version int NOT NULL DEFAULT 1;
+ALTER TABLE fields ADD COLUMN IF NOT EXISTS version int NOT NULL DEFAULT 1;
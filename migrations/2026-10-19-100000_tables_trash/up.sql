@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE tables ADD COLUMN IF NOT EXISTS deleted_at timestamp NULL;
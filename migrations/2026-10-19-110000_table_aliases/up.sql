@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS table_aliases
+(
+ id         serial NOT NULL,
+ table_id   int NOT NULL,
+ name       varchar(255) NOT NULL,
+ expires_at timestamp NOT NULL,
+ created_at timestamp NOT NULL DEFAULT now(),
+ CONSTRAINT PK_table_aliases PRIMARY KEY ( id ),
+ CONSTRAINT UQ_table_aliases_name UNIQUE ( name ),
+ CONSTRAINT FK_table_aliases_table FOREIGN KEY ( table_id ) REFERENCES tables ( id ) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS row_history;
+ALTER TABLE tables DROP COLUMN IF EXISTS history;
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+ALTER TABLE tables ADD COLUMN IF NOT EXISTS history boolean NOT NULL DEFAULT false;
+
+CREATE TABLE IF NOT EXISTS row_history
+(
+ id         serial NOT NULL,
+ table_id   int NOT NULL,
+ row_id     text NOT NULL,
+ version    int NOT NULL,
+ operation  varchar(7) NOT NULL,
+ data       jsonb NOT NULL,
+ changed_by int NULL,
+ changed_at timestamp NOT NULL DEFAULT now(),
+ CONSTRAINT PK_row_history PRIMARY KEY ( id ),
+ CONSTRAINT UQ_row_history_version UNIQUE ( table_id, row_id, version ),
+ CONSTRAINT FK_row_history_table FOREIGN KEY ( table_id ) REFERENCES tables ( id ) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS row_history_changed_at ON row_history ( table_id, changed_at );
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS idempotency_keys
+(
+ id            serial NOT NULL,
+ key           varchar(255) NOT NULL,
+ fingerprint   varchar(64) NOT NULL,
+ status_code   int NULL,
+ response_body text NULL,
+ expires_at    timestamp NOT NULL,
+ created_at    timestamp NOT NULL DEFAULT now(),
+ CONSTRAINT PK_idempotency_keys PRIMARY KEY ( id ),
+ CONSTRAINT UQ_idempotency_keys_key UNIQUE ( key )
+);
+
+CREATE INDEX IF NOT EXISTS IX_idempotency_keys_expires_at ON idempotency_keys ( expires_at );
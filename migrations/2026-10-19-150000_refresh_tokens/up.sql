@@ -0,0 +1,26 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS refresh_tokens
+(
+ id         serial NOT NULL,
+ user_id    int NOT NULL,
+ token_hash varchar(64) NOT NULL,
+ family     varchar(32) NOT NULL,
+ access_jti varchar(64) NOT NULL,
+ used_at    timestamp NULL,
+ revoked_at timestamp NULL,
+ expires_at timestamp NOT NULL,
+ created_at timestamp NOT NULL DEFAULT now(),
+ CONSTRAINT PK_refresh_tokens PRIMARY KEY ( id ),
+ CONSTRAINT UQ_refresh_tokens_token_hash UNIQUE ( token_hash ),
+ CONSTRAINT FK_refresh_tokens_user FOREIGN KEY ( user_id ) REFERENCES users ( id ) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_refresh_tokens_family ON refresh_tokens ( family );
+
+CREATE TABLE IF NOT EXISTS revoked_tokens
+(
+ jti        varchar(64) NOT NULL,
+ expires_at timestamp NOT NULL,
+ revoked_at timestamp NOT NULL DEFAULT now(),
+ CONSTRAINT PK_revoked_tokens PRIMARY KEY ( jti )
+);
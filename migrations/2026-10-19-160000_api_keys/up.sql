@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS api_keys
+(
+ id           serial NOT NULL,
+ user_id      int NOT NULL,
+ name         varchar(100) NOT NULL,
+ prefix       varchar(16) NOT NULL,
+ key_hash     varchar(64) NOT NULL,
+ scopes       jsonb NOT NULL DEFAULT '[]',
+ expires_at   timestamp NULL,
+ last_used_at timestamp NULL,
+ revoked_at   timestamp NULL,
+ created_at   timestamp NOT NULL DEFAULT now(),
+ CONSTRAINT PK_api_keys PRIMARY KEY ( id ),
+ CONSTRAINT UQ_api_keys_key_hash UNIQUE ( key_hash ),
+ CONSTRAINT FK_api_keys_user FOREIGN KEY ( user_id ) REFERENCES users ( id ) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_api_keys_user_id ON api_keys ( user_id );
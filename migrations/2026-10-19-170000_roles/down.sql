@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS users_roles;
+DROP TABLE IF EXISTS role_grants;
+DROP TABLE IF EXISTS roles;
@@ -0,0 +1,51 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS roles
+(
+ id          serial NOT NULL,
+ name        varchar(50) NOT NULL,
+ description text NULL,
+ created_at  timestamp NOT NULL DEFAULT now(),
+ CONSTRAINT PK_roles PRIMARY KEY ( id ),
+ CONSTRAINT UQ_roles_name UNIQUE ( name )
+);
+
+-- `table_name` and `permission` take `*` for any, `Schema` grants table and field management.
+-- A `*` table covers CMS tables only, `users`, `roles` and `posts` need a grant naming them.
+CREATE TABLE IF NOT EXISTS role_grants
+(
+ id         serial NOT NULL,
+ role_id    int NOT NULL,
+ table_name varchar(255) NOT NULL DEFAULT '*',
+ permission varchar(9) NOT NULL,
+ CONSTRAINT PK_role_grants PRIMARY KEY ( id ),
+ CONSTRAINT UQ_role_grants UNIQUE ( role_id, table_name, permission ),
+ CONSTRAINT FK_role_grants_role FOREIGN KEY ( role_id ) REFERENCES roles ( id ) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS users_roles
+(
+ user_id int NOT NULL,
+ role_id int NOT NULL,
+ CONSTRAINT PK_users_roles PRIMARY KEY ( user_id, role_id ),
+ CONSTRAINT FK_users_roles_user FOREIGN KEY ( user_id ) REFERENCES users ( id ) ON DELETE CASCADE,
+ CONSTRAINT FK_users_roles_role FOREIGN KEY ( role_id ) REFERENCES roles ( id ) ON DELETE CASCADE
+);
+
+-- Users with API rights keep the access they had before roles, to CMS tables; new ones get
+-- `DEFAULT_ROLE`
+INSERT INTO roles ( name, description )
+VALUES ( 'editor', 'Every method on every CMS table' ),
+       ( 'schema_admin', 'Manages tables and fields' )
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_grants ( role_id, table_name, permission )
+SELECT id, '*', '*' FROM roles WHERE name = 'editor'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_grants ( role_id, table_name, permission )
+SELECT id, '*', 'Schema' FROM roles WHERE name = 'schema_admin'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO users_roles ( user_id, role_id )
+SELECT users.id, roles.id FROM users, roles WHERE roles.name = 'editor' AND users.api_rights
+ON CONFLICT DO NOTHING;
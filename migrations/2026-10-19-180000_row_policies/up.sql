@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- `owner` policies match `field` to the user id, `team` policies match `field` to a team
+-- the user belongs to according to the membership table
+CREATE TABLE IF NOT EXISTS row_policies
+(
+ id                    serial NOT NULL,
+ table_id              int NOT NULL,
+ name                  varchar(100) NOT NULL,
+ kind                  varchar(10) NOT NULL,
+ field                 varchar(255) NOT NULL,
+ membership_table      varchar(255) NULL,
+ membership_team_field varchar(255) NULL,
+ membership_user_field varchar(255) NULL,
+ operations            jsonb NOT NULL DEFAULT '["read", "update", "delete"]',
+ created_at            timestamp NOT NULL DEFAULT now(),
+ CONSTRAINT PK_row_policies PRIMARY KEY ( id ),
+ CONSTRAINT UQ_row_policies_name UNIQUE ( table_id, name ),
+ CONSTRAINT FK_row_policies_table FOREIGN KEY ( table_id ) REFERENCES tables ( id ) ON DELETE CASCADE
+);
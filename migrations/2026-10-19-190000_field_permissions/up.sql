@@ -0,0 +1,16 @@
+-- Your SQL goes here
+-- How a role sees a field: `hidden`, `readOnly` or `masked` to its last `mask_visible` characters.
+-- Fields without a row for a role are fully readable and writable by it
+CREATE TABLE IF NOT EXISTS field_permissions
+(
+ id           serial NOT NULL,
+ field_id     int NOT NULL,
+ role_id      int NOT NULL,
+ access       varchar(10) NOT NULL,
+ mask_visible int NOT NULL DEFAULT 4,
+ created_at   timestamp NOT NULL DEFAULT now(),
+ CONSTRAINT PK_field_permissions PRIMARY KEY ( id ),
+ CONSTRAINT UQ_field_permissions_role UNIQUE ( field_id, role_id ),
+ CONSTRAINT FK_field_permissions_field FOREIGN KEY ( field_id ) REFERENCES fields ( id ) ON DELETE CASCADE,
+ CONSTRAINT FK_field_permissions_role FOREIGN KEY ( role_id ) REFERENCES roles ( id ) ON DELETE CASCADE
+);
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified_at timestamp NULL;
+
+-- Single use tokens mailed to users, `purpose` is `password_reset` or `email_verification`
+CREATE TABLE IF NOT EXISTS user_tokens
+(
+ id         serial NOT NULL,
+ user_id    int NOT NULL,
+ purpose    varchar(20) NOT NULL,
+ token_hash varchar(64) NOT NULL,
+ expires_at timestamp NOT NULL,
+ used_at    timestamp NULL,
+ created_at timestamp NOT NULL DEFAULT now(),
+ CONSTRAINT PK_user_tokens PRIMARY KEY ( id ),
+ CONSTRAINT UQ_user_tokens_token_hash UNIQUE ( token_hash ),
+ CONSTRAINT FK_user_tokens_user FOREIGN KEY ( user_id ) REFERENCES users ( id ) ON DELETE CASCADE
+);
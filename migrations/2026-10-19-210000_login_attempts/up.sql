@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- Failed logins of an account (`email:<email>`) or of a client (`ip:<address>`) since the last success
+CREATE TABLE IF NOT EXISTS login_attempts
+(
+ id              serial NOT NULL,
+ subject         varchar(300) NOT NULL,
+ failures        int NOT NULL DEFAULT 0,
+ locked_until    timestamp NULL,
+ last_failure_at timestamp NOT NULL DEFAULT now(),
+ CONSTRAINT PK_login_attempts PRIMARY KEY ( id ),
+ CONSTRAINT UQ_login_attempts_subject UNIQUE ( subject )
+);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS UQ_idempotency_keys_user_key;
+DELETE FROM idempotency_keys a USING idempotency_keys b WHERE a.key = b.key AND a.id > b.id;
+ALTER TABLE idempotency_keys ADD CONSTRAINT UQ_idempotency_keys_key UNIQUE ( key );
+ALTER TABLE idempotency_keys DROP CONSTRAINT IF EXISTS FK_idempotency_keys_user;
+ALTER TABLE idempotency_keys DROP COLUMN IF EXISTS user_id;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- Keys are namespaced by the authenticated user, anonymous requests share the `NULL` namespace
+ALTER TABLE idempotency_keys ADD COLUMN IF NOT EXISTS user_id int NULL;
+ALTER TABLE idempotency_keys
+  ADD CONSTRAINT FK_idempotency_keys_user FOREIGN KEY ( user_id ) REFERENCES users ( id ) ON DELETE CASCADE;
+ALTER TABLE idempotency_keys DROP CONSTRAINT IF EXISTS UQ_idempotency_keys_key;
+CREATE UNIQUE INDEX IF NOT EXISTS UQ_idempotency_keys_user_key ON idempotency_keys ( COALESCE(user_id, 0), key );
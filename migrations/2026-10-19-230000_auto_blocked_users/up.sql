@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Set when failed logins blocked the user, a password reset or an admin unblocks them
+ALTER TABLE users ADD COLUMN IF NOT EXISTS auto_blocked_at timestamp NULL;
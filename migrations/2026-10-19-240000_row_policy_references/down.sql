@@ -0,0 +1,20 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE row_policies ADD COLUMN IF NOT EXISTS field varchar(255) NULL;
+ALTER TABLE row_policies ADD COLUMN IF NOT EXISTS membership_table varchar(255) NULL;
+ALTER TABLE row_policies ADD COLUMN IF NOT EXISTS membership_team_field varchar(255) NULL;
+ALTER TABLE row_policies ADD COLUMN IF NOT EXISTS membership_user_field varchar(255) NULL;
+
+UPDATE row_policies p SET field = f.name FROM fields f WHERE f.id = p.field_id;
+UPDATE row_policies p SET membership_table = t.name FROM tables t WHERE t.id = p.membership_table_id;
+UPDATE row_policies p SET membership_team_field = f.name FROM fields f WHERE f.id = p.membership_team_field_id;
+UPDATE row_policies p SET membership_user_field = f.name FROM fields f WHERE f.id = p.membership_user_field_id;
+ALTER TABLE row_policies ALTER COLUMN field SET NOT NULL;
+
+ALTER TABLE row_policies DROP CONSTRAINT IF EXISTS FK_row_policies_field;
+ALTER TABLE row_policies DROP CONSTRAINT IF EXISTS FK_row_policies_membership_table;
+ALTER TABLE row_policies DROP CONSTRAINT IF EXISTS FK_row_policies_membership_team_field;
+ALTER TABLE row_policies DROP CONSTRAINT IF EXISTS FK_row_policies_membership_user_field;
+ALTER TABLE row_policies DROP COLUMN IF EXISTS field_id;
+ALTER TABLE row_policies DROP COLUMN IF EXISTS membership_table_id;
+ALTER TABLE row_policies DROP COLUMN IF EXISTS membership_team_field_id;
+ALTER TABLE row_policies DROP COLUMN IF EXISTS membership_user_field_id;
@@ -0,0 +1,43 @@
+-- Your SQL goes here
+-- Policies point to their fields and membership table by id, so renaming or trashing a table and
+-- renaming a field keep them working. A field or table a policy points to cannot be deleted.
+ALTER TABLE row_policies ADD COLUMN IF NOT EXISTS field_id int NULL;
+ALTER TABLE row_policies ADD COLUMN IF NOT EXISTS membership_table_id int NULL;
+ALTER TABLE row_policies ADD COLUMN IF NOT EXISTS membership_team_field_id int NULL;
+ALTER TABLE row_policies ADD COLUMN IF NOT EXISTS membership_user_field_id int NULL;
+
+UPDATE row_policies p SET field_id = f.id
+  FROM fields f WHERE f.table_id = p.table_id AND f.name = p.field;
+UPDATE row_policies p SET membership_table_id = t.id
+  FROM tables t WHERE t.name = p.membership_table;
+UPDATE row_policies p SET membership_team_field_id = f.id
+  FROM fields f WHERE f.table_id = p.membership_table_id AND f.name = p.membership_team_field;
+UPDATE row_policies p SET membership_user_field_id = f.id
+  FROM fields f WHERE f.table_id = p.membership_table_id AND f.name = p.membership_user_field;
+
+-- Dropping a policy that no longer resolves would open its rows to everyone, it is fixed by hand
+DO $$
+BEGIN
+  IF EXISTS (
+    SELECT 1 FROM row_policies
+    WHERE field_id IS NULL
+       OR (kind = 'team' AND (membership_team_field_id IS NULL OR membership_user_field_id IS NULL))
+  ) THEN
+    RAISE EXCEPTION 'row_policies holds policies whose fields or membership table no longer exist';
+  END IF;
+END $$;
+
+ALTER TABLE row_policies ALTER COLUMN field_id SET NOT NULL;
+ALTER TABLE row_policies
+  ADD CONSTRAINT FK_row_policies_field FOREIGN KEY ( field_id ) REFERENCES fields ( id );
+ALTER TABLE row_policies
+  ADD CONSTRAINT FK_row_policies_membership_table FOREIGN KEY ( membership_table_id ) REFERENCES tables ( id );
+ALTER TABLE row_policies
+  ADD CONSTRAINT FK_row_policies_membership_team_field FOREIGN KEY ( membership_team_field_id ) REFERENCES fields ( id );
+ALTER TABLE row_policies
+  ADD CONSTRAINT FK_row_policies_membership_user_field FOREIGN KEY ( membership_user_field_id ) REFERENCES fields ( id );
+
+ALTER TABLE row_policies DROP COLUMN IF EXISTS field;
+ALTER TABLE row_policies DROP COLUMN IF EXISTS membership_table;
+ALTER TABLE row_policies DROP COLUMN IF EXISTS membership_team_field;
+ALTER TABLE row_policies DROP COLUMN IF EXISTS membership_user_field;
@@ -0,0 +1,257 @@
+use std::collections::{BTreeMap, HashMap};
+
+use diesel::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::custom_controller::CustomController;
+use super::history_controller::HistoryController;
+use super::patch::RowUpdate;
+use super::row_count::RowCountCache;
+use crate::controller::login::auth_controller::Claims;
+use crate::controller::tables::permissions::table_permissions_controller::TablePermissionsController;
+use crate::controller::tables::row_policy_controller::Viewer;
+use crate::models::cms::permission_model::PermissionType;
+use crate::models::cms::table_model::Table;
+use crate::models::db::connection::establish_connection;
+use crate::routes::utils::reponses::ReturnError;
+
+pub const BATCH_LIMIT: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl BatchAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BatchAction::Create => "create",
+            BatchAction::Update => "update",
+            BatchAction::Delete => "delete",
+        }
+    }
+
+    pub fn permission(&self) -> PermissionType {
+        match self {
+            BatchAction::Create => PermissionType::Create,
+            BatchAction::Update => PermissionType::Update,
+            BatchAction::Delete => PermissionType::Delete,
+        }
+    }
+}
+
+/// One write of a batch. `id` and `values` may hold `${ref.field}` strings,
+/// replaced by the field of the row returned by an earlier operation (named by its `ref` or index).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchOperation {
+    pub op: BatchAction,
+    pub table: String,
+    pub id: Option<Value>,
+    pub values: Option<Value>,
+    #[serde(rename = "ref")]
+    pub reference: Option<String>,
+    pub if_match: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BatchResult {
+    pub op: BatchAction,
+    pub table: String,
+    #[serde(rename = "ref")]
+    pub reference: Option<String>,
+    pub row: Value,
+}
+
+/// Runs create, update and delete operations on any tables in a single transaction
+pub struct BatchController;
+
+impl BatchController {
+    pub fn run(
+        request: BatchRequest,
+        claims: Option<&Claims>,
+    ) -> Result<Vec<BatchResult>, ReturnError> {
+        let operations = request.operations;
+        if operations.is_empty() {
+            return Err(ReturnError::without_value(
+                "At least one operation is required".to_owned(),
+            ));
+        }
+        if operations.len() > BATCH_LIMIT {
+            return Err(ReturnError::without_value(format!(
+                "A batch holds at most {} operations",
+                BATCH_LIMIT
+            )));
+        }
+
+        // Tables and permissions are checked up front, nothing is written if one is refused
+        let mut tables: HashMap<String, Table> = HashMap::new();
+        for (i, operation) in operations.iter().enumerate() {
+            if !tables.contains_key(&operation.table) {
+                let table = CustomController::active_table(&operation.table)
+                    .map_err(|err| Self::operation_error(i, err))?;
+                tables.insert(operation.table.clone(), table);
+            }
+            TablePermissionsController::authorize(
+                &tables[&operation.table],
+                operation.op.permission(),
+                claims,
+            )
+            .map_err(|err| Self::operation_error(i, err))?;
+        }
+
+        // Tables are locked by `check` in name order, so two batches cannot deadlock
+        let mut inserts: BTreeMap<String, i64> = BTreeMap::new();
+        for operation in operations.iter().filter(|x| x.op == BatchAction::Create) {
+            *inserts.entry(operation.table.clone()).or_default() += 1;
+        }
+
+        let viewer = Viewer {
+            user: claims.and_then(|x| x.user_id()),
+            admin: claims.is_some_and(|x| x.admin_rights),
+        };
+        let connection = &mut establish_connection();
+        let results = connection.transaction::<_, ReturnError, _>(|conn| {
+            for (table, adding) in &inserts {
+                RowCountCache::check(conn, &tables[table], *adding)?;
+            }
+            let mut rows: HashMap<String, Value> = HashMap::new();
+            let mut results = Vec::new();
+            for (i, operation) in operations.iter().enumerate() {
+                let table = &tables[&operation.table];
+                let row = Self::execute(conn, table, operation, &rows, viewer)
+                    .map_err(|err| Self::operation_error(i, err))?;
+                rows.insert(i.to_string(), row.clone());
+                if let Some(reference) = operation.reference.as_ref() {
+                    rows.insert(reference.clone(), row.clone());
+                }
+                results.push(BatchResult {
+                    op: operation.op,
+                    table: operation.table.clone(),
+                    reference: operation.reference.clone(),
+                    row,
+                });
+            }
+            Ok(results)
+        })?;
+
+        // Counts only move once the rows are committed, a rolled back batch leaves them as they were
+        for result in &results {
+            let delta = match result.op {
+                BatchAction::Create => 1,
+                BatchAction::Delete => -1,
+                BatchAction::Update => continue,
+            };
+            RowCountCache::add(tables[&result.table].id, delta);
+        }
+        Ok(results)
+    }
+
+    fn execute(
+        conn: &mut diesel::PgConnection,
+        table: &Table,
+        operation: &BatchOperation,
+        rows: &HashMap<String, Value>,
+        viewer: Viewer,
+    ) -> Result<Value, ReturnError> {
+        let values = operation
+            .values
+            .as_ref()
+            .map(|x| Self::resolve(x, rows))
+            .transpose()?;
+        let id = match operation.id.as_ref() {
+            Some(id) => match Self::resolve(id, rows)? {
+                Value::String(id) => Some(id),
+                Value::Null => None,
+                id => Some(id.to_string()),
+            },
+            None => None,
+        };
+        let id = || {
+            id.clone().ok_or(ReturnError::without_value(format!(
+                "An id is required to {} a row",
+                operation.op.as_str()
+            )))
+        };
+        let values = || {
+            values
+                .clone()
+                .ok_or(ReturnError::without_value("Invalid data".to_owned()))
+        };
+        let if_match = operation.if_match.as_deref();
+
+        match operation.op {
+            BatchAction::Create => CustomController::create_in(conn, table, values()?, viewer)?
+                .into_iter()
+                .next()
+                .map(|x| x.0)
+                .ok_or(ReturnError::without_value("Row was not created".to_owned())),
+            BatchAction::Update => CustomController::update_in(
+                conn,
+                table,
+                &id()?,
+                &RowUpdate::Values(values()?),
+                viewer,
+                if_match,
+            )
+            .map(|(row, _)| row.0),
+            BatchAction::Delete => {
+                CustomController::delete_in(conn, table, &id()?, viewer, if_match).map(|x| x.0)
+            }
+        }
+    }
+
+    /// Replaces `${ref.field}` strings, anywhere in the value, by the field of an earlier row
+    fn resolve(value: &Value, rows: &HashMap<String, Value>) -> Result<Value, ReturnError> {
+        match value {
+            Value::String(text) => {
+                let reference = match text
+                    .strip_prefix("${")
+                    .and_then(|x| x.strip_suffix('}'))
+                    .and_then(|x| x.split_once('.'))
+                {
+                    Some(reference) => reference,
+                    None => return Ok(value.clone()),
+                };
+                let (name, field) = reference;
+                rows.get(name)
+                    .and_then(|row| row.as_object())
+                    .and_then(|row| HistoryController::value_of(row, field))
+                    .cloned()
+                    .ok_or(ReturnError::new(
+                        format!("Unknown reference \"{}\"", text),
+                        value,
+                    ))
+            }
+            Value::Array(items) => items
+                .iter()
+                .map(|x| Self::resolve(x, rows))
+                .collect::<Result<Vec<Value>, ReturnError>>()
+                .map(Value::Array),
+            Value::Object(map) => {
+                let mut resolved = Map::new();
+                for (key, value) in map {
+                    resolved.insert(key.clone(), Self::resolve(value, rows)?);
+                }
+                Ok(Value::Object(resolved))
+            }
+            value => Ok(value.clone()),
+        }
+    }
+
+    fn operation_error(index: usize, err: ReturnError) -> ReturnError {
+        ReturnError {
+            error_msg: format!("Operation {} failed: {}", index, err.error_msg),
+            values: err.values,
+        }
+    }
+}
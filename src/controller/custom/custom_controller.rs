@@ -1,33 +1,92 @@
 use std::sync::Arc;
 
+use tokio_postgres::types::ToSql;
+
 use crate::controller::GenericValue;
+use crate::models::cms::custom::row_history_model::RowHistory;
 use crate::models::cms::fields_model::Field;
+use crate::models::cms::row_policy_model::PolicyOperation;
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use diesel::pg::Pg;
 use diesel::query_builder::BoxedSqlQuery;
-use diesel::sql_types::{Binary, Bool, Date, Float, Integer, Json, Time, Timestamp, VarChar};
-use diesel::{sql_query, RunQueryDsl};
+use diesel::sql_types::{
+    Binary, Bool, Date, Float, Integer, Json, Jsonb, Nullable, Time, Timestamp, VarChar,
+};
+use diesel::{sql_query, Connection, OptionalExtension, PgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
 use crate::controller::db::establish_connection;
 use crate::controller::fields::field_controller::FieldController;
+use crate::controller::fields::field_permission_controller::{
+    FieldPermissionController, FieldRules,
+};
 use crate::controller::fields::types::FieldType;
+use super::etag::{Etag, ETAG_SQL};
+use super::history_controller::{HistoryController, RowOperation};
+use super::patch::RowUpdate;
+use super::row_count::RowCountCache;
+use crate::controller::tables::row_policy_controller::{RowPolicyController, Viewer};
 use crate::controller::tables::table_controller::TableController;
 use crate::controller::QueryParams;
+use crate::controller::API_LIMIT;
+use crate::models::cms::table_model::Table;
 use crate::models::db::connection::DbPool;
 use crate::models::db::driver_connection::establish_driver_connection;
 
 use crate::routes::utils::reponses::ReturnError;
+use crate::utils::sql::AUDIT_COLUMNS;
+use crate::utils::string_utils::to_snake_case;
 
 pub struct CustomController(pub Arc<DbPool>);
 
+/// Primary keys to look up with `_mget`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ManyRequest {
+    pub ids: Vec<Value>,
+}
+
+/// Rows found by `_mget` in request order, with the keys that matched nothing
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ManyRows {
+    pub rows: Vec<Value>,
+    pub missing: Vec<Value>,
+}
+
 impl CustomController {
+    /// Inactive tables keep their data but are not served
+    pub fn active_table<S: AsRef<str>>(table_name: S) -> Result<Table, ReturnError> {
+        let name = table_name.as_ref();
+        let table = match TableController::find_by_name(name) {
+            Ok(table) => table,
+            Err(_) => {
+                return Err(ReturnError::without_value(format!(
+                    "Table \"{name}\" not found"
+                )))
+            }
+        };
+        if !table.is_active {
+            return Err(ReturnError::without_value(format!(
+                "Table \"{name}\" is inactive"
+            )));
+        }
+        Ok(table)
+    }
+
+    /// Returns the row with its `ETag`, past states read through `asOf` have none
     pub async fn find_one(
         &self,
         table_name: String,
         id: String,
         mut query_params: QueryParams,
-    ) -> Result<Value, ReturnError> {
+        viewer: Viewer,
+    ) -> Result<(Value, Option<String>), ReturnError> {
+        if let Some(as_of) = query_params.as_of.take() {
+            let rules = Self::check_history(&table_name, viewer)?;
+            let mut row = HistoryController::find_one_as_of(table_name, id, &as_of)?;
+            rules.apply(&mut row);
+            return Ok((row, None));
+        }
         let pk = FieldController::find_pk(&table_name);
 
         if pk.is_err() {
@@ -36,21 +95,27 @@ impl CustomController {
             )));
         }
         let pk = pk.unwrap();
-        query_params.extra.insert(pk.name, id.into());
+        let id = Self::pk_value(&pk, &id)?;
+        query_params.extra.insert(pk.name, id);
 
-        let conditions_str = match get_conditions(&query_params) {
+        let name = table_name;
+        let table = Self::active_table(&name)?;
+        let fields = FieldController::find_all_by_table_name(&name)?;
+        let (mut conditions_str, params) = match get_conditions(&query_params, &fields) {
             Ok(value) => value,
             Err(value) => return Err(value),
         };
-
-        let name = table_name;
-        let table = TableController::find_by_name(&name);
-
-        if table.is_err() {
-            return Err(ReturnError::without_value(format!(
-                "Table \"{name}\" not found"
-            )));
+        let rules = FieldPermissionController::rules(&table, viewer)?;
+        let unreadable = rules.unreadable();
+        for key in query_params.extra.keys() {
+            unreadable.check(key)?;
         }
+        if let Some(predicate) =
+            RowPolicyController::predicate(&table, PolicyOperation::Read, viewer)?
+        {
+            conditions_str.push_str(&format!(" AND ({})", predicate));
+        }
+
         let client = match establish_driver_connection().await {
             Ok(client) => client,
             Err(_) => {
@@ -60,10 +125,31 @@ impl CustomController {
             }
         };
 
-        let query = format!("SELECT * FROM {} {}", name, conditions_str);
-        match client.query(&query, &[]).await {
-            Ok(rows) => match resolve_rows(rows) {
-                Ok(value) => return Ok(value.first().unwrap().clone()),
+        let query = format!(
+            "SELECT t.*, {} AS _etag FROM {} t {}",
+            ETAG_SQL, name, conditions_str
+        );
+        let params = params
+            .iter()
+            .map(|x| x as &(dyn ToSql + Sync))
+            .collect::<Vec<_>>();
+        match client.query(&query, &params).await {
+            Ok(rows) => match resolve_rows(rows, &rules) {
+                Ok(value) => {
+                    let mut row = match value.into_iter().next() {
+                        Some(row) => row,
+                        None => {
+                            return Err(ReturnError::without_value(format!(
+                                "Row not found in table \"{name}\""
+                            )))
+                        }
+                    };
+                    let etag = row
+                        .as_object_mut()
+                        .and_then(|x| x.remove("_etag"))
+                        .and_then(|x| x.as_str().map(Etag::quoted));
+                    return Ok((row, etag));
+                }
                 Err(value) => return Err(value),
             },
             Err(err) => {
@@ -79,7 +165,18 @@ impl CustomController {
     pub async fn find_all(
         table_name: String,
         mut query_params: QueryParams,
+        viewer: Viewer,
     ) -> Result<Vec<GenericValue>, ReturnError> {
+        if let Some(as_of) = query_params.as_of.take() {
+            let rules = Self::check_history(&table_name, viewer)?;
+            // Snapshots are filtered before the rules redact them
+            rules.check_filters(&query_params.get_extras())?;
+            let mut rows = HistoryController::find_all_as_of(table_name, query_params, &as_of)?;
+            rules.apply_all(&mut rows);
+            return Ok(rows);
+        }
+        let table = Self::active_table(&table_name)?;
+        let rules = FieldPermissionController::rules(&table, viewer)?;
         let fields = FieldController::find_all_by_table_name(&table_name);
         if fields.is_err() {
             return Err(ReturnError::without_value("Table not found".to_owned()));
@@ -92,7 +189,7 @@ impl CustomController {
         let mut field_in_condition = Vec::new();
         for (i, (key, _)) in extras.iter().enumerate() {
             if i == 0 {
-                clause.push_str("WHERE ");
+                clause.push_str("WHERE (");
             }
             if i > 0 {
                 clause.push_str(" AND ");
@@ -104,21 +201,33 @@ impl CustomController {
             }
             field_in_condition.push(key.clone());
         }
+        if !clause.is_empty() {
+            clause.push(')');
+        }
+        // Both sides are parenthesized so an `OR` in either cannot widen the other
+        if let Some(predicate) =
+            RowPolicyController::predicate(&table, PolicyOperation::Read, viewer)?
+        {
+            let join = if clause.is_empty() { "WHERE" } else { " AND" };
+            clause.push_str(&format!("{} ({})", join, predicate));
+        }
 
         let connection = &mut establish_connection();
         let query = format!(
-            "SELECT row_to_json(t) as row FROM (Select * from {} {}) t;",
+            "SELECT row_to_json(t) as row FROM (Select * from {} t {}) t;",
             table_name, clause
         );
         let query = sql_query(query);
 
-        let query = match add_params(fields.iter(), &extras, query) {
+        let unreadable = rules.unreadable();
+        let query = match add_params(fields.iter(), &extras, query, &unreadable) {
             Ok(query) => query,
             Err(err) => return Err(err),
         };
 
         match query.get_results::<GenericValue>(connection) {
-            Ok(results) => {
+            Ok(mut results) => {
+                rules.apply_all(&mut results);
                 return Ok(results);
             }
             Err(err) => {
@@ -127,14 +236,144 @@ impl CustomController {
         }
     }
 
+    /// Audit columns are written by the server only
+    fn check_managed(table: &Table, values: &Map<String, Value>) -> Result<(), ReturnError> {
+        if !table.audit {
+            return Ok(());
+        }
+        match values
+            .keys()
+            .find(|x| AUDIT_COLUMNS.contains(&to_snake_case(x).as_str()))
+        {
+            Some(key) => Err(ReturnError::without_value(format!(
+                "Field \"{key}\" is managed by the server and cannot be set"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// SQL literal for the acting user, inlined since it never comes from the request body
+    fn user_literal(user: Option<i32>) -> String {
+        user.map(|x| x.to_string()).unwrap_or("NULL".to_owned())
+    }
+
+    /// Path ids are strings, they are bound with the type of the primary key
+    fn pk_value(pk: &Field, id: &str) -> Result<Value, ReturnError> {
+        let invalid = || ReturnError::without_value(format!("Invalid id \"{id}\""));
+        match FieldType::from_string(&pk.field_type)? {
+            FieldType::Integer => id.parse::<i64>().map(Value::from).map_err(|_| invalid()),
+            FieldType::Float => id.parse::<f64>().map(Value::from).map_err(|_| invalid()),
+            FieldType::Boolean => id.parse::<bool>().map(Value::from).map_err(|_| invalid()),
+            _ => Ok(Value::from(id)),
+        }
+    }
+
+    /// Rows of many primary keys in one query, ids are typed after the primary key
+    pub fn find_many(
+        table_name: String,
+        ids: Vec<Value>,
+        viewer: Viewer,
+    ) -> Result<ManyRows, ReturnError> {
+        let table = Self::active_table(&table_name)?;
+        if ids.is_empty() {
+            return Err(ReturnError::without_value(
+                "At least one id is required".to_owned(),
+            ));
+        }
+        if ids.len() > API_LIMIT as usize {
+            return Err(ReturnError::without_value(format!(
+                "At most {} ids can be looked up at once",
+                API_LIMIT
+            )));
+        }
+        let pk = FieldController::find_pk(&table_name).map_err(|_| {
+            ReturnError::without_value(format!("Table \"{table_name}\" has no primary key"))
+        })?;
+        let field_type = FieldType::from_string(&pk.field_type)?;
+        let keys = ids
+            .iter()
+            .map(|id| match id {
+                Value::String(id) => Self::pk_value(&pk, id),
+                id => Self::pk_value(&pk, &id.to_string()),
+            })
+            .collect::<Result<Vec<Value>, ReturnError>>()?;
+
+        // Ids are sent as one JSON array and cast back to the key type, so the index is used
+        let texts: Vec<Value> = keys
+            .iter()
+            .map(|x| match x {
+                Value::String(x) => Value::from(x.clone()),
+                x => Value::from(x.to_string()),
+            })
+            .collect();
+        let policy = RowPolicyController::predicate(&table, PolicyOperation::Read, viewer)?
+            .map(|x| format!(" AND ({})", x))
+            .unwrap_or_default();
+        let query = format!(
+            "SELECT row_to_json(t) as row FROM {} t WHERE t.\"{}\" IN (SELECT jsonb_array_elements_text($1)::{}){};",
+            table.name,
+            pk.name,
+            field_type.to_pg_type(),
+            policy
+        );
+        let connection = &mut establish_connection();
+        let found = sql_query(query)
+            .bind::<Jsonb, _>(Value::Array(texts))
+            .get_results::<GenericValue>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &ids))?;
+
+        let mut result = ManyRows {
+            rows: vec![],
+            missing: vec![],
+        };
+        for (id, key) in ids.into_iter().zip(keys) {
+            let row = found.iter().find(|row| {
+                row.0
+                    .as_object()
+                    .and_then(|x| HistoryController::value_of(x, &pk.name))
+                    .is_some_and(|value| *value == key)
+            });
+            match row {
+                Some(row) => result.rows.push(row.0.clone()),
+                None => result.missing.push(id),
+            }
+        }
+        let rules = FieldPermissionController::rules(&table, viewer)?;
+        result.rows.iter_mut().for_each(|row| rules.apply(row));
+        Ok(result)
+    }
+
     pub async fn create(
         table_name: String,
         values: Value,
         _query_params: QueryParams,
+        viewer: Viewer,
+    ) -> Result<Vec<GenericValue>, ReturnError> {
+        let table = Self::active_table(&table_name)?;
+        let connection = &mut establish_connection();
+        let created = connection.transaction(|conn| {
+            RowCountCache::check(conn, &table, 1)?;
+            Self::create_in(conn, &table, values, viewer)
+        })?;
+        RowCountCache::add(table.id, created.len() as i64);
+        Ok(created)
+    }
+
+    /// Inserts a row on `conn`, the caller checks the capacity in the same transaction
+    /// and updates the row count once it is committed
+    pub fn create_in(
+        conn: &mut PgConnection,
+        table: &Table,
+        values: Value,
+        viewer: Viewer,
     ) -> Result<Vec<GenericValue>, ReturnError> {
+        let user = viewer.user;
+        let rules = FieldPermissionController::rules(table, viewer)?;
         if !values.is_object() {
             return Err(ReturnError::without_value("Invalid data".to_owned()));
         }
+        let table_name = table.name.clone();
+        Self::check_managed(table, values.as_object().unwrap())?;
         let fields = FieldController::find_all_by_table_name(&table_name);
         if fields.is_err() {
             return Err(ReturnError::without_value("Table not found".to_owned()));
@@ -163,24 +402,343 @@ impl CustomController {
                 }
             }
         }
+        let pk = fields.iter().find(|x| x.is_primary_key).cloned();
         let fields = fields
             .iter()
             .filter(|x| !x.is_auto_increment)
             .map(|x| x.clone())
             .collect::<Vec<Field>>();
         let query = format!("INSERT INTO {}", table_name);
-        match mutate(table_name, values, query, fields) {
-            Ok(value) => Ok(value),
-            Err(err) => Err(err),
+        let managed = if table.audit {
+            vec![
+                ("created_by", Self::user_literal(user)),
+                ("updated_by", Self::user_literal(user)),
+            ]
+        } else {
+            vec![]
+        };
+        conn.transaction(|conn| {
+            let mut rows = mutate(conn, table_name, values, query, fields, managed, &rules)?;
+            if let Some(pk) = pk.as_ref() {
+                HistoryController::record(conn, table, pk, &rows, RowOperation::Insert, user)?;
+            }
+            rules.apply_all(&mut rows);
+            Ok(rows)
+        })
+    }
+
+    /// Rejects values that cannot be stored in their field, before anything is written
+    fn check_values(fields: &[Field], values: &Map<String, Value>) -> Result<(), ReturnError> {
+        for (key, value) in values {
+            let field = match fields
+                .iter()
+                .find(|x| x.name.to_lowercase() == key.to_lowercase())
+            {
+                Some(field) => field,
+                None => continue,
+            };
+            let field_type = FieldType::from_string(&field.field_type)?;
+            let valid = if value.is_null() {
+                !field.is_required
+            } else {
+                field_type.accepts(value)
+            };
+            if !valid {
+                return Err(ReturnError::new(
+                    format!(
+                        "Invalid value for field \"{}\", expected {}",
+                        field.name,
+                        field_type.to_string()
+                    ),
+                    value,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rows hidden by a policy are reported as missing, so their existence does not leak
+    fn check_reachable(
+        conn: &mut PgConnection,
+        table: &Table,
+        pk: &Field,
+        pk_value: &Map<String, Value>,
+        policy: Option<&str>,
+    ) -> Result<(), ReturnError> {
+        let policy = match policy {
+            Some(policy) => policy,
+            None => return Ok(()),
+        };
+        let query = format!(
+            "SELECT row_to_json(t) as row FROM {} t WHERE t.\"{}\" = $1 AND ({});",
+            table.name, pk.name, policy
+        );
+        add_params(
+            std::slice::from_ref(pk).iter(),
+            pk_value,
+            sql_query(query),
+            &FieldRules::NONE,
+        )?
+        .get_result::<GenericValue>(conn)
+        .optional()
+        .map_err(|err| ReturnError::new(err.to_string(), pk_value))?
+        .map(|_| ())
+        .ok_or(ReturnError::without_value(format!(
+            "Row not found in table \"{}\"",
+            table.name
+        )))
+    }
+
+    /// History snapshots are not filtered by row policies, so viewers bound by one cannot read them.
+    /// Returns the field rules the snapshots are shown with.
+    fn check_history(table_name: &str, viewer: Viewer) -> Result<FieldRules, ReturnError> {
+        let table = Self::active_table(table_name)?;
+        if RowPolicyController::restricts(&table, PolicyOperation::Read, viewer)? {
+            return Err(ReturnError::without_value(format!(
+                "History of table \"{}\" is restricted by row policies",
+                table_name
+            )));
         }
+        FieldPermissionController::rules(&table, viewer)
+    }
+
+    /// Versions of a row, with the field rules of the viewer applied to their data
+    pub fn find_versions(
+        table_name: String,
+        id: String,
+        viewer: Viewer,
+    ) -> Result<Vec<RowHistory>, ReturnError> {
+        let rules = Self::check_history(&table_name, viewer)?;
+        let mut versions = HistoryController::find_versions(table_name, id)?;
+        versions
+            .iter_mut()
+            .for_each(|version| rules.apply(&mut version.data));
+        Ok(versions)
+    }
+
+    /// Current row keyed by field names, the document patches are applied to
+    fn patch_document(
+        conn: &mut PgConnection,
+        table: &Table,
+        fields: &[Field],
+        pk: &Field,
+        pk_value: &Map<String, Value>,
+    ) -> Result<(GenericValue, Map<String, Value>), ReturnError> {
+        let query = format!(
+            "SELECT row_to_json(t) as row FROM {} t WHERE \"{}\" = $1 FOR UPDATE;",
+            table.name, pk.name
+        );
+        let row = add_params(
+            std::slice::from_ref(pk).iter(),
+            pk_value,
+            sql_query(query),
+            &FieldRules::NONE,
+        )?
+        .get_result::<GenericValue>(conn)
+        .optional()
+        .map_err(|err| ReturnError::new(err.to_string(), pk_value))?
+        .ok_or(ReturnError::without_value(format!(
+            "Row not found in table \"{}\"",
+            table.name
+        )))?;
+        let mut document = Map::new();
+        if let Some(values) = row.0.as_object() {
+            for field in fields {
+                let value = HistoryController::value_of(values, &field.name)
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                document.insert(field.name.clone(), value);
+            }
+        }
+        Ok((row, document))
+    }
+
+    /// Returns the updated row with its new `ETag`, patches are applied to the locked row
+    pub async fn update(
+        table_name: String,
+        id: String,
+        update: RowUpdate,
+        viewer: Viewer,
+        if_match: Option<String>,
+    ) -> Result<(GenericValue, Option<String>), ReturnError> {
+        let table = Self::active_table(&table_name)?;
+        let connection = &mut establish_connection();
+        Self::update_in(
+            connection,
+            &table,
+            &id,
+            &update,
+            viewer,
+            if_match.as_deref(),
+        )
+    }
+
+    /// Updates a row on `conn`, in a transaction nested in the caller's one if any.
+    /// The row has to match the update policies of the viewer before and after the change.
+    pub fn update_in(
+        conn: &mut PgConnection,
+        table: &Table,
+        id: &str,
+        update: &RowUpdate,
+        viewer: Viewer,
+        if_match: Option<&str>,
+    ) -> Result<(GenericValue, Option<String>), ReturnError> {
+        let user = viewer.user;
+        let rules = FieldPermissionController::rules(table, viewer)?;
+        let table_name = &table.name;
+        let fields = FieldController::find_all_by_table_name(table_name)
+            .map_err(|_| ReturnError::without_value("Table not found".to_owned()))?;
+        let pk = fields
+            .iter()
+            .find(|x| x.is_primary_key)
+            .ok_or(ReturnError::without_value(format!(
+                "Table \"{table_name}\" has no primary key"
+            )))?;
+        let mut pk_value = Map::new();
+        pk_value.insert(pk.name.clone(), Self::pk_value(pk, id)?);
+
+        let policy = RowPolicyController::predicate(table, PolicyOperation::Update, viewer)?;
+        conn.transaction(|conn| {
+            Self::check_reachable(conn, table, pk, &pk_value, policy.as_deref())?;
+            Etag::check_if_match(conn, table_name, pk, &pk_value, if_match)?;
+            let values = match update {
+                RowUpdate::Values(_) => update.changes(&Map::new())?,
+                _ => {
+                    let (mut row, mut document) =
+                        Self::patch_document(conn, table, &fields, pk, &pk_value)?;
+                    // Patches see the row as the viewer does
+                    rules.apply_map(&mut document);
+                    let values = update.changes(&document)?;
+                    if values.is_empty() {
+                        let etag = Etag::find(conn, table_name, pk, &pk_value, false)?;
+                        rules.apply(&mut row.0);
+                        return Ok((row, etag));
+                    }
+                    values
+                }
+            };
+            Self::check_managed(table, &values)?;
+            if let Some(key) = values
+                .keys()
+                .find(|x| x.to_lowercase() == pk.name.to_lowercase())
+            {
+                return Err(ReturnError::without_value(format!(
+                    "Field \"{key}\" is the primary key and cannot be changed"
+                )));
+            }
+            Self::check_values(&fields, &values)?;
+
+            let mut params = values.clone();
+            params.extend(pk_value.clone());
+            let mut sets = Vec::new();
+            let mut condition = String::new();
+            for (i, key) in params.keys().enumerate() {
+                if *key == pk.name {
+                    condition = format!("\"{}\" = ${}", key, i + 1);
+                } else {
+                    sets.push(format!("\"{}\" = ${}", key, i + 1));
+                }
+            }
+            if table.audit {
+                sets.push(format!("updated_by = {}", Self::user_literal(user)));
+            }
+            let query = format!(
+                "UPDATE {} SET {} WHERE {} RETURNING row_to_json({}.*) as row;",
+                table_name,
+                sets.join(", "),
+                condition,
+                table_name
+            );
+
+            let mut results = add_params(fields.iter(), &params, sql_query(query), &rules)?
+                .get_results::<GenericValue>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &values))?;
+            HistoryController::record(conn, table, pk, &results, RowOperation::Update, user)?;
+            rules.apply_all(&mut results);
+            let row = results
+                .into_iter()
+                .next()
+                .ok_or(ReturnError::without_value(format!(
+                    "Row \"{id}\" not found in table \"{table_name}\""
+                )))?;
+            if Self::check_reachable(conn, table, pk, &pk_value, policy.as_deref()).is_err() {
+                return Err(ReturnError::new(
+                    "Row policy does not allow this change".to_string(),
+                    &values,
+                ));
+            }
+            let etag = Etag::find(conn, table_name, pk, &pk_value, false)?;
+            Ok((row, etag))
+        })
+    }
+
+    pub async fn delete(
+        table_name: String,
+        id: String,
+        viewer: Viewer,
+        if_match: Option<String>,
+    ) -> Result<GenericValue, ReturnError> {
+        let table = Self::active_table(&table_name)?;
+        let connection = &mut establish_connection();
+        let deleted = Self::delete_in(connection, &table, &id, viewer, if_match.as_deref())?;
+        RowCountCache::add(table.id, -1);
+        Ok(deleted)
+    }
+
+    /// Deletes a row on `conn`, the caller updates the row count
+    pub fn delete_in(
+        conn: &mut PgConnection,
+        table: &Table,
+        id: &str,
+        viewer: Viewer,
+        if_match: Option<&str>,
+    ) -> Result<GenericValue, ReturnError> {
+        let user = viewer.user;
+        let rules = FieldPermissionController::rules(table, viewer)?;
+        let table_name = &table.name;
+        let pk = FieldController::find_pk(table_name)
+            .map_err(|_| ReturnError::without_value("Table not found".to_owned()))?;
+        let mut params = Map::new();
+        params.insert(pk.name.clone(), Self::pk_value(&pk, id)?);
+
+        let query = format!(
+            "DELETE FROM {} WHERE \"{}\" = $1 RETURNING row_to_json({}.*) as row;",
+            table_name, pk.name, table_name
+        );
+        let policy = RowPolicyController::predicate(table, PolicyOperation::Delete, viewer)?;
+        conn.transaction(|conn| {
+            Self::check_reachable(conn, table, &pk, &params, policy.as_deref())?;
+            Etag::check_if_match(conn, table_name, &pk, &params, if_match)?;
+            let mut results = add_params(
+                std::slice::from_ref(&pk).iter(),
+                &params,
+                sql_query(query),
+                &FieldRules::NONE,
+            )?
+            .get_results::<GenericValue>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), id))?;
+            // The snapshot of a deletion is the row as it was before
+            HistoryController::record(conn, table, &pk, &results, RowOperation::Delete, user)?;
+            rules.apply_all(&mut results);
+            results
+                .into_iter()
+                .next()
+                .ok_or(ReturnError::without_value(format!(
+                    "Row \"{id}\" not found in table \"{table_name}\""
+                )))
+        })
     }
 }
 
+/// `managed` columns are set to SQL literals next to the bound values
 fn mutate(
+    connection: &mut PgConnection,
     table_name: String,
     values: Value,
     query: String,
     fields: Vec<Field>,
+    managed: Vec<(&str, String)>,
+    rules: &FieldRules,
 ) -> Result<Vec<GenericValue>, ReturnError> {
     let fields = fields.iter();
     let values = values.as_object().unwrap();
@@ -197,13 +755,16 @@ fn mutate(
         columns.push_str(&format!("\"{}\"", key));
         placeholders.push_str(&format!("${}", i + 1));
     }
-    let connection = &mut establish_connection();
+    for (column, value) in managed {
+        columns.push_str(&format!(", {}", column));
+        placeholders.push_str(&format!(", {}", value));
+    }
     let query = format!(
         "{} ({}) VALUES ({}) RETURNING row_to_json({}.*) as row;",
         query, columns, placeholders, table_name
     );
     let query = sql_query(query);
-    let query = add_params(fields, values, query);
+    let query = add_params(fields, values, query, rules);
     if query.is_err() {
         return Err(query.err().unwrap());
     }
@@ -218,10 +779,13 @@ fn mutate(
     }
 }
 
-fn add_params<'a>(
+/// Binds the values of `key_value`, refusing the fields `rules` do not let the caller write.
+/// Primary keys are only bound to find rows and are not checked.
+pub(crate) fn add_params<'a>(
     fields: std::slice::Iter<'a, Field>,
     key_value: &'a Map<String, Value>,
     query: diesel::query_builder::SqlQuery,
+    rules: &FieldRules,
 ) -> Result<BoxedSqlQuery<'a, Pg, diesel::query_builder::SqlQuery>, ReturnError> {
     let mut query = query.into_boxed::<Pg>();
     let exist_in_fields: Vec<String> = fields
@@ -253,6 +817,9 @@ fn add_params<'a>(
         }
 
         let field = field.unwrap();
+        if !field.is_primary_key {
+            rules.check(&field.name)?;
+        }
         let field_type_str = field.field_type.clone();
         let field_type = FieldType::from_string(&field_type_str);
         if field_type.is_err() {
@@ -262,6 +829,35 @@ fn add_params<'a>(
             )));
         }
         let field_type = field_type.unwrap();
+        if value.is_null() {
+            query = match field_type {
+                FieldType::Varchar | FieldType::Text => {
+                    query.bind::<Nullable<VarChar>, Option<String>>(None)
+                }
+                FieldType::Integer => query.bind::<Nullable<Integer>, Option<i32>>(None),
+                FieldType::Float => query.bind::<Nullable<Float>, Option<f32>>(None),
+                FieldType::Boolean => query.bind::<Nullable<Bool>, Option<bool>>(None),
+                FieldType::Timestamp => {
+                    query.bind::<Nullable<Timestamp>, Option<NaiveDateTime>>(None)
+                }
+                FieldType::Date => query.bind::<Nullable<Date>, Option<NaiveDate>>(None),
+                FieldType::Binary => query.bind::<Nullable<Binary>, Option<Vec<u8>>>(None),
+                FieldType::Time => query.bind::<Nullable<Time>, Option<NaiveTime>>(None),
+                FieldType::Json => query.bind::<Nullable<Json>, Option<Value>>(None),
+            };
+            continue;
+        }
+        // The binds below unwrap the value as the type of the field
+        if !field_type.accepts(value) {
+            return Err(ReturnError::new(
+                format!(
+                    "Invalid value for field \"{}\", expected {}",
+                    field.name,
+                    field_type.to_string()
+                ),
+                value,
+            ));
+        }
         match field_type {
             FieldType::Varchar => {
                 query = query.bind::<VarChar, String>(value.as_str().unwrap().to_owned());
@@ -309,26 +905,50 @@ fn add_params<'a>(
     return Ok(query);
 }
 
-fn get_conditions(query_params: &QueryParams) -> Result<String, ReturnError> {
+/// Parenthesized `where` clause of the conditions with their values, bound as text
+/// and cast to the type of their field
+fn get_conditions(
+    query_params: &QueryParams,
+    fields: &[Field],
+) -> Result<(String, Vec<String>), ReturnError> {
     let conditions = &query_params.extra;
     if conditions.is_empty() {
         return Err(ReturnError::without_value(
             "At least one condition is required".to_owned(),
         ));
     }
-    let mut conditions_str = String::new();
-    if !conditions.is_empty() {
-        conditions_str.push_str("where ");
-        for (i, (key, value)) in conditions.iter().enumerate() {
-            if i > 0 {
-                conditions_str.push_str(" AND ");
-            }
-            conditions_str.push_str(&format!("{} = {}", key, value.as_str().unwrap()));
+    let mut conditions_str = String::from("where (");
+    let mut params = Vec::new();
+    for (i, (key, value)) in conditions.iter().enumerate() {
+        let field = fields
+            .iter()
+            .find(|x| x.name.eq_ignore_ascii_case(key))
+            .ok_or(ReturnError::without_value(format!(
+                "Column \"{}\" not found",
+                key
+            )))?;
+        if i > 0 {
+            conditions_str.push_str(" AND ");
         }
+        conditions_str.push_str(&format!(
+            "t.\"{}\" = CAST(${}::text AS {})",
+            field.name,
+            i + 1,
+            FieldType::from_string(&field.field_type)?.to_pg_type()
+        ));
+        params.push(match value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        });
     }
-    Ok(conditions_str)
+    conditions_str.push(')');
+    Ok((conditions_str, params))
 }
-fn resolve_rows(rows: Vec<tokio_postgres::Row>) -> Result<Vec<Value>, ReturnError> {
+/// Rows of the driver as JSON, shown with the field `rules` of the viewer
+fn resolve_rows(
+    rows: Vec<tokio_postgres::Row>,
+    rules: &FieldRules,
+) -> Result<Vec<Value>, ReturnError> {
     let mut results: Vec<Value> = vec![];
     for row in rows {
         let mut result: Value = Value::Object(Default::default());
@@ -426,6 +1046,7 @@ fn resolve_rows(rows: Vec<tokio_postgres::Row>) -> Result<Vec<Value>, ReturnErro
                 }
             }
         }
+        rules.apply(&mut result);
         results.push(result);
     }
     Ok(results)
@@ -0,0 +1,97 @@
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::Text;
+use serde_json::{Map, Value};
+
+use super::custom_controller::add_params;
+use crate::controller::fields::field_permission_controller::FieldRules;
+use crate::models::cms::fields_model::Field;
+use crate::routes::utils::reponses::ReturnError;
+
+/// Hash of a row aliased as `t`, computed by Postgres so every read path agrees on it
+pub const ETAG_SQL: &str = "md5(row_to_json(t)::text)";
+
+#[derive(QueryableByName)]
+struct RowEtag {
+    #[diesel(sql_type = Text)]
+    etag: String,
+}
+
+/// Entity tags of the rows served by the data API
+pub struct Etag;
+
+impl Etag {
+    pub fn quoted<S: AsRef<str>>(hash: S) -> String {
+        format!("\"{}\"", hash.as_ref())
+    }
+
+    /// Whether an `If-Match`/`If-None-Match` header names the tag, `weak` ignores `W/` prefixes
+    pub fn matches(header: &str, etag: &str, weak: bool) -> bool {
+        header.split(',').map(|x| x.trim()).any(|candidate| {
+            if candidate == "*" {
+                return true;
+            }
+            let candidate = match candidate.strip_prefix("W/") {
+                Some(_) if !weak => return false,
+                Some(stripped) => stripped,
+                None => candidate,
+            };
+            candidate == etag
+        })
+    }
+
+    /// Tag of the row whose primary key is in `pk_value`, `lock` holds the row until the transaction ends
+    pub fn find(
+        conn: &mut PgConnection,
+        table_name: &str,
+        pk: &Field,
+        pk_value: &Map<String, Value>,
+        lock: bool,
+    ) -> Result<Option<String>, ReturnError> {
+        let query = format!(
+            "SELECT {} AS etag FROM {} t WHERE \"{}\" = $1{};",
+            ETAG_SQL,
+            table_name,
+            pk.name,
+            if lock { " FOR UPDATE" } else { "" }
+        );
+        add_params(
+            std::slice::from_ref(pk).iter(),
+            pk_value,
+            sql_query(query),
+            &FieldRules::NONE,
+        )?
+        .get_result::<RowEtag>(conn)
+        .optional()
+        .map(|x| x.map(|x| Self::quoted(x.etag)))
+        .map_err(|err| ReturnError::new(err.to_string(), pk_value))
+    }
+
+    /// Rejects writes whose `If-Match` does not name the current version of the row
+    pub fn check_if_match(
+        conn: &mut PgConnection,
+        table_name: &str,
+        pk: &Field,
+        pk_value: &Map<String, Value>,
+        if_match: Option<&str>,
+    ) -> Result<(), ReturnError> {
+        let if_match = match if_match {
+            Some(if_match) => if_match,
+            None => return Ok(()),
+        };
+        match Self::find(conn, table_name, pk, pk_value, true)? {
+            Some(current) if Self::matches(if_match, &current, false) => Ok(()),
+            Some(current) => Err(ReturnError::new(
+                format!(
+                    "Precondition failed: the row in \"{}\" was modified",
+                    table_name
+                ),
+                current,
+            )),
+            None => Err(ReturnError::new(
+                format!("Row not found in table \"{}\"", table_name),
+                pk_value,
+            )),
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use diesel::insert_into;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Integer, Jsonb, Text, Timestamp};
+use serde_json::{Map, Value};
+
+use super::custom_controller::CustomController;
+use crate::controller::fields::field_controller::FieldController;
+use crate::controller::GenericValue;
+use crate::controller::QueryParams;
+use crate::controller::API_LIMIT;
+use crate::models::cms::custom::row_history_model::RowHistory;
+use crate::models::cms::fields_model::Field;
+use crate::models::cms::table_model::Table;
+use crate::models::db::catalog::Catalog;
+use crate::models::db::connection::establish_connection;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::row_history::dsl as history_dsl;
+use crate::utils::string_utils::to_snake_case;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RowOperation {
+    Insert,
+    Update,
+    Delete,
+    Restore,
+}
+
+impl RowOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RowOperation::Insert => "insert",
+            RowOperation::Update => "update",
+            RowOperation::Delete => "delete",
+            RowOperation::Restore => "restore",
+        }
+    }
+}
+
+/// Versioned snapshots of the rows of tables created with `history`.
+/// Rows written before history was enabled have no snapshot and are invisible to `asOf` reads.
+pub struct HistoryController;
+
+impl HistoryController {
+    /// Row keys are camel cased by `GenericValue`, fields keep the name they were created with
+    pub(crate) fn key_matches(key: &str, name: &str) -> bool {
+        key == name
+            || key.to_lowercase() == name.to_lowercase()
+            || to_snake_case(key) == to_snake_case(name)
+    }
+
+    pub(crate) fn value_of<'a>(row: &'a Map<String, Value>, name: &str) -> Option<&'a Value> {
+        row.iter()
+            .find(|(key, _)| Self::key_matches(key, name))
+            .map(|(_, value)| value)
+    }
+
+    fn row_id(row: &Value, pk: &Field) -> Result<String, ReturnError> {
+        let value = row
+            .as_object()
+            .and_then(|row| Self::value_of(row, &pk.name))
+            .ok_or(ReturnError::new(
+                format!("Row has no value for primary key \"{}\"", pk.name),
+                row,
+            ))?;
+        Ok(match value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        })
+    }
+
+    /// Stores a new version of every row, the caller runs it in the transaction of the write
+    pub fn record(
+        conn: &mut PgConnection,
+        table: &Table,
+        pk: &Field,
+        rows: &[GenericValue],
+        operation: RowOperation,
+        user: Option<i32>,
+    ) -> Result<(), ReturnError> {
+        if !table.history {
+            return Ok(());
+        }
+        for row in rows {
+            let row_id = Self::row_id(&row.0, pk)?;
+            // The write holds the row lock, so no other version can be taken meanwhile
+            let last = history_dsl::row_history
+                .filter(history_dsl::table_id.eq(table.id))
+                .filter(history_dsl::row_id.eq(&row_id))
+                .select(diesel::dsl::max(history_dsl::version))
+                .first::<Option<i32>>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &row_id))?;
+
+            insert_into(history_dsl::row_history)
+                .values((
+                    history_dsl::table_id.eq(table.id),
+                    history_dsl::row_id.eq(&row_id),
+                    history_dsl::version.eq(last.unwrap_or(0) + 1),
+                    history_dsl::operation.eq(operation.as_str()),
+                    history_dsl::data.eq(&row.0),
+                    history_dsl::changed_by.eq(user),
+                    history_dsl::changed_at.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &row_id))?;
+        }
+        Ok(())
+    }
+
+    /// Accepts RFC 3339 timestamps, naive ones (read as UTC) and plain dates (read as midnight)
+    pub fn parse_as_of(as_of: &str) -> Result<NaiveDateTime, ReturnError> {
+        if let Ok(value) = DateTime::parse_from_rfc3339(as_of) {
+            return Ok(value.naive_utc());
+        }
+        if let Ok(value) = as_of.parse::<NaiveDateTime>() {
+            return Ok(value);
+        }
+        if let Some(value) = as_of
+            .parse::<NaiveDate>()
+            .ok()
+            .and_then(|x| x.and_hms_opt(0, 0, 0))
+        {
+            return Ok(value);
+        }
+        Err(ReturnError::without_value(format!(
+            "Invalid asOf \"{}\"",
+            as_of
+        )))
+    }
+
+    fn history_table(table_name: &str) -> Result<Table, ReturnError> {
+        let table = CustomController::active_table(table_name)?;
+        if !table.history {
+            return Err(ReturnError::without_value(format!(
+                "Table \"{}\" does not keep history",
+                table_name
+            )));
+        }
+        Ok(table)
+    }
+
+    pub fn find_versions(table_name: String, id: String) -> Result<Vec<RowHistory>, ReturnError> {
+        let table = Self::history_table(&table_name)?;
+        let connection = &mut establish_connection();
+        history_dsl::row_history
+            .filter(history_dsl::table_id.eq(table.id))
+            .filter(history_dsl::row_id.eq(&id))
+            .order(history_dsl::version.desc())
+            .load::<RowHistory>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &id))
+    }
+
+    pub fn find_one_as_of(
+        table_name: String,
+        id: String,
+        as_of: &str,
+    ) -> Result<Value, ReturnError> {
+        let table = Self::history_table(&table_name)?;
+        let as_of = Self::parse_as_of(as_of)?;
+        let connection = &mut establish_connection();
+        let snapshot = history_dsl::row_history
+            .filter(history_dsl::table_id.eq(table.id))
+            .filter(history_dsl::row_id.eq(&id))
+            .filter(history_dsl::changed_at.le(as_of))
+            .order(history_dsl::version.desc())
+            .first::<RowHistory>(connection)
+            .optional()
+            .map_err(|err| ReturnError::new(err.to_string(), &id))?;
+
+        match snapshot {
+            Some(snapshot) if snapshot.operation != RowOperation::Delete.as_str() => {
+                Ok(snapshot.data)
+            }
+            _ => Err(ReturnError::without_value(format!(
+                "Row \"{}\" not found in table \"{}\" as of {}",
+                id, table_name, as_of
+            ))),
+        }
+    }
+
+    pub fn find_all_as_of(
+        table_name: String,
+        mut query_params: QueryParams,
+        as_of: &str,
+    ) -> Result<Vec<GenericValue>, ReturnError> {
+        let table = Self::history_table(&table_name)?;
+        let as_of = Self::parse_as_of(as_of)?;
+        let extras = query_params.get_extras();
+
+        // The latest version of every row is picked first, so deletions and filters
+        // apply to that version only and the page is cut by the database
+        let mut conditions = vec!["h.operation <> 'delete'".to_string()];
+        let mut binds = Vec::new();
+        for (key, expected) in &extras {
+            let (name, expected) = Self::filter_value(key, expected);
+            let matches = if QueryParams::is_array(key) {
+                format!(
+                    "jsonb_typeof(e.value) = 'array' AND NOT EXISTS (SELECT 1 FROM jsonb_array_elements_text(${}::jsonb) x(v) WHERE x.v NOT IN (SELECT jsonb_array_elements_text(e.value)))",
+                    binds.len() + 4
+                )
+            } else {
+                format!("e.value #>> '{{}}' = ${}", binds.len() + 4)
+            };
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM jsonb_each(h.data) e WHERE {} AND {})",
+                Self::key_condition(binds.len() + 3),
+                matches
+            ));
+            binds.push(name);
+            binds.push(expected);
+        }
+        let query = format!(
+            "SELECT h.data as row FROM (SELECT DISTINCT ON (row_id) row_id, operation, data FROM row_history WHERE table_id = $1 AND changed_at <= $2 ORDER BY row_id, version DESC) h WHERE {} ORDER BY h.row_id LIMIT ${} OFFSET ${};",
+            conditions.join(" AND "),
+            binds.len() + 3,
+            binds.len() + 4
+        );
+
+        let mut query = sql_query(query)
+            .into_boxed::<Pg>()
+            .bind::<Integer, _>(table.id)
+            .bind::<Timestamp, _>(as_of);
+        for value in binds {
+            query = query.bind::<Text, _>(value);
+        }
+        let connection = &mut establish_connection();
+        query
+            .bind::<BigInt, _>(query_params.limit.unwrap_or(API_LIMIT))
+            .bind::<BigInt, _>(query_params.offset.unwrap_or(0))
+            .get_results::<GenericValue>(connection)
+            .map_err(|err| ReturnError::without_value(err.to_string()))
+    }
+
+    /// Snapshot keys are camel cased, so names are compared without case and underscores,
+    /// as `key_matches` does
+    fn key_condition(bind: usize) -> String {
+        format!(
+            "replace(lower(e.key), '_', '') = replace(lower(${}), '_', '')",
+            bind
+        )
+    }
+
+    /// Filter values come as query strings, so they are compared by their text
+    fn filter_value(key: &str, expected: &Value) -> (String, String) {
+        let expected = match expected {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        (QueryParams::get_array_key(key), expected)
+    }
+
+    /// Writes a previous version back, recreating the row if it was deleted since
+    pub fn restore(
+        table_name: String,
+        id: String,
+        version: i32,
+        user: Option<i32>,
+    ) -> Result<GenericValue, ReturnError> {
+        let table = Self::history_table(&table_name)?;
+        let pk = FieldController::find_pk(&table_name)?;
+        let connection = &mut establish_connection();
+
+        connection.transaction(|conn| {
+            let snapshot = history_dsl::row_history
+                .filter(history_dsl::table_id.eq(table.id))
+                .filter(history_dsl::row_id.eq(&id))
+                .filter(history_dsl::version.eq(version))
+                .first::<RowHistory>(conn)
+                .optional()
+                .map_err(|err| ReturnError::new(err.to_string(), &id))?
+                .ok_or(ReturnError::without_value(format!(
+                    "Version {} of row \"{}\" not found",
+                    version, id
+                )))?;
+            if snapshot.operation == RowOperation::Delete.as_str() {
+                return Err(ReturnError::new(
+                    format!("Version {} of row \"{}\" is a deletion", version, id),
+                    &snapshot,
+                ));
+            }
+
+            // Snapshot keys are mapped back to the columns that still exist
+            let (schema, name) = Catalog::split_name(&table.name);
+            let columns = Catalog::columns(conn, schema, Some(name))?;
+            let mut data = Map::new();
+            for (key, value) in snapshot.data.as_object().into_iter().flatten() {
+                if let Some(column) = columns.iter().find(|x| {
+                    x.matches_field(key) || Self::key_matches(&x.column_name, key)
+                }) {
+                    data.insert(column.column_name.clone(), value.clone());
+                }
+            }
+            if table.audit {
+                data.insert("updated_by".to_string(), Value::from(user));
+                data.insert(
+                    "updated_at".to_string(),
+                    Value::from(chrono::Utc::now().naive_utc().to_string()),
+                );
+            }
+            let pk_column = columns
+                .iter()
+                .find(|x| x.matches_field(&pk.name))
+                .ok_or(ReturnError::without_value(format!(
+                    "Column for field \"{}\" not found",
+                    pk.name
+                )))?;
+
+            let names: Vec<&String> = data.keys().collect();
+            let column_list = names
+                .iter()
+                .map(|x| x.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ");
+            let updates = names
+                .iter()
+                .filter(|x| ***x != pk_column.column_name)
+                // Creation audit columns describe the row, not the version being restored
+                .filter(|x| !(table.audit && ["created_at", "created_by"].contains(&x.as_str())))
+                .map(|x| format!("{x} = EXCLUDED.{x}"))
+                .collect::<Vec<String>>()
+                .join(", ");
+            let conflict = if updates.is_empty() {
+                "DO NOTHING".to_string()
+            } else {
+                format!("DO UPDATE SET {}", updates)
+            };
+            let query = format!(
+                "INSERT INTO {table} ({columns}) SELECT {columns} FROM jsonb_populate_record(NULL::{table}, $1) ON CONFLICT ({pk}) {conflict} RETURNING row_to_json({table}.*) as row;",
+                table = table.name,
+                columns = column_list,
+                pk = pk_column.column_name,
+                conflict = conflict,
+            );
+            let row = sql_query(&query)
+                .bind::<Jsonb, _>(Value::Object(data.clone()))
+                .get_result::<GenericValue>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &data))?;
+
+            Self::record(
+                conn,
+                &table,
+                &pk,
+                std::slice::from_ref(&row),
+                RowOperation::Restore,
+                user,
+            )?;
+            Ok(row)
+        })
+    }
+}
@@ -1,2 +1,7 @@
+pub mod batch_controller;
 pub mod custom_controller;
+pub mod etag;
+pub mod history_controller;
+pub mod patch;
+pub mod row_count;
 pub mod structs;
\ No newline at end of file
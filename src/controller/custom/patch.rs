@@ -0,0 +1,635 @@
+use serde_json::{Map, Value};
+
+use crate::routes::utils::reponses::ReturnError;
+
+pub const MERGE_PATCH: &str = "application/merge-patch+json";
+pub const JSON_PATCH: &str = "application/json-patch+json";
+
+/// Body of a row update, chosen by the request `Content-Type`
+#[derive(Debug, Clone)]
+pub enum RowUpdate {
+    /// Plain object, its keys replace whole columns
+    Values(Value),
+    /// RFC 7396 document merged into the row
+    MergePatch(Value),
+    /// RFC 6902 operations applied to the row
+    JsonPatch(Value),
+}
+
+impl RowUpdate {
+    pub fn from_content_type(content_type: Option<&str>, body: Value) -> Self {
+        let mime = content_type
+            .and_then(|x| x.split(';').next())
+            .map(|x| x.trim().to_lowercase());
+        match mime.as_deref() {
+            Some(MERGE_PATCH) => RowUpdate::MergePatch(body),
+            Some(JSON_PATCH) => RowUpdate::JsonPatch(body),
+            _ => RowUpdate::Values(body),
+        }
+    }
+
+    /// Applies the update to the current row and returns the columns whose value changed.
+    /// Columns removed by the patch are set to `null`.
+    pub fn changes(&self, current: &Map<String, Value>) -> Result<Map<String, Value>, ReturnError> {
+        let document = match self {
+            RowUpdate::Values(values) => {
+                return match values.as_object() {
+                    Some(values) if !values.is_empty() => Ok(values.clone()),
+                    _ => Err(ReturnError::without_value("Invalid data".to_owned())),
+                }
+            }
+            RowUpdate::MergePatch(patch) => {
+                if !patch.is_object() {
+                    return Err(ReturnError::new(
+                        "Merge patch must be an object".to_owned(),
+                        patch,
+                    ));
+                }
+                let mut document = Value::Object(current.clone());
+                merge_patch(&mut document, patch);
+                document
+            }
+            RowUpdate::JsonPatch(operations) => {
+                let mut document = Value::Object(current.clone());
+                json_patch(&mut document, operations)?;
+                document
+            }
+        };
+
+        let patched = match document {
+            Value::Object(patched) => patched,
+            document => {
+                return Err(ReturnError::new(
+                    "Patched row must be an object".to_owned(),
+                    document,
+                ))
+            }
+        };
+        let mut changes = Map::new();
+        for (key, value) in &patched {
+            if current.get(key) != Some(value) {
+                changes.insert(key.clone(), value.clone());
+            }
+        }
+        for key in current.keys() {
+            if !patched.contains_key(key) && !current[key].is_null() {
+                changes.insert(key.clone(), Value::Null);
+            }
+        }
+        Ok(changes)
+    }
+}
+
+/// RFC 7396, `null` members remove the key from the target
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let patch = match patch.as_object() {
+        Some(patch) => patch,
+        None => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+fn patch_error<S: AsRef<str>>(message: S, operation: &Value) -> ReturnError {
+    ReturnError::new(format!("Invalid patch: {}", message.as_ref()), operation)
+}
+
+/// Splits a JSON pointer (RFC 6901) into its unescaped tokens
+fn pointer_tokens(pointer: &str) -> Result<Vec<String>, String> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    match pointer.strip_prefix('/') {
+        Some(rest) => Ok(rest
+            .split('/')
+            .map(|x| x.replace("~1", "/").replace("~0", "~"))
+            .collect()),
+        None => Err(format!("\"{}\" is not a JSON pointer", pointer)),
+    }
+}
+
+fn array_index(token: &str, len: usize, allow_end: bool) -> Result<usize, String> {
+    if allow_end && token == "-" {
+        return Ok(len);
+    }
+    // Leading zeros and signs are not valid array indexes
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+        return Err(format!("\"{}\" is not an array index", token));
+    }
+    let index = token
+        .parse::<usize>()
+        .map_err(|_| format!("\"{}\" is not an array index", token))?;
+    let max = if allow_end {
+        len
+    } else {
+        len.saturating_sub(1)
+    };
+    if index > max || (!allow_end && len == 0) {
+        return Err(format!("index {} is out of bounds", index));
+    }
+    Ok(index)
+}
+
+/// Parent of the value at `tokens` and the last token
+fn parent_mut<'a>(
+    document: &'a mut Value,
+    tokens: &'a [String],
+) -> Result<(&'a mut Value, &'a str), String> {
+    let (last, parents) = match tokens.split_last() {
+        Some(split) => split,
+        None => return Err("the root cannot be the target".to_owned()),
+    };
+    let mut current = document;
+    for token in parents {
+        current = match current {
+            Value::Object(map) => map
+                .get_mut(token)
+                .ok_or(format!("path \"{}\" does not exist", token))?,
+            Value::Array(items) => {
+                let index = array_index(token, items.len(), false)?;
+                &mut items[index]
+            }
+            _ => return Err(format!("\"{}\" is not a container", token)),
+        };
+    }
+    Ok((current, last.as_str()))
+}
+
+fn get<'a>(document: &'a Value, tokens: &[String]) -> Result<&'a Value, String> {
+    let mut current = document;
+    for token in tokens {
+        current = match current {
+            Value::Object(map) => map
+                .get(token)
+                .ok_or(format!("path \"{}\" does not exist", token))?,
+            Value::Array(items) => &items[array_index(token, items.len(), false)?],
+            _ => return Err(format!("\"{}\" is not a container", token)),
+        };
+    }
+    Ok(current)
+}
+
+fn add(document: &mut Value, tokens: &[String], value: Value) -> Result<(), String> {
+    let (parent, last) = parent_mut(document, tokens)?;
+    match parent {
+        Value::Object(map) => {
+            map.insert(last.to_owned(), value);
+        }
+        Value::Array(items) => {
+            let index = array_index(last, items.len(), true)?;
+            items.insert(index, value);
+        }
+        _ => return Err(format!("\"{}\" is not a container", last)),
+    }
+    Ok(())
+}
+
+fn remove(document: &mut Value, tokens: &[String]) -> Result<Value, String> {
+    let (parent, last) = parent_mut(document, tokens)?;
+    match parent {
+        Value::Object(map) => map
+            .remove(last)
+            .ok_or(format!("path \"{}\" does not exist", last)),
+        Value::Array(items) => {
+            let index = array_index(last, items.len(), false)?;
+            Ok(items.remove(index))
+        }
+        _ => Err(format!("\"{}\" is not a container", last)),
+    }
+}
+
+/// RFC 6902, the operations are applied in order and the document is left unchanged if one fails
+pub fn json_patch(document: &mut Value, operations: &Value) -> Result<(), ReturnError> {
+    let operations = operations
+        .as_array()
+        .ok_or(patch_error("expected an array of operations", operations))?;
+    let mut patched = document.clone();
+    for operation in operations {
+        let op = operation
+            .get("op")
+            .and_then(|x| x.as_str())
+            .ok_or(patch_error("missing \"op\"", operation))?;
+        let path = operation
+            .get("path")
+            .and_then(|x| x.as_str())
+            .ok_or(patch_error("missing \"path\"", operation))?;
+        let path = pointer_tokens(path).map_err(|err| patch_error(err, operation))?;
+        let value = || {
+            operation
+                .get("value")
+                .cloned()
+                .ok_or(patch_error("missing \"value\"", operation))
+        };
+        let from = || {
+            operation
+                .get("from")
+                .and_then(|x| x.as_str())
+                .ok_or("missing \"from\"".to_owned())
+                .and_then(pointer_tokens)
+                .map_err(|err| patch_error(err, operation))
+        };
+
+        let result = match op {
+            "add" => add(&mut patched, &path, value()?),
+            "remove" => remove(&mut patched, &path).map(|_| ()),
+            "replace" => {
+                let value = value()?;
+                get(&patched, &path)
+                    .map(|_| ())
+                    .and_then(|_| remove(&mut patched, &path))
+                    .and_then(|_| add(&mut patched, &path, value))
+            }
+            "move" => {
+                let from = from()?;
+                if path.starts_with(&from) && path != from {
+                    return Err(patch_error("cannot move a value into itself", operation));
+                }
+                remove(&mut patched, &from).and_then(|value| add(&mut patched, &path, value))
+            }
+            "copy" => {
+                let from = from()?;
+                get(&patched, &from)
+                    .cloned()
+                    .and_then(|value| add(&mut patched, &path, value))
+            }
+            "test" => {
+                let expected = value()?;
+                get(&patched, &path).and_then(|actual| {
+                    if *actual == expected {
+                        Ok(())
+                    } else {
+                        Err("test failed".to_owned())
+                    }
+                })
+            }
+            op => Err(format!("unknown operation \"{}\"", op)),
+        };
+        result.map_err(|err| patch_error(err, operation))?;
+    }
+    *document = patched;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn patched(document: Value, operations: Value) -> Result<Value, String> {
+        let mut document = document;
+        json_patch(&mut document, &operations)
+            .map(|_| document)
+            .map_err(|err| err.to_string())
+    }
+
+    fn merged(target: Value, patch: Value) -> Value {
+        let mut target = target;
+        merge_patch(&mut target, &patch);
+        target
+    }
+
+    // RFC 6902, appendix A
+
+    #[test]
+    fn adds_an_object_member() {
+        let result = patched(
+            json!({ "foo": "bar" }),
+            json!([{ "op": "add", "path": "/baz", "value": "qux" }]),
+        );
+        assert_eq!(result, Ok(json!({ "baz": "qux", "foo": "bar" })));
+    }
+
+    #[test]
+    fn adds_an_array_element() {
+        let result = patched(
+            json!({ "foo": ["bar", "baz"] }),
+            json!([{ "op": "add", "path": "/foo/1", "value": "qux" }]),
+        );
+        assert_eq!(result, Ok(json!({ "foo": ["bar", "qux", "baz"] })));
+    }
+
+    #[test]
+    fn removes_an_object_member() {
+        let result = patched(
+            json!({ "baz": "qux", "foo": "bar" }),
+            json!([{ "op": "remove", "path": "/baz" }]),
+        );
+        assert_eq!(result, Ok(json!({ "foo": "bar" })));
+    }
+
+    #[test]
+    fn removes_an_array_element() {
+        let result = patched(
+            json!({ "foo": ["bar", "qux", "baz"] }),
+            json!([{ "op": "remove", "path": "/foo/1" }]),
+        );
+        assert_eq!(result, Ok(json!({ "foo": ["bar", "baz"] })));
+    }
+
+    #[test]
+    fn replaces_a_value() {
+        let result = patched(
+            json!({ "baz": "qux", "foo": "bar" }),
+            json!([{ "op": "replace", "path": "/baz", "value": "boo" }]),
+        );
+        assert_eq!(result, Ok(json!({ "baz": "boo", "foo": "bar" })));
+    }
+
+    #[test]
+    fn moves_a_value() {
+        let result = patched(
+            json!({
+                "foo": { "bar": "baz", "waldo": "fred" },
+                "qux": { "corge": "grault" }
+            }),
+            json!([{ "op": "move", "from": "/foo/waldo", "path": "/qux/thud" }]),
+        );
+        assert_eq!(
+            result,
+            Ok(json!({
+                "foo": { "bar": "baz" },
+                "qux": { "corge": "grault", "thud": "fred" }
+            }))
+        );
+    }
+
+    #[test]
+    fn moves_an_array_element() {
+        let result = patched(
+            json!({ "foo": ["all", "grass", "cows", "eat"] }),
+            json!([{ "op": "move", "from": "/foo/1", "path": "/foo/3" }]),
+        );
+        assert_eq!(
+            result,
+            Ok(json!({ "foo": ["all", "cows", "eat", "grass"] }))
+        );
+    }
+
+    #[test]
+    fn tests_a_value() {
+        let result = patched(
+            json!({ "baz": "qux", "foo": ["a", 2, "c"] }),
+            json!([
+                { "op": "test", "path": "/baz", "value": "qux" },
+                { "op": "test", "path": "/foo/1", "value": 2 }
+            ]),
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn fails_a_test() {
+        let result = patched(
+            json!({ "baz": "qux" }),
+            json!([{ "op": "test", "path": "/baz", "value": "bar" }]),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn adds_a_nested_member() {
+        let result = patched(
+            json!({ "foo": "bar" }),
+            json!([{ "op": "add", "path": "/child", "value": { "grandchild": {} } }]),
+        );
+        assert_eq!(
+            result,
+            Ok(json!({ "foo": "bar", "child": { "grandchild": {} } }))
+        );
+    }
+
+    #[test]
+    fn ignores_unrecognized_members() {
+        let result = patched(
+            json!({ "foo": "bar" }),
+            json!([{ "op": "add", "path": "/baz", "value": "qux", "xyz": 123 }]),
+        );
+        assert_eq!(result, Ok(json!({ "foo": "bar", "baz": "qux" })));
+    }
+
+    #[test]
+    fn fails_to_add_to_a_missing_target() {
+        let result = patched(
+            json!({ "foo": "bar" }),
+            json!([{ "op": "add", "path": "/baz/bat", "value": "qux" }]),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn unescapes_pointers() {
+        let result = patched(
+            json!({ "/": 9, "~1": 10 }),
+            json!([{ "op": "test", "path": "/~01", "value": 10 }]),
+        );
+        assert!(result.is_ok());
+        let result = patched(
+            json!({ "a/b": 1 }),
+            json!([{ "op": "replace", "path": "/a~1b", "value": 2 }]),
+        );
+        assert_eq!(result, Ok(json!({ "a/b": 2 })));
+    }
+
+    #[test]
+    fn compares_strings_and_numbers_strictly() {
+        let result = patched(
+            json!({ "/": 9, "~1": 10 }),
+            json!([{ "op": "test", "path": "/~01", "value": "10" }]),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn adds_an_array_value() {
+        let result = patched(
+            json!({ "foo": ["bar"] }),
+            json!([{ "op": "add", "path": "/foo/-", "value": ["abc", "def"] }]),
+        );
+        assert_eq!(result, Ok(json!({ "foo": ["bar", ["abc", "def"]] })));
+    }
+
+    #[test]
+    fn copies_a_value() {
+        let result = patched(
+            json!({ "foo": { "bar": 1 } }),
+            json!([{ "op": "copy", "from": "/foo", "path": "/baz" }]),
+        );
+        assert_eq!(
+            result,
+            Ok(json!({ "foo": { "bar": 1 }, "baz": { "bar": 1 } }))
+        );
+    }
+
+    #[test]
+    fn refuses_invalid_array_indexes() {
+        for path in ["/foo/01", "/foo/-1", "/foo/2", "/foo/x"] {
+            let result = patched(
+                json!({ "foo": ["a", "b"] }),
+                json!([{ "op": "replace", "path": path, "value": "c" }]),
+            );
+            assert!(result.is_err(), "{} was accepted", path);
+        }
+        let result = patched(
+            json!({ "foo": ["a", "b"] }),
+            json!([{ "op": "add", "path": "/foo/2", "value": "c" }]),
+        );
+        assert_eq!(result, Ok(json!({ "foo": ["a", "b", "c"] })));
+    }
+
+    #[test]
+    fn refuses_to_move_a_value_into_itself() {
+        let result = patched(
+            json!({ "foo": { "bar": 1 } }),
+            json!([{ "op": "move", "from": "/foo", "path": "/foo/bar/baz" }]),
+        );
+        assert!(result.is_err());
+        let result = patched(
+            json!({ "foo": 1 }),
+            json!([{ "op": "move", "from": "/foo", "path": "/foo" }]),
+        );
+        assert_eq!(result, Ok(json!({ "foo": 1 })));
+    }
+
+    #[test]
+    fn leaves_the_document_unchanged_when_an_operation_fails() {
+        let mut document = json!({ "foo": "bar" });
+        let result = json_patch(
+            &mut document,
+            &json!([
+                { "op": "add", "path": "/baz", "value": "qux" },
+                { "op": "remove", "path": "/missing" }
+            ]),
+        );
+        assert!(result.is_err());
+        assert_eq!(document, json!({ "foo": "bar" }));
+    }
+
+    #[test]
+    fn refuses_malformed_operations() {
+        for operations in [
+            json!({ "op": "add", "path": "/a", "value": 1 }),
+            json!([{ "path": "/a", "value": 1 }]),
+            json!([{ "op": "add", "value": 1 }]),
+            json!([{ "op": "add", "path": "/a" }]),
+            json!([{ "op": "add", "path": "a", "value": 1 }]),
+            json!([{ "op": "move", "path": "/a" }]),
+            json!([{ "op": "frobnicate", "path": "/a" }]),
+        ] {
+            assert!(patched(json!({ "a": 0 }), operations.clone()).is_err());
+        }
+    }
+
+    // RFC 7396, appendix A
+
+    #[test]
+    fn merges_the_rfc_examples() {
+        let cases = [
+            (json!({"a":"b"}), json!({"a":"c"}), json!({"a":"c"})),
+            (json!({"a":"b"}), json!({"b":"c"}), json!({"a":"b","b":"c"})),
+            (json!({"a":"b"}), json!({"a":null}), json!({})),
+            (
+                json!({"a":"b","b":"c"}),
+                json!({"a":null}),
+                json!({"b":"c"}),
+            ),
+            (json!({"a":["b"]}), json!({"a":"c"}), json!({"a":"c"})),
+            (json!({"a":"c"}), json!({"a":["b"]}), json!({"a":["b"]})),
+            (
+                json!({"a":{"b":"c"}}),
+                json!({"a":{"b":"d","c":null}}),
+                json!({"a":{"b":"d"}}),
+            ),
+            (json!({"a":[{"b":"c"}]}), json!({"a":[1]}), json!({"a":[1]})),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a":"b"}), json!(["c"]), json!(["c"])),
+            (json!({"a":"foo"}), json!(null), json!(null)),
+            (json!({"a":"foo"}), json!("bar"), json!("bar")),
+            (json!({"e":null}), json!({"a":1}), json!({"e":null,"a":1})),
+            (json!([1, 2]), json!({"a":"b","c":null}), json!({"a":"b"})),
+            (
+                json!({}),
+                json!({"a":{"bb":{"ccc":null}}}),
+                json!({"a":{"bb":{}}}),
+            ),
+        ];
+        for (target, patch, expected) in cases {
+            assert_eq!(
+                merged(target.clone(), patch.clone()),
+                expected,
+                "{} + {}",
+                target,
+                patch
+            );
+        }
+    }
+
+    // Row updates
+
+    fn row() -> Map<String, Value> {
+        json!({ "id": 1, "title": "a", "tags": ["x"], "body": "b" })
+            .as_object()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn picks_the_update_from_the_content_type() {
+        let body = json!({});
+        assert!(matches!(
+            RowUpdate::from_content_type(
+                Some("application/merge-patch+json; charset=utf-8"),
+                body.clone()
+            ),
+            RowUpdate::MergePatch(_)
+        ));
+        assert!(matches!(
+            RowUpdate::from_content_type(Some("Application/JSON-Patch+JSON"), body.clone()),
+            RowUpdate::JsonPatch(_)
+        ));
+        assert!(matches!(
+            RowUpdate::from_content_type(None, body),
+            RowUpdate::Values(_)
+        ));
+    }
+
+    #[test]
+    fn reports_changed_and_removed_columns() {
+        let update = RowUpdate::MergePatch(json!({ "title": "c", "body": null, "id": 1 }));
+        let changes = update.changes(&row()).unwrap();
+        assert_eq!(
+            Value::Object(changes),
+            json!({ "title": "c", "body": null })
+        );
+
+        let update = RowUpdate::JsonPatch(json!([
+            { "op": "add", "path": "/tags/-", "value": "y" },
+            { "op": "remove", "path": "/body" }
+        ]));
+        let changes = update.changes(&row()).unwrap();
+        assert_eq!(
+            Value::Object(changes),
+            json!({ "tags": ["x", "y"], "body": null })
+        );
+    }
+
+    #[test]
+    fn refuses_updates_that_are_not_objects() {
+        assert!(RowUpdate::Values(json!({})).changes(&row()).is_err());
+        assert!(RowUpdate::MergePatch(json!(["a"])).changes(&row()).is_err());
+        let replace_root =
+            RowUpdate::JsonPatch(json!([{ "op": "replace", "path": "", "value": 1 }]));
+        assert!(replace_root.changes(&row()).is_err());
+    }
+}
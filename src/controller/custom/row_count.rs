@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
+use dotenvy::dotenv;
+
+use crate::models::cms::table_model::Table;
+use crate::models::db::locks::lock_table;
+use crate::routes::utils::reponses::ReturnError;
+
+pub const DEFAULT_TTL_SECS: u64 = 60;
+
+/// Row counts per table id, with the moment they were taken
+static ROW_COUNTS: LazyLock<Mutex<HashMap<i32, (i64, Instant)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(QueryableByName)]
+struct RowCount {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// Keeps `tables.capacity` checks from running a `COUNT(*)` on every insert.
+/// Counts are cached for `ROW_COUNT_CACHE_TTL_SECS` and only recounted exactly near the limit.
+pub struct RowCountCache;
+
+impl RowCountCache {
+    fn ttl() -> Duration {
+        dotenv().ok();
+        let secs = env::var("ROW_COUNT_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|x| x.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_TTL_SECS);
+        Duration::from_secs(secs)
+    }
+
+    fn cached(table_id: i32) -> Option<i64> {
+        let counts = ROW_COUNTS.lock().ok()?;
+        counts
+            .get(&table_id)
+            .filter(|(_, at)| at.elapsed() < Self::ttl())
+            .map(|(count, _)| *count)
+    }
+
+    fn count(conn: &mut PgConnection, table: &Table) -> Result<i64, ReturnError> {
+        let count = sql_query(format!("SELECT COUNT(*) AS count FROM {}", table.name))
+            .get_result::<RowCount>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), &table.name))?
+            .count;
+
+        if let Ok(mut counts) = ROW_COUNTS.lock() {
+            counts.insert(table.id, (count, Instant::now()));
+        }
+        Ok(count)
+    }
+
+    /// Fails when inserting `adding` rows would push the table past its capacity. Runs in the
+    /// transaction of the insert: tables with a capacity are locked until it commits, so
+    /// concurrent inserts cannot both pass the check. The caller then calls `add` before committing.
+    pub fn check(conn: &mut PgConnection, table: &Table, adding: i64) -> Result<(), ReturnError> {
+        let capacity = match table.capacity {
+            Some(capacity) => capacity as i64,
+            None => return Ok(()),
+        };
+        lock_table(conn, &table.name)?;
+
+        let mut count = match Self::cached(table.id) {
+            Some(count) => count,
+            None => Self::count(conn, table)?,
+        };
+        // Within 10% of the limit the cached value is not trusted anymore
+        let margin = (capacity / 10).max(1);
+        if count + adding > capacity - margin {
+            count = Self::count(conn, table)?;
+        }
+
+        if count + adding > capacity {
+            return Err(ReturnError::new(
+                format!(
+                    "Quota exceeded: table \"{}\" is limited to {} rows",
+                    table.name, capacity
+                ),
+                count,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Keeps the cached count in step with the rows written through the API
+    pub fn add(table_id: i32, rows: i64) {
+        if let Ok(mut counts) = ROW_COUNTS.lock() {
+            if let Some((count, _)) = counts.get_mut(&table_id) {
+                *count += rows;
+            }
+        }
+    }
+}
@@ -13,13 +13,17 @@ use super::structs::UpdateField;
 use super::utils::set_table_for_vec;
 use crate::controller::tables::table_controller::TableController;
 use crate::controller::Controller;
+use crate::controller::check_version;
 use crate::controller::QueryParams;
+use crate::controller::Versioned;
 use crate::controller::API_LIMIT;
 use crate::models::db::connection::establish_connection;
+use crate::models::db::locks::lock_table;
 use crate::models::cms::fields_model::Field;
 
 use crate::routes::utils::reponses::ReturnError;
 use crate::schema::fields::dsl as fields_dsl;
+use crate::schema::tables::dsl as tables_dsl;
 use crate::utils::sql::FieldQueryBuilder;
 
 pub struct FieldController;
@@ -52,6 +56,9 @@ impl FieldController {
 
         let transaction: std::result::Result<Vec<Field>, ReturnError> =
             connection.transaction(|conn| {
+                lock_table(conn, table_name.as_ref())?;
+                TableController::bump_version(conn, table_id)?;
+
                 let query = insert_into(fields_dsl::fields)
                     .values(&fields)
                     .get_results::<Field>(conn);
@@ -313,23 +320,29 @@ impl FieldController {
     }
 
     pub fn delete_field(table_id: i32, id: i32) -> Result<Field, ReturnError> {
+        let table_name = TableController::find(table_id)?.name;
         let connection = &mut establish_connection();
-        match delete(fields_dsl::fields)
-            .filter(fields_dsl::id.eq(&id))
-            .filter(fields_dsl::table_id.eq(table_id))
-            .get_result::<Field>(connection)
-        {
-            Ok(res) => {
-                return Ok(res); // if Successful, return the deleted data
-            }
-            Err(err) => {
-                return Err(ReturnError {
-                    error_msg: err.to_string(),
-                    values: Some(id.into()),
+        connection.transaction(|conn| {
+            lock_table(conn, &table_name)?;
+            TableController::bump_version(conn, table_id)?;
+
+            match delete(fields_dsl::fields)
+                .filter(fields_dsl::id.eq(&id))
+                .filter(fields_dsl::table_id.eq(table_id))
+                .get_result::<Field>(conn)
+            {
+                Ok(res) => {
+                    return Ok(res); // if Successful, return the deleted data
+                }
+                Err(err) => {
+                    return Err(ReturnError {
+                        error_msg: err.to_string(),
+                        values: Some(id.into()),
+                    }
+                    .into());
                 }
-                .into());
             }
-        }
+        })
     }
 
     pub fn delete_field_by_name<S: AsRef<str>>(table: S, name: S) -> Result<Value, ReturnError> {
@@ -339,6 +352,9 @@ impl FieldController {
         let table_id = TableController::find_by_name(table).unwrap().id;
 
         let transaction = connection.transaction(|conn| {
+            lock_table(conn, table)?;
+            TableController::bump_version(conn, table_id)?;
+
             match delete(fields_dsl::fields)
                 .filter(fields_dsl::name.eq(name))
                 .filter(fields_dsl::table_id.eq(table_id))
@@ -374,7 +390,9 @@ impl FieldController {
         transaction
     }
 
-    pub fn update_field(id: i32, mut new_field: UpdateField) -> Result<Field, ReturnError> {
+    pub fn update_field(id: i32, new_field: Versioned<UpdateField>) -> Result<Field, ReturnError> {
+        let version = new_field.version;
+        let mut new_field = new_field.data;
         if new_field.is_empty() {
             return Err(ReturnError {
                 error_msg: "Invalid json send at least one field".to_string(),
@@ -397,15 +415,41 @@ impl FieldController {
         }
         let connection = &mut establish_connection();
 
+        let table_name = TableController::find(old.table_id)?.name;
+
         let transaction: std::result::Result<Field, ReturnError> = connection.transaction(|conn| {
+            lock_table(conn, &table_name)?;
+
+            // Re-read under the lock, a concurrent edit may have committed meanwhile
+            let old = fields_dsl::fields
+                .filter(fields_dsl::id.eq(id))
+                .first::<Field>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &new_field))?;
+            check_version(&old.name, old.version, version)?;
+            let renamed = tables_dsl::tables
+                .filter(tables_dsl::id.eq(old.table_id))
+                .select(tables_dsl::name)
+                .first::<String>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &new_field))?
+                != table_name;
+            if renamed {
+                return Err(ReturnError::without_value(format!(
+                    "Version conflict: table \"{}\" was renamed while \"{}\" was edited",
+                    table_name, old.name
+                )));
+            }
+            TableController::bump_version(conn, old.table_id)?;
+
             new_field.updated_at = Some(chrono::Utc::now().naive_utc()); // update the updated_at field with the current time
             match update(fields_dsl::fields)
-                .set(&new_field)
+                .set((
+                    &new_field,
+                    fields_dsl::version.eq(fields_dsl::version + 1),
+                ))
                 .filter(fields_dsl::id.eq(id))
                 .get_result::<Field>(conn)
             {
                 Ok(res) => {
-                    let table_name = TableController::find(res.table_id).unwrap().name;
                     let field_query_builder =
                         FieldQueryBuilder::from_vec(table_name, vec![res.clone().to()]);
                     let query = field_query_builder.build_update(&old.name, new_field.clone());
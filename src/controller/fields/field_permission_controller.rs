@@ -0,0 +1,379 @@
+use diesel::delete;
+use diesel::insert_into;
+use diesel::prelude::*;
+use serde_json::{Map, Value};
+
+use super::structs::SetFieldPermission;
+use super::{field_controller::FieldController, types::FieldType};
+use crate::controller::custom::history_controller::HistoryController;
+use crate::controller::tables::row_policy_controller::Viewer;
+use crate::controller::tables::table_controller::TableController;
+use crate::controller::GenericValue;
+use crate::controller::QueryParams;
+use crate::models::cms::field_permission_model::{FieldAccess, FieldPermission};
+use crate::models::cms::fields_model::Field;
+use crate::models::cms::table_model::Table;
+use crate::models::db::connection::establish_connection;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::field_permissions::dsl;
+use crate::schema::fields::dsl as fields_dsl;
+use crate::schema::users_roles::dsl as user_roles_dsl;
+
+pub const DEFAULT_MASK_VISIBLE: i32 = 4;
+
+#[derive(Debug, Clone)]
+pub struct FieldRule {
+    pub field: String,
+    pub access: FieldAccess,
+    pub mask_visible: usize,
+}
+
+/// Access of a viewer to the restricted fields of a table, other fields are `full`
+#[derive(Debug, Clone, Default)]
+pub struct FieldRules {
+    rules: Vec<FieldRule>,
+}
+
+impl FieldRules {
+    /// No restriction, for internal reads and primary key lookups
+    pub const NONE: FieldRules = FieldRules { rules: Vec::new() };
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn access(&self, name: &str) -> FieldAccess {
+        self.rule(name).map_or(FieldAccess::Full, |x| x.access)
+    }
+
+    fn rule(&self, name: &str) -> Option<&FieldRule> {
+        self.rules
+            .iter()
+            .find(|x| HistoryController::key_matches(name, &x.field))
+    }
+
+    /// Rules of the fields that cannot be filtered on, read-only fields can
+    pub fn unreadable(&self) -> FieldRules {
+        FieldRules {
+            rules: self
+                .rules
+                .iter()
+                .filter(|x| x.access < FieldAccess::ReadOnly)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Hidden fields are reported like unknown columns
+    pub fn check(&self, name: &str) -> Result<(), ReturnError> {
+        match self.access(name) {
+            FieldAccess::Full => Ok(()),
+            FieldAccess::Hidden => Err(ReturnError::without_value(format!(
+                "Column \"{}\" not found",
+                name
+            ))),
+            access => Err(ReturnError::without_value(format!(
+                "Field \"{}\" is {} for this user",
+                name,
+                access.as_str()
+            ))),
+        }
+    }
+
+    /// Filters on fields the viewer cannot read are refused, their values would leak through
+    /// the rows matched
+    pub fn check_filters(&self, filters: &Map<String, Value>) -> Result<(), ReturnError> {
+        let unreadable = self.unreadable();
+        for key in filters.keys() {
+            unreadable.check(&QueryParams::get_array_key(key))?;
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, row: &mut Value) {
+        if let Some(row) = row.as_object_mut() {
+            self.apply_map(row);
+        }
+    }
+
+    /// Removes hidden fields from a row and masks masked ones, keys may be columns or fields
+    pub fn apply_map(&self, row: &mut Map<String, Value>) {
+        for rule in &self.rules {
+            let key = match row
+                .keys()
+                .find(|x| HistoryController::key_matches(x, &rule.field))
+            {
+                Some(key) => key.clone(),
+                None => continue,
+            };
+            match rule.access {
+                FieldAccess::Hidden => {
+                    row.remove(&key);
+                }
+                FieldAccess::Masked => {
+                    let masked = Self::mask(&row[&key], rule.mask_visible);
+                    row.insert(key, masked);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn apply_all(&self, rows: &mut [GenericValue]) {
+        if self.is_empty() {
+            return;
+        }
+        for row in rows {
+            self.apply(&mut row.0);
+        }
+    }
+
+    /// Values no longer than `visible` are masked entirely
+    fn mask(value: &Value, visible: usize) -> Value {
+        let text = match value {
+            Value::Null => return Value::Null,
+            Value::String(text) => text.clone(),
+            value => value.to_string(),
+        };
+        let chars = text.chars().collect::<Vec<char>>();
+        let keep = if chars.len() > visible { visible } else { 0 };
+        let hidden = chars.len() - keep;
+        let mut masked = "*".repeat(hidden);
+        masked.extend(&chars[hidden..]);
+        Value::String(masked)
+    }
+}
+
+/// Field rules are given per role. A user gets the most permissive access among their roles,
+/// a role without a rule on the field giving full access. Users without roles get the most
+/// restrictive rule, admins are not bound by any.
+pub struct FieldPermissionController;
+
+impl FieldPermissionController {
+    pub fn find_all(
+        table_name: &str,
+        field_name: &str,
+    ) -> Result<Vec<FieldPermission>, ReturnError> {
+        let field = Self::field(table_name, field_name)?;
+        let connection = &mut establish_connection();
+        dsl::field_permissions
+            .filter(dsl::field_id.eq(field.id))
+            .order(dsl::role_id.asc())
+            .load::<FieldPermission>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), field.id))
+    }
+
+    /// Creates or replaces the rule of the role on the field
+    pub fn set(
+        table_name: &str,
+        field_name: &str,
+        permission: SetFieldPermission,
+    ) -> Result<FieldPermission, ReturnError> {
+        let field = Self::field(table_name, field_name)?;
+        if permission.access == FieldAccess::Full {
+            return Err(ReturnError::new(
+                "Fields are fully accessible without a rule, delete the rule instead".to_string(),
+                &permission,
+            ));
+        }
+        let mask_visible = permission.mask_visible.unwrap_or(DEFAULT_MASK_VISIBLE);
+        if mask_visible < 0 {
+            return Err(ReturnError::new(
+                "maskVisible cannot be negative".to_string(),
+                &permission,
+            ));
+        }
+        if permission.access == FieldAccess::Masked
+            && FieldType::from_string(&field.field_type)? == FieldType::Binary
+        {
+            return Err(ReturnError::new(
+                format!("Binary field \"{}\" cannot be masked", field.name),
+                &permission,
+            ));
+        }
+
+        let connection = &mut establish_connection();
+        insert_into(dsl::field_permissions)
+            .values((
+                dsl::field_id.eq(field.id),
+                dsl::role_id.eq(permission.role_id),
+                dsl::access.eq(permission.access.as_str()),
+                dsl::mask_visible.eq(mask_visible),
+                dsl::created_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .on_conflict((dsl::field_id, dsl::role_id))
+            .do_update()
+            .set((
+                dsl::access.eq(permission.access.as_str()),
+                dsl::mask_visible.eq(mask_visible),
+            ))
+            .get_result::<FieldPermission>(connection)
+            .map_err(|err| {
+                let message = err.to_string();
+                if message.contains("foreign key") {
+                    ReturnError::new(
+                        format!("Role with id: {} not found", permission.role_id),
+                        &permission,
+                    )
+                } else {
+                    ReturnError::new(message, &permission)
+                }
+            })
+    }
+
+    pub fn delete(
+        table_name: &str,
+        field_name: &str,
+        role_id: i32,
+    ) -> Result<FieldPermission, ReturnError> {
+        let field = Self::field(table_name, field_name)?;
+        let connection = &mut establish_connection();
+        delete(
+            dsl::field_permissions
+                .filter(dsl::field_id.eq(field.id))
+                .filter(dsl::role_id.eq(role_id)),
+        )
+        .get_result::<FieldPermission>(connection)
+        .optional()
+        .map_err(|err| ReturnError::new(err.to_string(), role_id))?
+        .ok_or(ReturnError::new(
+            format!(
+                "Rule of role {} on field \"{}\" not found",
+                role_id, field.name
+            ),
+            role_id,
+        ))
+    }
+
+    /// Rules the viewer is bound by on the fields of the table
+    pub fn rules(table: &Table, viewer: Viewer) -> Result<FieldRules, ReturnError> {
+        if viewer.admin {
+            return Ok(FieldRules::default());
+        }
+        let connection = &mut establish_connection();
+        let permissions = dsl::field_permissions
+            .inner_join(fields_dsl::fields)
+            .filter(fields_dsl::table_id.eq(table.id))
+            .select((FieldPermission::as_select(), fields_dsl::name))
+            .load::<(FieldPermission, String)>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), table.id))?;
+        if permissions.is_empty() {
+            return Ok(FieldRules::default());
+        }
+        let roles = match viewer.user {
+            Some(user) => user_roles_dsl::users_roles
+                .filter(user_roles_dsl::user_id.eq(user))
+                .select(user_roles_dsl::role_id)
+                .load::<i32>(connection)
+                .map_err(|err| ReturnError::new(err.to_string(), user))?,
+            None => vec![],
+        };
+
+        let mut names = permissions
+            .iter()
+            .map(|(_, name)| name.clone())
+            .collect::<Vec<String>>();
+        names.sort();
+        names.dedup();
+        let mut rules = Vec::new();
+        for field in &names {
+            let of_field = permissions
+                .iter()
+                .filter(|(_, name)| name == field)
+                .map(|(permission, _)| {
+                    (
+                        FieldAccess::from_string(&permission.access).unwrap_or(FieldAccess::Hidden),
+                        permission.mask_visible.max(0) as usize,
+                        permission.role_id,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let (access, mask_visible) = if roles.is_empty() {
+                of_field
+                    .iter()
+                    .map(|(access, visible, _)| (*access, *visible))
+                    .min()
+                    .unwrap()
+            } else {
+                roles
+                    .iter()
+                    .map(|role| {
+                        of_field
+                            .iter()
+                            .find(|(_, _, x)| x == role)
+                            .map_or((FieldAccess::Full, 0), |(access, visible, _)| {
+                                (*access, *visible)
+                            })
+                    })
+                    .max()
+                    .unwrap()
+            };
+            if access != FieldAccess::Full {
+                rules.push(FieldRule {
+                    field: field.clone(),
+                    access,
+                    mask_visible,
+                });
+            }
+        }
+        Ok(FieldRules { rules })
+    }
+
+    fn field(table_name: &str, field_name: &str) -> Result<Field, ReturnError> {
+        let table = TableController::find_by_name(table_name)?;
+        FieldController::find_all(table.id)?
+            .into_iter()
+            .find(|x| x.name.eq_ignore_ascii_case(field_name))
+            .ok_or(ReturnError::without_value(format!(
+                "Field \"{}\" not found in table \"{}\"",
+                field_name, table.name
+            )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules() -> FieldRules {
+        let rule = |field: &str, access| FieldRule {
+            field: field.to_string(),
+            access,
+            mask_visible: DEFAULT_MASK_VISIBLE as usize,
+        };
+        FieldRules {
+            rules: vec![
+                rule("salary", FieldAccess::Hidden),
+                rule("iban", FieldAccess::Masked),
+                rule("status", FieldAccess::ReadOnly),
+            ],
+        }
+    }
+
+    fn filters(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn hidden_field_filter_is_rejected() {
+        let err = rules()
+            .check_filters(&filters(json!({ "salary": 5000 })))
+            .unwrap_err();
+        assert_eq!(err.error_msg, "Column \"salary\" not found");
+    }
+
+    #[test]
+    fn masked_field_filter_is_rejected() {
+        assert!(rules()
+            .check_filters(&filters(json!({ "iban[]": "{FR76}" })))
+            .is_err());
+    }
+
+    #[test]
+    fn readable_field_filters_are_accepted() {
+        assert!(rules()
+            .check_filters(&filters(json!({ "status": "paid", "name": "Ada" })))
+            .is_ok());
+    }
+}
@@ -1,4 +1,5 @@
 pub mod field_controller;
+pub mod field_permission_controller;
 pub mod utils;
 pub mod structs;
 pub mod types;
\ No newline at end of file
@@ -3,7 +3,10 @@ use derive_more::derive::Debug;
 use diesel::{AsChangeset, Insertable};
 use serde::{Deserialize, Serialize};
 
-use crate::{models::cms::fields_model::Field, routes::utils::reponses::ReturnError};
+use crate::{
+    models::cms::{field_permission_model::FieldAccess, fields_model::Field},
+    routes::utils::reponses::ReturnError,
+};
 
 use super::types::FieldType;
 
@@ -74,6 +77,7 @@ impl CreateField {
             created_at: self.created_at,
             updated_at: self.updated_at,
             custom_expression: self.custom_expression.to_owned(),
+            version: 1,
         }
     }
 
@@ -171,6 +175,7 @@ impl Field {
             created_at: field.created_at,
             updated_at: field.updated_at,
             custom_expression: field.custom_expression,
+            version: 1,
         }
     }
 }
@@ -288,3 +293,12 @@ impl PartialEq<Field> for UpdateField {
         true
     }
 }
+
+/// Rule of a role on a field, `maskVisible` only matters to `masked` and defaults to 4
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SetFieldPermission {
+    pub role_id: i32,
+    pub access: FieldAccess,
+    pub mask_visible: Option<i32>,
+}
@@ -7,17 +7,15 @@ use diesel::{
 };
 use serde::{
     de::{self, Visitor},
-    Deserialize, Deserializer, Serialize,
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 
 use std::fmt;
 
 use crate::routes::utils::reponses::ReturnError;
 
-#[derive(FromSqlRow, Debug, AsExpression, Serialize, Clone, Copy, PartialEq, Eq)]
+#[derive(FromSqlRow, Debug, AsExpression, Clone, Copy, PartialEq, Eq)]
 #[diesel(sql_type = VarChar)]
-#[serde(untagged)]
-#[serde(rename_all_fields = "lowercase")]
 pub enum FieldType {
     Varchar,
     Integer,
@@ -59,9 +57,42 @@ impl FieldType {
             FieldType::Binary => "bytea".to_string(),
         }
     }
+    /// Maps a postgres `udt_name` (as found in `information_schema.columns`) back to a field type.
+    /// `int8` and `numeric` have none: values are bound as `i32` and `f32`, which would truncate them.
+    pub fn from_pg_type(udt_name: &str) -> Option<Self> {
+        match udt_name.to_lowercase().as_str() {
+            "varchar" | "bpchar" => Some(FieldType::Varchar),
+            "int2" | "int4" => Some(FieldType::Integer),
+            "float4" | "float8" => Some(FieldType::Float),
+            "bool" => Some(FieldType::Boolean),
+            "date" => Some(FieldType::Date),
+            "time" | "timetz" => Some(FieldType::Time),
+            "timestamp" | "timestamptz" => Some(FieldType::Timestamp),
+            "text" => Some(FieldType::Text),
+            "json" | "jsonb" => Some(FieldType::Json),
+            "bytea" => Some(FieldType::Binary),
+            _ => None,
+        }
+    }
+    /// Whether a JSON value can be bound to a column of this type, `null` is checked by the caller
+    pub fn accepts(&self, value: &serde_json::Value) -> bool {
+        let text = value.as_str();
+        match self {
+            FieldType::Varchar | FieldType::Text | FieldType::Binary => text.is_some(),
+            FieldType::Integer => value.as_i64().is_some_and(|x| i32::try_from(x).is_ok()),
+            FieldType::Float => value.is_number(),
+            FieldType::Boolean => value.is_boolean(),
+            FieldType::Date => text.is_some_and(|x| x.parse::<chrono::NaiveDate>().is_ok()),
+            FieldType::Time => text.is_some_and(|x| x.parse::<chrono::NaiveTime>().is_ok()),
+            FieldType::Timestamp => {
+                text.is_some_and(|x| x.parse::<chrono::DateTime<chrono::Utc>>().is_ok())
+            }
+            FieldType::Json => true,
+        }
+    }
     pub fn from_string(s: &str) -> Result<Self, ReturnError> {
         match s.to_lowercase().as_str() {
-            "varchar" => Ok(FieldType::Varchar),
+            "varchar" | "string" => Ok(FieldType::Varchar),
             "integer" => Ok(FieldType::Integer),
             "float" => Ok(FieldType::Float),
             "boolean" => Ok(FieldType::Boolean),
@@ -127,6 +158,16 @@ impl FromSql<Text, diesel::pg::Pg> for FieldType {
     }
 }
 
+/// Written as the name `Deserialize` reads back, e.g. `"String"`
+impl Serialize for FieldType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
 impl<'de> Deserialize<'de> for FieldType {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
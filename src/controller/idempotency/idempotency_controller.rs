@@ -0,0 +1,164 @@
+use std::env;
+
+use diesel::delete;
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::update;
+use dotenvy::dotenv;
+use sha2::{Digest, Sha256};
+
+use crate::models::db::connection::establish_connection;
+use crate::models::idempotency_key_model::IdempotencyKey;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::idempotency_keys::dsl as keys_dsl;
+
+pub const DEFAULT_TTL_SECS: i64 = 24 * 60 * 60;
+pub const DEFAULT_IN_PROGRESS_TIMEOUT_SECS: i64 = 60;
+pub const MAX_KEY_LENGTH: usize = 255;
+
+/// What to do with a request carrying an `Idempotency-Key`
+#[derive(Debug, Clone)]
+pub enum IdempotencyState {
+    /// First time the key is seen, the request runs and its response is stored
+    New,
+    /// Same request seen before, its stored response is sent back
+    Replay(IdempotencyKey),
+    /// Same request still running, for less than the in-progress timeout
+    InProgress,
+    /// The key was used for a different request
+    Mismatch,
+}
+
+pub struct IdempotencyController;
+
+impl IdempotencyController {
+    /// Seconds a stored response can be replayed
+    pub fn ttl_secs() -> i64 {
+        dotenv().ok();
+        env::var("IDEMPOTENCY_KEY_TTL_SECS")
+            .ok()
+            .and_then(|x| x.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_TTL_SECS)
+    }
+
+    /// Seconds after which a request that never completed, e.g. because the server
+    /// stopped, no longer holds its key
+    pub fn in_progress_timeout_secs() -> i64 {
+        dotenv().ok();
+        env::var("IDEMPOTENCY_IN_PROGRESS_TIMEOUT_SECS")
+            .ok()
+            .and_then(|x| x.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_IN_PROGRESS_TIMEOUT_SECS)
+    }
+
+    /// Hash of everything that makes two requests the same one. The credentials are left out,
+    /// a retry after a token refresh is the same request; keys are namespaced by user instead.
+    pub fn fingerprint(method: &str, path: &str, query: &str, body: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        for part in [method, path, query] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update(body);
+        hasher
+            .finalize()
+            .iter()
+            .map(|x| format!("{:02x}", x))
+            .collect()
+    }
+
+    /// Claims the key of the user for this request, or tells how it was used before
+    pub fn begin(
+        user_id: Option<i32>,
+        key: &str,
+        fingerprint: &str,
+    ) -> Result<IdempotencyState, ReturnError> {
+        if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+            return Err(ReturnError::without_value(format!(
+                "Idempotency key must be between 1 and {} characters",
+                MAX_KEY_LENGTH
+            )));
+        }
+        let now = chrono::Utc::now().naive_utc();
+        let connection = &mut establish_connection();
+        connection.transaction(|conn| {
+            delete(keys_dsl::idempotency_keys.filter(keys_dsl::expires_at.lt(now)))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), key))?;
+
+            let inserted = insert_into(keys_dsl::idempotency_keys)
+                .values((
+                    keys_dsl::user_id.eq(user_id),
+                    keys_dsl::key.eq(key),
+                    keys_dsl::fingerprint.eq(fingerprint),
+                    keys_dsl::expires_at.eq(now + chrono::Duration::seconds(Self::ttl_secs())),
+                    keys_dsl::created_at.eq(now),
+                ))
+                .on_conflict_do_nothing()
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), key))?;
+            if inserted > 0 {
+                return Ok(IdempotencyState::New);
+            }
+
+            let stored = keys_dsl::idempotency_keys
+                .filter(keys_dsl::user_id.is_not_distinct_from(user_id))
+                .filter(keys_dsl::key.eq(key))
+                .for_update()
+                .first::<IdempotencyKey>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), key))?;
+            if stored.fingerprint != fingerprint {
+                return Ok(IdempotencyState::Mismatch);
+            }
+            if stored.status_code.is_some() {
+                return Ok(IdempotencyState::Replay(stored));
+            }
+            let timeout = chrono::Duration::seconds(Self::in_progress_timeout_secs());
+            if stored.created_at + timeout > now {
+                return Ok(IdempotencyState::InProgress);
+            }
+            // The request holding the key never finished, this one takes it over
+            update(keys_dsl::idempotency_keys.filter(keys_dsl::id.eq(stored.id)))
+                .set(keys_dsl::created_at.eq(now))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), key))?;
+            Ok(IdempotencyState::New)
+        })
+    }
+
+    /// Stores the response to replay for the key of the user
+    pub fn complete(
+        user_id: Option<i32>,
+        key: &str,
+        status_code: u16,
+        body: &str,
+    ) -> Result<(), ReturnError> {
+        let connection = &mut establish_connection();
+        update(
+            keys_dsl::idempotency_keys
+                .filter(keys_dsl::user_id.is_not_distinct_from(user_id))
+                .filter(keys_dsl::key.eq(key)),
+        )
+        .set((
+            keys_dsl::status_code.eq(status_code as i32),
+            keys_dsl::response_body.eq(body),
+        ))
+        .execute(connection)
+        .map(|_| ())
+        .map_err(|err| ReturnError::new(err.to_string(), key))
+    }
+
+    /// Frees the key of a request that failed, so it can be retried
+    pub fn release(user_id: Option<i32>, key: &str) -> Result<(), ReturnError> {
+        let connection = &mut establish_connection();
+        delete(
+            keys_dsl::idempotency_keys
+                .filter(keys_dsl::user_id.is_not_distinct_from(user_id))
+                .filter(keys_dsl::key.eq(key))
+                .filter(keys_dsl::status_code.is_null()),
+        )
+        .execute(connection)
+        .map(|_| ())
+        .map_err(|err| ReturnError::new(err.to_string(), key))
+    }
+}
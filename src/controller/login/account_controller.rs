@@ -0,0 +1,262 @@
+use std::env;
+
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::update;
+use dotenvy::dotenv;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use super::throttle_controller::ThrottleController;
+use super::token_controller::TokenController;
+use crate::controller::users::user_controller::UserController;
+use crate::controller::users::utils::password::PasswordUtils;
+use crate::models::db::connection::establish_connection;
+use crate::models::users::user_token_model::{TokenPurpose, UserToken};
+use crate::models::users::users_model::User;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::user_tokens::dsl;
+use crate::schema::users::dsl as users_dsl;
+use crate::utils::mailer::{mailer, Mail};
+
+pub const DEFAULT_PASSWORD_RESET_TTL_MINUTES: i64 = 60;
+pub const DEFAULT_EMAIL_VERIFICATION_TTL_HOURS: i64 = 48;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ForgotPasswordData {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetPasswordData {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyEmailData {
+    pub token: String,
+}
+
+/// Password resets and email verification. Both mail a random token that is stored hashed,
+/// expires and can be redeemed once; issuing a new one invalidates the previous ones.
+pub struct AccountController;
+
+impl AccountController {
+    pub fn password_reset_minutes() -> i64 {
+        dotenv().ok();
+        env::var("PASSWORD_RESET_TTL_MINUTES")
+            .ok()
+            .and_then(|x| x.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_PASSWORD_RESET_TTL_MINUTES)
+    }
+
+    pub fn email_verification_hours() -> i64 {
+        dotenv().ok();
+        env::var("EMAIL_VERIFICATION_TTL_HOURS")
+            .ok()
+            .and_then(|x| x.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_EMAIL_VERIFICATION_TTL_HOURS)
+    }
+
+    /// Mails a reset token when the email belongs to a user. Unknown emails and mail failures
+    /// are only logged, so the answer does not tell which accounts exist. Blocks on the
+    /// database and the mailer, the route runs it in the background.
+    pub fn forgot_password(data: ForgotPasswordData) -> Result<(), ReturnError> {
+        let user = match UserController::find_by_email(data.email.clone()) {
+            Ok(user) => user,
+            Err(_) => {
+                info!("Password reset requested for unknown email {}", data.email);
+                return Ok(());
+            }
+        };
+        // Users blocked by failed logins unblock themselves with a reset, not those an admin blocked
+        if user.blocked && user.auto_blocked_at.is_none() {
+            info!("Password reset requested for blocked user {}", user.id);
+            return Ok(());
+        }
+        let ttl = chrono::Duration::minutes(Self::password_reset_minutes());
+        let token = Self::issue(&user, TokenPurpose::PasswordReset, ttl)?;
+        let mail = Mail {
+            to: user.email.clone(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "A password reset was requested for your account.\n\n{}\n\nIt expires in {} minutes. Ignore this mail if you did not ask for it.",
+                Self::instructions("reset-password", &token),
+                ttl.num_minutes()
+            ),
+        };
+        if let Err(err) = mailer().send(&mail) {
+            error!(
+                "Cannot mail the password reset of user {}: {}",
+                user.id, err
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets the new password and ends every session of the user. Redeeming the token proves
+    /// the user reads the mailbox, so it also verifies the email and lifts a block set by
+    /// failed logins.
+    pub fn reset_password(data: ResetPasswordData) -> Result<(), ReturnError> {
+        if data.password.is_empty() {
+            return Err(ReturnError::without_value(
+                "Password is required".to_string(),
+            ));
+        }
+        let hash = PasswordUtils::hash(data.password)?;
+        let connection = &mut establish_connection();
+        let email = connection.transaction(|conn| {
+            let token = Self::redeem(conn, &data.token, TokenPurpose::PasswordReset)?;
+            let now = chrono::Utc::now().naive_utc();
+            let user = users_dsl::users.filter(users_dsl::id.eq(token.user_id));
+            update(user)
+                .set((users_dsl::password.eq(&hash), users_dsl::updated_at.eq(now)))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), token.user_id))?;
+            update(user.filter(users_dsl::email_verified_at.is_null()))
+                .set(users_dsl::email_verified_at.eq(now))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), token.user_id))?;
+            update(user.filter(users_dsl::auto_blocked_at.is_not_null()))
+                .set((
+                    users_dsl::blocked.eq(false),
+                    users_dsl::auto_blocked_at.eq(None::<chrono::NaiveDateTime>),
+                ))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), token.user_id))?;
+            Self::invalidate(conn, token.user_id, TokenPurpose::PasswordReset)?;
+            TokenController::revoke_sessions(conn, token.user_id)?;
+            user.select(users_dsl::email)
+                .first::<String>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), token.user_id))
+        })?;
+        // The failures that blocked the account would block it again at the next one
+        ThrottleController::record_success(&email)
+    }
+
+    /// Mails a verification token to the current email of the user
+    pub fn send_verification(user: &User) -> Result<(), ReturnError> {
+        let ttl = chrono::Duration::hours(Self::email_verification_hours());
+        let token = Self::issue(user, TokenPurpose::EmailVerification, ttl)?;
+        mailer().send(&Mail {
+            to: user.email.clone(),
+            subject: "Verify your email".to_string(),
+            body: format!(
+                "Confirm that this address belongs to your account.\n\n{}\n\nIt expires in {} hours.",
+                Self::instructions("verify-email", &token),
+                ttl.num_hours()
+            ),
+        })
+    }
+
+    /// The new email of the user is unverified until the mailed token is redeemed, mail
+    /// failures are only logged
+    pub fn email_changed(user: &User) -> Result<(), ReturnError> {
+        let connection = &mut establish_connection();
+        update(users_dsl::users.filter(users_dsl::id.eq(user.id)))
+            .set(users_dsl::email_verified_at.eq(None::<chrono::NaiveDateTime>))
+            .execute(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), user.id))?;
+        if let Err(err) = Self::send_verification(user) {
+            error!(
+                "Cannot mail the email verification of user {}: {}",
+                user.id, err
+            );
+        }
+        Ok(())
+    }
+
+    pub fn verify_email(data: VerifyEmailData) -> Result<(), ReturnError> {
+        let connection = &mut establish_connection();
+        connection.transaction(|conn| {
+            let token = Self::redeem(conn, &data.token, TokenPurpose::EmailVerification)?;
+            update(users_dsl::users.filter(users_dsl::id.eq(token.user_id)))
+                .set(users_dsl::email_verified_at.eq(chrono::Utc::now().naive_utc()))
+                .execute(conn)
+                .map(|_| ())
+                .map_err(|err| ReturnError::new(err.to_string(), token.user_id))
+        })
+    }
+
+    /// Stores a new token for the purpose and returns it in clear, older ones stop working
+    fn issue(
+        user: &User,
+        purpose: TokenPurpose,
+        ttl: chrono::Duration,
+    ) -> Result<String, ReturnError> {
+        let token = TokenController::random_hex(32);
+        let now = chrono::Utc::now().naive_utc();
+        let connection = &mut establish_connection();
+        connection.transaction(|conn| {
+            Self::invalidate(conn, user.id, purpose)?;
+            insert_into(dsl::user_tokens)
+                .values((
+                    dsl::user_id.eq(user.id),
+                    dsl::purpose.eq(purpose.as_str()),
+                    dsl::token_hash.eq(TokenController::hash(&token)),
+                    dsl::expires_at.eq(now + ttl),
+                    dsl::created_at.eq(now),
+                ))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), user.id))
+        })?;
+        Ok(token)
+    }
+
+    /// Marks the token used, failing the same way whether it is unknown, used or expired
+    fn redeem(
+        conn: &mut PgConnection,
+        token: &str,
+        purpose: TokenPurpose,
+    ) -> Result<UserToken, ReturnError> {
+        let invalid = || ReturnError::without_value("Token invalid or expired".to_string());
+        let now = chrono::Utc::now().naive_utc();
+        let stored = dsl::user_tokens
+            .filter(dsl::token_hash.eq(TokenController::hash(token)))
+            .filter(dsl::purpose.eq(purpose.as_str()))
+            .for_update()
+            .first::<UserToken>(conn)
+            .optional()
+            .map_err(|err| ReturnError::without_value(err.to_string()))?
+            .ok_or_else(invalid)?;
+        if stored.used_at.is_some() || stored.expires_at < now {
+            return Err(invalid());
+        }
+        update(dsl::user_tokens.filter(dsl::id.eq(stored.id)))
+            .set(dsl::used_at.eq(now))
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), stored.id))?;
+        Ok(stored)
+    }
+
+    fn invalidate(
+        conn: &mut PgConnection,
+        user_id: i32,
+        purpose: TokenPurpose,
+    ) -> Result<(), ReturnError> {
+        let now = chrono::Utc::now().naive_utc();
+        update(
+            dsl::user_tokens
+                .filter(dsl::user_id.eq(user_id))
+                .filter(dsl::purpose.eq(purpose.as_str()))
+                .filter(dsl::used_at.is_null()),
+        )
+        .set(dsl::used_at.eq(now))
+        .execute(conn)
+        .map(|_| ())
+        .map_err(|err| ReturnError::new(err.to_string(), user_id))
+    }
+
+    /// Link to the frontend page `FRONTEND_URL/<page>?token=` when configured, the bare token otherwise
+    fn instructions(page: &str, token: &str) -> String {
+        dotenv().ok();
+        match env::var("FRONTEND_URL").ok().filter(|x| !x.is_empty()) {
+            Some(url) => format!("{}/{}?token={}", url.trim_end_matches('/'), page, token),
+            None => format!("Token: {}", token),
+        }
+    }
+}
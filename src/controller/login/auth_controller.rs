@@ -1,12 +1,20 @@
 use std::env;
+use std::net::IpAddr;
 
+use super::jwt_keys::JwtKeys;
+use super::throttle_controller::ThrottleController;
+use super::token_controller::TokenController;
 use crate::{
     controller::users::{user_controller::UserController, utils::password::PasswordUtils},
-    models::users::users_model::User,
+    models::db::connection::establish_connection,
+    models::users::{api_key_model::ApiKeyScope, users_model::User},
     routes::utils::reponses::ReturnError,
+    schema::users::dsl as users_dsl,
 };
+use diesel::prelude::*;
 use dotenvy::dotenv;
-use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
+use log::error;
 use serde::{Deserialize, Serialize};
 
 pub struct AuthController;
@@ -17,48 +25,220 @@ pub struct LoginData {
     password: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+pub const DEFAULT_ISSUER: &str = "actix_server";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub exp: usize,
+    /// Rights of the user when the token was issued, `authenticate` sets the current ones
     pub api_rights: bool,
     pub admin_rights: bool,
+    /// Id of the user the token was issued to, tokens issued before it was added have none
+    #[serde(default)]
+    pub sub: Option<String>,
+    /// Seconds since the epoch when the token was issued. This and the next two claims are
+    /// missing from tokens issued before they were added, which still decode.
+    #[serde(default)]
+    pub iat: usize,
+    /// Unique id of the token, empty when missing: such tokens cannot be revoked and live until `exp`
+    #[serde(default)]
+    pub jti: String,
+    /// Only checked when present
+    #[serde(default)]
+    pub iss: String,
+    /// Tables and methods an API key is restricted to, tokens are not restricted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<ApiKeyScope>>,
+}
+
+/// Why `authenticate` refused a token or an API key
+#[derive(Debug)]
+pub enum AuthError {
+    /// Malformed, expired or badly signed token
+    BadToken,
+    /// Unknown, revoked or expired API key
+    BadKey,
+    /// Token logged out or revoked
+    Revoked,
+    /// The user of the token or key was deleted
+    UserGone,
+    Blocked(i32),
+    /// The credential could not be checked
+    Db(ReturnError),
+}
+
+impl From<AuthError> for ReturnError {
+    fn from(err: AuthError) -> Self {
+        match err {
+            AuthError::BadToken => ReturnError::without_value("Token invalid".to_string()),
+            AuthError::BadKey => ReturnError::without_value("API key invalid".to_string()),
+            AuthError::Revoked => ReturnError::without_value("Token revoked".to_string()),
+            AuthError::UserGone => ReturnError::without_value("User not found".to_string()),
+            AuthError::Blocked(id) => ReturnError::new("User is blocked".to_string(), id),
+            AuthError::Db(err) => err,
+        }
+    }
+}
+
+impl Claims {
+    pub fn user_id(&self) -> Option<i32> {
+        self.sub.as_ref().and_then(|x| x.parse::<i32>().ok())
+    }
+
+    pub fn allows(&self, table: &str, method: &str) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.iter().any(|x| x.allows(table, method)))
+    }
 }
 
 impl AuthController {
-    pub async fn login(login_data: LoginData) -> Result<User, ReturnError> {
+    /// Failures count towards the lockout of the account and of the client address,
+    /// a locked login is refused before its password is checked
+    pub async fn login(login_data: LoginData, ip: Option<IpAddr>) -> Result<User, ReturnError> {
         let err_default =
             ReturnError::new("Invalid email or password".to_string(), login_data.clone());
 
+        if let Some(retry_after) = ThrottleController::locked_for(&login_data.email, ip)? {
+            return Err(ReturnError::new(
+                "Too many failed login attempts, try again later".to_string(),
+                serde_json::json!({ "retryAfter": retry_after }),
+            ));
+        }
+        let failed = |user_id: Option<i32>| {
+            if let Err(err) = ThrottleController::record_failure(&login_data.email, ip, user_id) {
+                error!("Cannot record a failed login: {}", err);
+            }
+            err_default.clone()
+        };
+
         let user_email = login_data.email.clone();
         let user = match UserController::find_by_email(user_email) {
             Ok(res) => res,
-            Err(_) => return Err(err_default),
+            Err(_) => return Err(failed(None)),
         };
 
-        let valid_pass = PasswordUtils::verify(&login_data.password, &user.password);
+        let valid_pass = match PasswordUtils::verify(&login_data.password, &user.password) {
+            Ok(valid) => valid,
+            Err(err) => {
+                error!("User {}: {}", user.id, err);
+                return Err(failed(Some(user.id)));
+            }
+        };
 
         if !valid_pass {
+            return Err(failed(Some(user.id)));
+        }
+        // Told apart from a wrong password, it would confirm the password and the account
+        if user.blocked {
             return Err(err_default);
         }
+        if let Err(err) = ThrottleController::record_success(&login_data.email) {
+            error!(
+                "Cannot reset the failed logins of user {}: {}",
+                user.id, err
+            );
+        }
+
+        // The password is only known now, older hashes are upgraded with it
+        if PasswordUtils::needs_rehash(&user.password) {
+            let rehashed = PasswordUtils::hash(login_data.password.clone())
+                .and_then(|hash| UserController::set_password_hash(user.id, &hash));
+            if let Err(err) = rehashed {
+                error!("Cannot rehash the password of user {}: {}", user.id, err);
+            }
+        }
 
         Ok(user)
     }
 
     pub fn verify_jwt(token: String) -> (bool, Option<Claims>) {
-        dotenv().ok();
-        let secret = env::var("SECRET").expect("SALT must be set");
+        // The `kid` header picks the key, tokens without one are HS256 signed with `SECRET`,
+        // refused once an asymmetric key signs unless `JWT_ACCEPT_HS256` is set
+        let kid = match decode_header(&token) {
+            Ok(header) => header.kid,
+            Err(_) => return (false, None),
+        };
+        let (algorithm, key) = match JwtKeys::get().verifying(kid.as_deref()) {
+            Some(key) => key,
+            None => return (false, None),
+        };
+
+        let mut validation = Validation::new(algorithm);
+        validation.set_issuer(&[Self::issuer()]);
 
-        // my_claims is a struct that implements Serialize
-        // This will create a JWT using HS256 as algorithm
-        let token = decode::<Claims>(
-            &token,
-            &DecodingKey::from_secret(secret.as_ref()),
-            &Validation::new(Algorithm::HS256),
-        );
+        let token = decode::<Claims>(&token, key, &validation);
 
         match token {
             Ok(token) => (true, Some(token.claims)),
             Err(_) => (false, None),
         }
     }
+
+    /// `iss` of the tokens issued and accepted by this server
+    pub fn issuer() -> String {
+        dotenv().ok();
+        env::var("JWT_ISSUER").unwrap_or(DEFAULT_ISSUER.to_string())
+    }
+
+    /// Random `jti` for a new token
+    pub fn new_jti() -> String {
+        TokenController::random_hex(16)
+    }
+
+    /// Signs an access token for the user valid for `expires_in` seconds
+    pub fn generate_jwt(user_data: &User, expires_in: i64) -> (String, Claims) {
+        let now = chrono::Utc::now();
+        let expires = now + chrono::Duration::seconds(expires_in);
+
+        let my_claims = Claims {
+            exp: expires.timestamp() as usize,
+            api_rights: user_data.api_rights,
+            admin_rights: user_data.admin,
+            sub: Some(user_data.id.to_string()),
+            iat: now.timestamp() as usize,
+            jti: Self::new_jti(),
+            iss: Self::issuer(),
+            scopes: None,
+        };
+
+        let signing = JwtKeys::get().signing();
+        let mut header = Header::new(signing.algorithm);
+        header.kid = signing.kid.clone();
+
+        let token = encode(&header, &my_claims, &signing.key).unwrap();
+
+        (token, my_claims)
+    }
+
+    /// Verifies the token and loads its user, revoked tokens and tokens of deleted or blocked
+    /// users are refused. The rights of the claims are those the user has now.
+    pub fn authenticate(token: String) -> Result<(Claims, User), AuthError> {
+        let mut claims = match Self::verify_jwt(token) {
+            (true, Some(claims)) => claims,
+            _ => return Err(AuthError::BadToken),
+        };
+        if TokenController::is_revoked(&claims.jti).map_err(AuthError::Db)? {
+            return Err(AuthError::Revoked);
+        }
+        let user = Self::active_user(claims.user_id().ok_or(AuthError::UserGone)?)?;
+        claims.api_rights = user.api_rights;
+        claims.admin_rights = user.admin;
+        Ok((claims, user))
+    }
+
+    /// User a token or an API key was issued to, refused when deleted or blocked
+    pub fn active_user(id: i32) -> Result<User, AuthError> {
+        let connection = &mut establish_connection();
+        let user = users_dsl::users
+            .find(id)
+            .first::<User>(connection)
+            .optional()
+            .map_err(|err| AuthError::Db(ReturnError::new(err.to_string(), id)))?
+            .ok_or(AuthError::UserGone)?;
+        if user.blocked {
+            return Err(AuthError::Blocked(user.id));
+        }
+        Ok(user)
+    }
 }
@@ -0,0 +1,30 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    dev::Payload, error::InternalError, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse,
+};
+
+use crate::{models::users::users_model::User, routes::utils::reponses::ReturnError};
+
+/// User behind the request token, resolved by `CheckLogin` and `ShouldCheckLogin`.
+/// Routes that also serve anonymous requests take `Option<CurrentUser>`.
+#[derive(Debug, Clone)]
+pub struct CurrentUser(pub User);
+
+impl FromRequest for CurrentUser {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<CurrentUser>()
+                .cloned()
+                .ok_or_else(|| {
+                    let response = HttpResponse::Unauthorized()
+                        .json(ReturnError::without_value("Token missing".to_string()));
+                    InternalError::from_response("Token missing", response).into()
+                }),
+        )
+    }
+}
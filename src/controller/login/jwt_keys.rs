@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::LazyLock;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use dotenvy::dotenv;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{crypto, Algorithm, DecodingKey, EncodingKey};
+use simple_asn1::{from_der, ASN1Block};
+
+use crate::routes::utils::reponses::ReturnError;
+
+const RSA_OID: &[u64] = &[1, 2, 840, 113549, 1, 1, 1];
+const ED25519_OID: &[u64] = &[1, 3, 101, 112];
+
+/// Loaded on first use, changing the keys needs a restart
+static JWT_KEYS: LazyLock<JwtKeys> =
+    LazyLock::new(|| JwtKeys::load().unwrap_or_else(|err| panic!("Invalid JWT keys: {}", err)));
+
+/// Key new tokens are signed with, `kid` is only set for asymmetric keys
+pub struct SigningKey {
+    pub kid: Option<String>,
+    pub algorithm: Algorithm,
+    pub key: EncodingKey,
+}
+
+/// Keys tokens are signed and verified with.
+///
+/// `JWT_PUBLIC_KEYS` lists the RS256 or Ed25519 public keys accepted for verification as
+/// `kid=path.pem` pairs, the key of a token is picked by its `kid` header. `JWT_PRIVATE_KEY`
+/// signs new tokens under the `JWT_KEY_ID` entry of that list. Without a private key tokens
+/// are signed with the HS256 `SECRET`, which also verifies tokens that carry no `kid`.
+/// With one, those HS256 tokens are refused unless `JWT_ACCEPT_HS256` keeps them valid
+/// while migrating.
+pub struct JwtKeys {
+    signing: SigningKey,
+    verifying: HashMap<String, (Algorithm, DecodingKey)>,
+    hmac: Option<DecodingKey>,
+    jwks: JwkSet,
+}
+
+impl JwtKeys {
+    pub fn get() -> &'static JwtKeys {
+        &JWT_KEYS
+    }
+
+    pub fn signing(&self) -> &SigningKey {
+        &self.signing
+    }
+
+    /// Key for a token with the given `kid` header
+    pub fn verifying(&self, kid: Option<&str>) -> Option<(Algorithm, &DecodingKey)> {
+        match kid {
+            Some(kid) => self.verifying.get(kid).map(|(alg, key)| (*alg, key)),
+            None => self.hmac.as_ref().map(|key| (Algorithm::HS256, key)),
+        }
+    }
+
+    /// Public keys, as published at `/.well-known/jwks.json`
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+
+    fn load() -> Result<JwtKeys, ReturnError> {
+        dotenv().ok();
+        let secret = env::var("SECRET").ok().filter(|x| !x.is_empty());
+        let accept_hs256 = env::var("JWT_ACCEPT_HS256")
+            .ok()
+            .and_then(|x| x.parse::<bool>().ok())
+            .unwrap_or(false);
+
+        let mut jwks = JwkSet { keys: Vec::new() };
+        let mut verifying = HashMap::new();
+        let public_keys = env::var("JWT_PUBLIC_KEYS").unwrap_or_default();
+        for entry in public_keys.split(',').filter(|x| !x.trim().is_empty()) {
+            let (kid, path) = entry.split_once('=').ok_or(ReturnError::new(
+                "JWT_PUBLIC_KEYS entries must be kid=path".to_string(),
+                entry,
+            ))?;
+            let jwk = Self::public_jwk(kid.trim(), &Self::read_pem(path.trim(), "PUBLIC KEY")?)?;
+            let key = DecodingKey::from_jwk(&jwk)
+                .map_err(|err| ReturnError::new(err.to_string(), kid))?;
+            let algorithm = match jwk.common.key_algorithm {
+                Some(KeyAlgorithm::EdDSA) => Algorithm::EdDSA,
+                _ => Algorithm::RS256,
+            };
+            verifying.insert(kid.trim().to_string(), (algorithm, key));
+            jwks.keys.push(jwk);
+        }
+
+        let signing = match env::var("JWT_PRIVATE_KEY").ok().filter(|x| !x.is_empty()) {
+            Some(path) => {
+                let kid = env::var("JWT_KEY_ID").map_err(|_| {
+                    ReturnError::without_value("JWT_KEY_ID must be set".to_string())
+                })?;
+                let (algorithm, _) = verifying.get(&kid).ok_or(ReturnError::new(
+                    "JWT_KEY_ID must be one of JWT_PUBLIC_KEYS".to_string(),
+                    &kid,
+                ))?;
+                let pem =
+                    fs::read(&path).map_err(|err| ReturnError::new(err.to_string(), &path))?;
+                let key = match algorithm {
+                    Algorithm::EdDSA => EncodingKey::from_ed_pem(&pem),
+                    _ => EncodingKey::from_rsa_pem(&pem),
+                }
+                .map_err(|err| ReturnError::new(err.to_string(), &path))?;
+                Self::check_pair(&kid, *algorithm, &key, &verifying[&kid].1)?;
+                SigningKey {
+                    kid: Some(kid),
+                    algorithm: *algorithm,
+                    key,
+                }
+            }
+            None => SigningKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: EncodingKey::from_secret(
+                    secret
+                        .as_ref()
+                        .ok_or(ReturnError::without_value(
+                            "SECRET or JWT_PRIVATE_KEY must be set".to_string(),
+                        ))?
+                        .as_bytes(),
+                ),
+            },
+        };
+
+        let hmac = match signing.algorithm {
+            Algorithm::HS256 => secret,
+            _ => secret.filter(|_| accept_hs256),
+        };
+        Ok(JwtKeys {
+            signing,
+            verifying,
+            hmac: hmac.map(|x| DecodingKey::from_secret(x.as_bytes())),
+            jwks,
+        })
+    }
+
+    /// Fails when the private key does not match the public key listed under its `kid`
+    fn check_pair(
+        kid: &str,
+        algorithm: Algorithm,
+        private: &EncodingKey,
+        public: &DecodingKey,
+    ) -> Result<(), ReturnError> {
+        let message = b"jwt key check";
+        let signature = crypto::sign(message, private, algorithm)
+            .map_err(|err| ReturnError::new(err.to_string(), kid))?;
+        match crypto::verify(&signature, message, public, algorithm) {
+            Ok(true) => Ok(()),
+            _ => Err(ReturnError::new(
+                "JWT_PRIVATE_KEY does not match the public key of JWT_KEY_ID".to_string(),
+                kid,
+            )),
+        }
+    }
+
+    fn read_pem(path: &str, tag: &str) -> Result<Vec<u8>, ReturnError> {
+        let content = fs::read(path).map_err(|err| ReturnError::new(err.to_string(), path))?;
+        let pem = pem::parse(content).map_err(|err| ReturnError::new(err.to_string(), path))?;
+        if pem.tag() != tag {
+            return Err(ReturnError::new(format!("Expected a {} PEM", tag), path));
+        }
+        Ok(pem.contents().to_vec())
+    }
+
+    /// JWK of a DER encoded SubjectPublicKeyInfo
+    fn public_jwk(kid: &str, der: &[u8]) -> Result<Jwk, ReturnError> {
+        let invalid = || ReturnError::new("Unsupported public key".to_string(), kid);
+        let blocks = from_der(der).map_err(|_| invalid())?;
+        let (oid, key) = match blocks.first() {
+            Some(ASN1Block::Sequence(_, items)) => match (items.first(), items.get(1)) {
+                (Some(ASN1Block::Sequence(_, id)), Some(ASN1Block::BitString(_, _, key))) => {
+                    match id.first() {
+                        Some(ASN1Block::ObjectIdentifier(_, oid)) => {
+                            (oid.as_vec::<u64>().map_err(|_| invalid())?, key)
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(invalid()),
+            },
+            _ => return Err(invalid()),
+        };
+
+        let (key_algorithm, algorithm) = if oid == RSA_OID {
+            let blocks = from_der(key).map_err(|_| invalid())?;
+            let (n, e) = match blocks.first() {
+                Some(ASN1Block::Sequence(_, items)) => match (items.first(), items.get(1)) {
+                    (Some(ASN1Block::Integer(_, n)), Some(ASN1Block::Integer(_, e))) => {
+                        (n.to_bytes_be().1, e.to_bytes_be().1)
+                    }
+                    _ => return Err(invalid()),
+                },
+                _ => return Err(invalid()),
+            };
+            (
+                KeyAlgorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(n),
+                    e: URL_SAFE_NO_PAD.encode(e),
+                }),
+            )
+        } else if oid == ED25519_OID {
+            (
+                KeyAlgorithm::EdDSA,
+                AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(key),
+                }),
+            )
+        } else {
+            return Err(invalid());
+        };
+
+        Ok(Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm,
+        })
+    }
+}
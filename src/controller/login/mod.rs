@@ -1 +1,6 @@
+pub mod account_controller;
 pub mod auth_controller;
+pub mod current_user;
+pub mod jwt_keys;
+pub mod throttle_controller;
+pub mod token_controller;
@@ -0,0 +1,203 @@
+use std::env;
+use std::net::IpAddr;
+
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::update;
+use dotenvy::dotenv;
+use log::warn;
+
+use crate::models::db::connection::establish_connection;
+use crate::models::users::login_attempt_model::LoginAttempt;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::login_attempts::dsl;
+use crate::schema::users::dsl as users_dsl;
+
+pub const DEFAULT_MAX_FAILURES: i32 = 5;
+pub const DEFAULT_IP_MAX_FAILURES: i32 = 20;
+pub const DEFAULT_LOCKOUT_SECONDS: i64 = 30;
+pub const DEFAULT_MAX_LOCKOUT_SECONDS: i64 = 3600;
+pub const DEFAULT_FAILURE_WINDOW_MINUTES: i64 = 60;
+
+/// Limits of failed logins, from the `LOGIN_*` variables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThrottlePolicy {
+    /// Failures of an account before it is locked
+    pub max_failures: i32,
+    /// Failures of a client address before it is locked, across accounts
+    pub ip_max_failures: i32,
+    /// First lockout, doubled by each further failure
+    pub lockout_seconds: i64,
+    pub max_lockout_seconds: i64,
+    /// Failures older than this are forgotten
+    pub failure_window_minutes: i64,
+    /// Failures of an existing account that set `users.blocked`, `0` never blocks.
+    /// A password reset or an admin unblocks the account.
+    pub block_after: i32,
+}
+
+impl ThrottlePolicy {
+    pub fn from_env() -> ThrottlePolicy {
+        dotenv().ok();
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            env::var(name)
+                .ok()
+                .and_then(|x| x.parse::<T>().ok())
+                .unwrap_or(default)
+        }
+        ThrottlePolicy {
+            max_failures: var("LOGIN_MAX_FAILURES", DEFAULT_MAX_FAILURES).max(1),
+            ip_max_failures: var("LOGIN_IP_MAX_FAILURES", DEFAULT_IP_MAX_FAILURES).max(1),
+            lockout_seconds: var("LOGIN_LOCKOUT_SECONDS", DEFAULT_LOCKOUT_SECONDS).max(1),
+            max_lockout_seconds: var("LOGIN_MAX_LOCKOUT_SECONDS", DEFAULT_MAX_LOCKOUT_SECONDS),
+            failure_window_minutes: var(
+                "LOGIN_FAILURE_WINDOW_MINUTES",
+                DEFAULT_FAILURE_WINDOW_MINUTES,
+            ),
+            block_after: var("LOGIN_BLOCK_AFTER", 0),
+        }
+    }
+
+    /// Lockout after `failures`, none below the threshold
+    fn lockout(&self, failures: i32, threshold: i32) -> Option<chrono::Duration> {
+        if failures < threshold {
+            return None;
+        }
+        let doublings = (failures - threshold).min(30) as u32;
+        let seconds = self
+            .lockout_seconds
+            .saturating_mul(1 << doublings)
+            .min(self.max_lockout_seconds.max(self.lockout_seconds));
+        Some(chrono::Duration::seconds(seconds))
+    }
+}
+
+/// Counts failed logins per account and per client address. Past a threshold the subject
+/// is locked for a time that doubles with every further failure. Unknown emails are counted
+/// like existing ones, so lockouts do not tell which accounts exist.
+pub struct ThrottleController;
+
+impl ThrottleController {
+    fn email_subject(email: &str) -> String {
+        format!("email:{}", email.trim().to_lowercase())
+    }
+
+    fn ip_subject(ip: IpAddr) -> String {
+        format!("ip:{}", ip)
+    }
+
+    fn subjects(email: &str, ip: Option<IpAddr>) -> Vec<String> {
+        let mut subjects = vec![Self::email_subject(email)];
+        subjects.extend(ip.map(Self::ip_subject));
+        subjects
+    }
+
+    /// Seconds until the account or the client may try again, `None` when neither is locked
+    pub fn locked_for(email: &str, ip: Option<IpAddr>) -> Result<Option<i64>, ReturnError> {
+        let now = chrono::Utc::now().naive_utc();
+        let connection = &mut establish_connection();
+        let locked_until = dsl::login_attempts
+            .filter(dsl::subject.eq_any(Self::subjects(email, ip)))
+            .filter(dsl::locked_until.gt(now))
+            .select(diesel::dsl::max(dsl::locked_until))
+            .first::<Option<chrono::NaiveDateTime>>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), email))?;
+        // Rounded up, a client retrying after `Retry-After` must not be refused again
+        Ok(locked_until.map(|x| (x - now).num_seconds() + 1))
+    }
+
+    /// Counts a failed login and locks what went past its threshold. `user_id` is the account
+    /// the email belongs to, it gets blocked once `LOGIN_BLOCK_AFTER` failures are reached.
+    pub fn record_failure(
+        email: &str,
+        ip: Option<IpAddr>,
+        user_id: Option<i32>,
+    ) -> Result<(), ReturnError> {
+        let policy = ThrottlePolicy::from_env();
+        let connection = &mut establish_connection();
+        connection.transaction(|conn| {
+            let failures = Self::fail(
+                conn,
+                &Self::email_subject(email),
+                policy.max_failures,
+                &policy,
+            )?;
+            if let Some(ip) = ip {
+                Self::fail(conn, &Self::ip_subject(ip), policy.ip_max_failures, &policy)?;
+            }
+            match user_id {
+                Some(user_id) if policy.block_after > 0 && failures >= policy.block_after => {
+                    let blocked = update(
+                        users_dsl::users
+                            .filter(users_dsl::id.eq(user_id))
+                            .filter(users_dsl::blocked.eq(false)),
+                    )
+                    .set((
+                        users_dsl::blocked.eq(true),
+                        users_dsl::auto_blocked_at.eq(chrono::Utc::now().naive_utc()),
+                    ))
+                    .execute(conn)
+                    .map_err(|err| ReturnError::new(err.to_string(), user_id))?;
+                    if blocked > 0 {
+                        warn!("User {} blocked after {} failed logins", user_id, failures);
+                    }
+                    Ok(())
+                }
+                _ => Ok(()),
+            }
+        })
+    }
+
+    /// Forgets the failures of the account, those of the client address only expire
+    /// so that one valid account does not clear the attempts made on others
+    pub fn record_success(email: &str) -> Result<(), ReturnError> {
+        let connection = &mut establish_connection();
+        diesel::delete(dsl::login_attempts.filter(dsl::subject.eq(Self::email_subject(email))))
+            .execute(connection)
+            .map(|_| ())
+            .map_err(|err| ReturnError::new(err.to_string(), email))
+    }
+
+    /// Increments the failures of the subject and returns them
+    fn fail(
+        conn: &mut PgConnection,
+        subject: &str,
+        threshold: i32,
+        policy: &ThrottlePolicy,
+    ) -> Result<i32, ReturnError> {
+        let now = chrono::Utc::now().naive_utc();
+        insert_into(dsl::login_attempts)
+            .values((
+                dsl::subject.eq(subject),
+                dsl::failures.eq(0),
+                dsl::last_failure_at.eq(now),
+            ))
+            .on_conflict(dsl::subject)
+            .do_nothing()
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), subject))?;
+        let attempt = dsl::login_attempts
+            .filter(dsl::subject.eq(subject))
+            .for_update()
+            .first::<LoginAttempt>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), subject))?;
+
+        let window = chrono::Duration::minutes(policy.failure_window_minutes);
+        let unlocked = attempt.locked_until.is_none_or(|x| x <= now);
+        let failures = if unlocked && attempt.last_failure_at + window < now {
+            1
+        } else {
+            attempt.failures.saturating_add(1)
+        };
+        let locked_until = policy.lockout(failures, threshold).map(|x| now + x);
+        update(dsl::login_attempts.filter(dsl::id.eq(attempt.id)))
+            .set((
+                dsl::failures.eq(failures),
+                dsl::locked_until.eq(locked_until.or(attempt.locked_until)),
+                dsl::last_failure_at.eq(now),
+            ))
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), subject))?;
+        Ok(failures)
+    }
+}
@@ -0,0 +1,251 @@
+use std::env;
+
+use chrono::NaiveDateTime;
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::update;
+use dotenvy::dotenv;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::auth_controller::{AuthController, Claims};
+use crate::controller::users::user_controller::UserController;
+use crate::controller::Controller;
+use crate::models::db::connection::establish_connection;
+use crate::models::users::refresh_token_model::RefreshToken;
+use crate::models::users::users_model::User;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::refresh_tokens::dsl as refresh_dsl;
+use crate::schema::revoked_tokens::dsl as revoked_dsl;
+
+pub const DEFAULT_ACCESS_TOKEN_MINUTES: i64 = 15;
+pub const DEFAULT_REFRESH_TOKEN_DAYS: i64 = 30;
+
+/// Access token with the refresh token that renews it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenPair {
+    pub token: String,
+    pub refresh_token: String,
+    /// Seconds the access token is valid for
+    pub expires_in: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshData {
+    pub refresh_token: String,
+}
+
+/// Issues, rotates and revokes tokens. Refresh tokens are single use: presenting one
+/// that was already exchanged revokes every token of its family.
+pub struct TokenController;
+
+impl TokenController {
+    pub fn access_token_minutes() -> i64 {
+        dotenv().ok();
+        env::var("ACCESS_TOKEN_TTL_MINUTES")
+            .ok()
+            .and_then(|x| x.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_ACCESS_TOKEN_MINUTES)
+    }
+
+    pub fn refresh_token_days() -> i64 {
+        dotenv().ok();
+        env::var("REFRESH_TOKEN_TTL_DAYS")
+            .ok()
+            .and_then(|x| x.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_REFRESH_TOKEN_DAYS)
+    }
+
+    /// Hex encoded random bytes
+    pub fn random_hex(bytes: usize) -> String {
+        (0..bytes)
+            .map(|_| format!("{:02x}", rand::random::<u8>()))
+            .collect()
+    }
+
+    /// Hex SHA-256 a secret token is stored as
+    pub fn hash(token: &str) -> String {
+        Sha256::digest(token.as_bytes())
+            .iter()
+            .map(|x| format!("{:02x}", x))
+            .collect()
+    }
+
+    /// Tokens of a new login
+    pub fn issue(user: &User) -> Result<TokenPair, ReturnError> {
+        let connection = &mut establish_connection();
+        Self::issue_in(connection, user, Self::random_hex(16))
+    }
+
+    fn issue_in(
+        conn: &mut PgConnection,
+        user: &User,
+        family: String,
+    ) -> Result<TokenPair, ReturnError> {
+        let expires_in = Self::access_token_minutes() * 60;
+        let (token, claims) = AuthController::generate_jwt(user, expires_in);
+        let refresh_token = Self::random_hex(32);
+        let now = chrono::Utc::now().naive_utc();
+
+        insert_into(refresh_dsl::refresh_tokens)
+            .values((
+                refresh_dsl::user_id.eq(user.id),
+                refresh_dsl::token_hash.eq(Self::hash(&refresh_token)),
+                refresh_dsl::family.eq(&family),
+                refresh_dsl::access_jti.eq(&claims.jti),
+                refresh_dsl::expires_at
+                    .eq(now + chrono::Duration::days(Self::refresh_token_days())),
+                refresh_dsl::created_at.eq(now),
+            ))
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), user.id))?;
+
+        Ok(TokenPair {
+            token,
+            refresh_token,
+            expires_in,
+        })
+    }
+
+    /// Exchanges a refresh token for a new pair of the same family
+    pub fn refresh(refresh_token: &str) -> Result<TokenPair, ReturnError> {
+        let invalid = || ReturnError::without_value("Refresh token invalid".to_string());
+        let now = chrono::Utc::now().naive_utc();
+        let connection = &mut establish_connection();
+
+        // A reused token revokes its family, which has to be committed before failing
+        let rotated = connection.transaction::<_, ReturnError, _>(|conn| {
+            let stored = refresh_dsl::refresh_tokens
+                .filter(refresh_dsl::token_hash.eq(Self::hash(refresh_token)))
+                .for_update()
+                .first::<RefreshToken>(conn)
+                .optional()
+                .map_err(|err| ReturnError::without_value(err.to_string()))?
+                .ok_or(invalid())?;
+
+            if stored.used_at.is_some() || stored.revoked_at.is_some() {
+                Self::revoke_family(conn, &stored.family)?;
+                return Ok(None);
+            }
+            if stored.expires_at < now {
+                return Err(invalid());
+            }
+
+            let user = UserController::find(stored.user_id).map_err(|_| invalid())?;
+            if user.blocked {
+                return Err(ReturnError::new("User is blocked".to_string(), user.id));
+            }
+            update(refresh_dsl::refresh_tokens.filter(refresh_dsl::id.eq(stored.id)))
+                .set(refresh_dsl::used_at.eq(now))
+                .execute(conn)
+                .map_err(|err| ReturnError::without_value(err.to_string()))?;
+            Self::issue_in(conn, &user, stored.family).map(Some)
+        })?;
+
+        rotated.ok_or(ReturnError::without_value(
+            "Refresh token was already used, the session has been revoked".to_string(),
+        ))
+    }
+
+    /// Revokes the access token and the refresh tokens of its session
+    pub fn logout(claims: &Claims, refresh_token: Option<&str>) -> Result<(), ReturnError> {
+        let connection = &mut establish_connection();
+        connection.transaction(|conn| {
+            let expires_at = chrono::DateTime::from_timestamp(claims.exp as i64, 0)
+                .map(|x| x.naive_utc())
+                .unwrap_or(chrono::Utc::now().naive_utc());
+            if !claims.jti.is_empty() {
+                Self::revoke_jti(conn, &claims.jti, expires_at)?;
+            }
+
+            // Without a refresh token the session is the one the access token was issued with
+            let mut query = refresh_dsl::refresh_tokens.into_boxed();
+            query = match refresh_token {
+                Some(token) => query.filter(refresh_dsl::token_hash.eq(Self::hash(token))),
+                None => query.filter(refresh_dsl::access_jti.eq(&claims.jti)),
+            };
+            let stored = query
+                .first::<RefreshToken>(conn)
+                .optional()
+                .map_err(|err| ReturnError::without_value(err.to_string()))?;
+            match stored {
+                Some(stored) if Some(stored.user_id) == claims.user_id() => {
+                    Self::revoke_family(conn, &stored.family)
+                }
+                Some(_) => Err(ReturnError::without_value(
+                    "Refresh token invalid".to_string(),
+                )),
+                None => Ok(()),
+            }
+        })
+    }
+
+    pub fn is_revoked(jti: &str) -> Result<bool, ReturnError> {
+        let connection = &mut establish_connection();
+        revoked_dsl::revoked_tokens
+            .filter(revoked_dsl::jti.eq(jti))
+            .count()
+            .get_result::<i64>(connection)
+            .map(|x| x > 0)
+            .map_err(|err| ReturnError::new(err.to_string(), jti))
+    }
+
+    fn revoke_jti(
+        conn: &mut PgConnection,
+        jti: &str,
+        expires_at: NaiveDateTime,
+    ) -> Result<(), ReturnError> {
+        let now = chrono::Utc::now().naive_utc();
+        // Revocations outlive their token only until it would have expired anyway
+        diesel::delete(revoked_dsl::revoked_tokens.filter(revoked_dsl::expires_at.lt(now)))
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), jti))?;
+        insert_into(revoked_dsl::revoked_tokens)
+            .values((
+                revoked_dsl::jti.eq(jti),
+                revoked_dsl::expires_at.eq(expires_at),
+                revoked_dsl::revoked_at.eq(now),
+            ))
+            .on_conflict(revoked_dsl::jti)
+            .do_nothing()
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|err| ReturnError::new(err.to_string(), jti))
+    }
+
+    /// Revokes every session of the user, after a password change
+    pub fn revoke_sessions(conn: &mut PgConnection, user_id: i32) -> Result<(), ReturnError> {
+        let families = refresh_dsl::refresh_tokens
+            .filter(refresh_dsl::user_id.eq(user_id))
+            .filter(refresh_dsl::revoked_at.is_null())
+            .select(refresh_dsl::family)
+            .distinct()
+            .load::<String>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), user_id))?;
+        for family in families {
+            Self::revoke_family(conn, &family)?;
+        }
+        Ok(())
+    }
+
+    /// Revokes every refresh token of the family and the access tokens issued with them
+    fn revoke_family(conn: &mut PgConnection, family: &str) -> Result<(), ReturnError> {
+        let now = chrono::Utc::now().naive_utc();
+        let tokens = update(
+            refresh_dsl::refresh_tokens
+                .filter(refresh_dsl::family.eq(family))
+                .filter(refresh_dsl::revoked_at.is_null()),
+        )
+        .set(refresh_dsl::revoked_at.eq(now))
+        .get_results::<RefreshToken>(conn)
+        .map_err(|err| ReturnError::new(err.to_string(), family))?;
+
+        let access_expires = now + chrono::Duration::minutes(Self::access_token_minutes());
+        for token in tokens {
+            Self::revoke_jti(conn, &token.access_jti, access_expires)?;
+        }
+        Ok(())
+    }
+}
@@ -16,8 +16,10 @@ pub mod custom;
 pub mod db;
 pub mod deno;
 pub mod fields;
+pub mod idempotency;
 pub mod login;
 pub mod posts;
+pub mod schema;
 pub mod tables;
 pub mod users;
 pub mod utils;
@@ -73,12 +75,53 @@ impl GenericValue {
     }
 }
 
+/// Update payload carrying the version the client last read, used for optimistic locking.
+/// The version is required, it is only optional so that a missing one answers a conflict.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Versioned<T> {
+    #[serde(flatten)]
+    pub data: T,
+    pub version: Option<i32>,
+}
+
+impl<T> Versioned<T> {
+    pub fn check<S: AsRef<str>>(&self, name: S, current: i32) -> Result<(), ReturnError> {
+        check_version(name, current, self.version)
+    }
+}
+
+/// Fails when the client edited an outdated copy of the row or did not say which one it edited
+pub fn check_version<S: AsRef<str>>(
+    name: S,
+    current: i32,
+    version: Option<i32>,
+) -> Result<(), ReturnError> {
+    match version {
+        Some(version) if version != current => Err(ReturnError::without_value(format!(
+            "Version conflict: \"{}\" is at version {}, got {}",
+            name.as_ref(),
+            current,
+            version
+        ))),
+        Some(_) => Ok(()),
+        None => Err(ReturnError::without_value(format!(
+            "Version conflict: \"{}\" is at version {}, send the version that was edited",
+            name.as_ref(),
+            current
+        ))),
+    }
+}
+
 pub const API_LIMIT: i64 = 100;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all(serialize = "camelCase"))]
 pub struct QueryParams {
     pub id: Option<i32>,
     pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    /// Reads the state rows had at this moment, from the row history
+    #[serde(rename = "asOf")]
+    pub as_of: Option<String>,
     // aditional props
     #[serde(flatten)]
     pub extra: Map<String, Value>,
@@ -89,6 +132,8 @@ impl QueryParams {
         Self {
             id,
             limit,
+            offset: None,
+            as_of: None,
             extra: Map::new(),
         }
     }
@@ -101,8 +146,14 @@ impl QueryParams {
         if let Some(limit) = json.get("limit") {
             params.limit = Some(limit.as_i64().unwrap());
         }
+        if let Some(offset) = json.get("offset") {
+            params.offset = offset.as_i64();
+        }
+        if let Some(as_of) = json.get("asOf") {
+            params.as_of = as_of.as_str().map(|x| x.to_string());
+        }
         for (key, value) in json.as_object().unwrap() {
-            if key == "id" || key == "limit" {
+            if key == "id" || key == "limit" || key == "offset" || key == "asOf" {
                 continue;
             }
             extra.insert(key.to_string(), value.clone());
@@ -0,0 +1,2 @@
+pub mod schema_controller;
+pub mod structs;
@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::sql_query;
+
+use super::structs::BundleCustomization;
+use super::structs::BundlePermission;
+use super::structs::BundleTable;
+use super::structs::CreateCustomization;
+use super::structs::OnConflict;
+use super::structs::RemappedTable;
+use super::structs::SchemaBundle;
+use super::structs::SchemaImportRequest;
+use super::structs::SchemaImportResult;
+use super::structs::BUNDLE_VERSION;
+use crate::controller::fields::structs::CreateField;
+use crate::controller::fields::types::FieldType;
+use crate::controller::fields::utils::validate_fields;
+use crate::controller::tables::permissions::structs::CreateTablePermission;
+use crate::controller::tables::structs::Create;
+use crate::controller::tables::structs::SkippedTable;
+use crate::models::cms::custom::customization_model::Customization;
+use crate::models::cms::fields_model::Field;
+use crate::models::cms::permission_model::DefaultPermissions;
+use crate::models::cms::permission_model::PermissionType;
+use crate::models::cms::permission_model::TablePermissions;
+use crate::models::cms::table_model::Table;
+use crate::models::db::connection::establish_connection;
+use crate::models::db::locks::lock_table;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::customizations::dsl as customizations_dsl;
+use crate::schema::fields::dsl as fields_dsl;
+use crate::schema::tables::dsl as tables_dsl;
+use crate::schema::tables_permissions::dsl as permissions_dsl;
+use crate::schema::users::dsl as users_dsl;
+use crate::utils::sql::TableQueryBuilder;
+
+/// Moves content models between instances
+pub struct SchemaController;
+
+impl SchemaController {
+    pub fn export(names: Option<Vec<String>>) -> Result<SchemaBundle, ReturnError> {
+        let connection = &mut establish_connection();
+
+        let mut query = tables_dsl::tables
+            .filter(tables_dsl::is_deleted.eq(false))
+            .order(tables_dsl::id)
+            .into_boxed();
+        if let Some(names) = &names {
+            query = query.filter(tables_dsl::name.eq_any(names));
+        }
+        let tables = query
+            .load::<Table>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &names))?;
+
+        if let Some(names) = &names {
+            for name in names {
+                if !tables.iter().any(|x| &x.name == name) {
+                    return Err(ReturnError::new(
+                        format!("Table \"{}\" not found", name),
+                        names,
+                    ));
+                }
+            }
+        }
+
+        let table_ids: Vec<i32> = tables.iter().map(|x| x.id).collect();
+
+        let fields = fields_dsl::fields
+            .filter(fields_dsl::table_id.eq_any(&table_ids))
+            .order(fields_dsl::id)
+            .load::<Field>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &table_ids))?;
+        let permissions = permissions_dsl::tables_permissions
+            .filter(permissions_dsl::table_id.eq_any(&table_ids))
+            .order(permissions_dsl::id)
+            .load::<TablePermissions>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &table_ids))?;
+        let customizations = customizations_dsl::customizations
+            .filter(customizations_dsl::table_id.eq_any(&table_ids))
+            .order(customizations_dsl::id)
+            .load::<Customization>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &table_ids))?;
+
+        let user_ids: Vec<i32> = customizations
+            .iter()
+            .flat_map(|x| [x.created_by, x.updated_by])
+            .collect();
+        let emails: HashMap<i32, String> = users_dsl::users
+            .filter(users_dsl::id.eq_any(&user_ids))
+            .select((users_dsl::id, users_dsl::email))
+            .load::<(i32, String)>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &user_ids))?
+            .into_iter()
+            .collect();
+
+        let mut bundle_tables = Vec::new();
+        for table in tables {
+            let mut table_fields = Vec::new();
+            for field in fields.iter().filter(|x| x.table_id == table.id) {
+                table_fields.push(Self::export_field(field)?);
+            }
+
+            let table_permissions = permissions
+                .iter()
+                .filter(|x| x.table_id == table.id)
+                .map(|x| {
+                    PermissionType::from_string(&x.permission).map(|permission| BundlePermission {
+                        permission,
+                        allow: x.allow,
+                    })
+                })
+                .collect::<Result<Vec<BundlePermission>, ReturnError>>()?;
+
+            let table_customizations = customizations
+                .iter()
+                .filter(|x| x.table_id == table.id)
+                .map(|x| BundleCustomization {
+                    path: x.path.clone(),
+                    run_on: x.run_on.clone(),
+                    created_by: emails.get(&x.created_by).cloned(),
+                    updated_by: emails.get(&x.updated_by).cloned(),
+                })
+                .collect();
+
+            bundle_tables.push(BundleTable {
+                id: Some(table.id),
+                name: table.name,
+                description: table.description,
+                is_view: table.is_view,
+                is_active: table.is_active,
+                view_sql: table.view_sql,
+                capacity: table.capacity,
+                audit: table.audit,
+                history: table.history,
+                fields: table_fields,
+                permissions: table_permissions,
+                customizations: table_customizations,
+            });
+        }
+
+        Ok(SchemaBundle {
+            version: BUNDLE_VERSION,
+            exported_at: Some(chrono::Utc::now().naive_utc()),
+            tables: bundle_tables,
+        })
+    }
+
+    pub fn import(request: SchemaImportRequest) -> Result<SchemaImportResult, ReturnError> {
+        let bundle = request.bundle;
+        if bundle.version > BUNDLE_VERSION {
+            return Err(ReturnError::new(
+                format!("Unsupported bundle version {}", bundle.version),
+                bundle.version,
+            ));
+        }
+
+        // Validate everything before touching the database
+        let mut tables = Vec::new();
+        for bundle_table in bundle.tables {
+            let mut table = Create::new(
+                bundle_table.name.clone(),
+                bundle_table.description.clone(),
+                Some(bundle_table.is_view),
+                Some(bundle_table.is_active),
+                Some(false),
+                bundle_table.view_sql.clone(),
+                bundle_table.capacity,
+            );
+            table.audit = Some(bundle_table.audit);
+            table.history = Some(bundle_table.history);
+            table.normalize_name();
+            table.validate()?;
+            validate_fields(&bundle_table.fields)?;
+            table.validate_fields(&bundle_table.fields)?;
+            tables.push((table, bundle_table));
+        }
+
+        let connection = &mut establish_connection();
+        connection.transaction(|conn| {
+            let fallback_user = Self::fallback_user(conn)?;
+            let mut users: HashMap<String, i32> = HashMap::new();
+
+            let mut result = SchemaImportResult::default();
+            for (table, bundle_table) in tables {
+                lock_table(conn, &table.name)?;
+                let existing = tables_dsl::tables
+                    .filter(tables_dsl::name.eq(&table.name))
+                    .select(tables_dsl::id)
+                    .first::<i32>(conn)
+                    .optional()
+                    .map_err(|err| ReturnError::new(err.to_string(), &table))?;
+
+                if existing.is_some() {
+                    let reason = format!("Table \"{}\" already exists", table.name);
+                    match request.on_conflict {
+                        OnConflict::Fail => return Err(ReturnError::new(reason, &table)),
+                        OnConflict::Skip => {
+                            result.skipped.push(SkippedTable {
+                                name: table.name,
+                                reason,
+                            });
+                            continue;
+                        }
+                    }
+                }
+
+                let res_table = insert_into(tables_dsl::tables)
+                    .values(&table)
+                    .get_result::<Table>(conn)
+                    .map_err(|err| ReturnError::new(err.to_string(), &table))?;
+
+                let fields: Vec<CreateField> = bundle_table
+                    .fields
+                    .into_iter()
+                    .map(|mut field| {
+                        field.id = None;
+                        field.created_at = None;
+                        field.updated_at = None;
+                        field.set_table(res_table.id);
+                        field
+                    })
+                    .collect();
+                insert_into(fields_dsl::fields)
+                    .values(&fields)
+                    .execute(conn)
+                    .map_err(|err| ReturnError::new(err.to_string(), &fields))?;
+
+                let builder = TableQueryBuilder::from_create(table.clone(), fields);
+                sql_query(builder.build_create_table())
+                    .execute(conn)
+                    .map_err(|err| ReturnError::new(err.to_string(), &table))?;
+                if let Some(trigger) = builder.build_audit_trigger() {
+                    sql_query(&trigger)
+                        .execute(conn)
+                        .map_err(|err| ReturnError::new(err.to_string(), &trigger))?;
+                }
+
+                let permissions = if bundle_table.permissions.is_empty() {
+                    TablePermissions::default_permissions(res_table.id)
+                } else {
+                    bundle_table
+                        .permissions
+                        .iter()
+                        .map(|x| CreateTablePermission::new(res_table.id, x.permission, x.allow))
+                        .collect()
+                };
+                insert_into(permissions_dsl::tables_permissions)
+                    .values(&permissions)
+                    .execute(conn)
+                    .map_err(|err| ReturnError::new(err.to_string(), &permissions))?;
+
+                let now = chrono::Utc::now().naive_utc();
+                let mut customizations = Vec::new();
+                for customization in bundle_table.customizations {
+                    let created_by = Self::remap_user(
+                        conn,
+                        &mut users,
+                        customization.created_by.as_ref(),
+                        fallback_user,
+                    )?;
+                    let updated_by = Self::remap_user(
+                        conn,
+                        &mut users,
+                        customization.updated_by.as_ref(),
+                        fallback_user,
+                    )?;
+                    customizations.push(CreateCustomization {
+                        path: customization.path,
+                        table_id: res_table.id,
+                        run_on: customization.run_on,
+                        created_at: now,
+                        updated_at: now,
+                        created_by,
+                        updated_by,
+                    });
+                }
+                insert_into(customizations_dsl::customizations)
+                    .values(&customizations)
+                    .execute(conn)
+                    .map_err(|err| ReturnError::new(err.to_string(), &customizations))?;
+
+                result.imported.push(RemappedTable {
+                    name: res_table.name,
+                    source_id: bundle_table.id,
+                    id: res_table.id,
+                });
+            }
+            Ok(result)
+        })
+    }
+
+    fn export_field(field: &Field) -> Result<CreateField, ReturnError> {
+        Ok(CreateField {
+            id: None,
+            name: field.name.clone(),
+            description: field.description.clone(),
+            field_type: FieldType::from_string(&field.field_type)?,
+            table_id: None,
+            is_required: Some(field.is_required),
+            is_primary_key: Some(field.is_primary_key),
+            is_auto_increment: Some(field.is_auto_increment),
+            is_generated: Some(field.is_generated),
+            default_value: field.default_value.clone(),
+            is_unique: Some(field.is_unique),
+            created_at: None,
+            updated_at: None,
+            custom_expression: field.custom_expression.clone(),
+        })
+    }
+
+    /// Customizations whose author does not exist on this instance are attributed to the first admin
+    fn fallback_user(conn: &mut PgConnection) -> Result<i32, ReturnError> {
+        users_dsl::users
+            .filter(users_dsl::admin.eq(true))
+            .order(users_dsl::id)
+            .select(users_dsl::id)
+            .first::<i32>(conn)
+            .map_err(|err| ReturnError::without_value(format!("No admin user found: {}", err)))
+    }
+
+    fn remap_user(
+        conn: &mut PgConnection,
+        users: &mut HashMap<String, i32>,
+        email: Option<&String>,
+        fallback_user: i32,
+    ) -> Result<i32, ReturnError> {
+        let email = match email {
+            Some(email) => email,
+            None => return Ok(fallback_user),
+        };
+        if let Some(id) = users.get(email) {
+            return Ok(*id);
+        }
+        let id = users_dsl::users
+            .filter(users_dsl::email.eq(email))
+            .select(users_dsl::id)
+            .first::<i32>(conn)
+            .optional()
+            .map_err(|err| ReturnError::new(err.to_string(), email))?
+            .unwrap_or(fallback_user);
+        users.insert(email.clone(), id);
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_TYPES: [FieldType; 10] = [
+        FieldType::Varchar,
+        FieldType::Integer,
+        FieldType::Float,
+        FieldType::Boolean,
+        FieldType::Date,
+        FieldType::Time,
+        FieldType::Timestamp,
+        FieldType::Text,
+        FieldType::Json,
+        FieldType::Binary,
+    ];
+
+    fn field(id: i32, field_type: FieldType) -> Field {
+        Field {
+            id,
+            name: format!("field_{}", id),
+            description: None,
+            field_type: field_type.to_string(),
+            table_id: 1,
+            is_required: id % 2 == 0,
+            is_primary_key: id == 0,
+            is_auto_increment: id == 0,
+            is_generated: false,
+            default_value: None,
+            custom_expression: None,
+            is_unique: false,
+            created_at: None,
+            updated_at: None,
+            version: 1,
+        }
+    }
+
+    #[test]
+    fn exported_bundle_is_importable() {
+        let fields = ALL_TYPES
+            .iter()
+            .enumerate()
+            .map(|(id, field_type)| SchemaController::export_field(&field(id as i32, *field_type)))
+            .collect::<Result<Vec<CreateField>, ReturnError>>()
+            .unwrap();
+        let bundle = SchemaBundle {
+            version: BUNDLE_VERSION,
+            exported_at: None,
+            tables: vec![BundleTable {
+                id: Some(1),
+                name: "posts".to_string(),
+                description: String::new(),
+                is_view: false,
+                is_active: true,
+                view_sql: None,
+                capacity: None,
+                audit: false,
+                history: false,
+                fields,
+                permissions: vec![],
+                customizations: vec![],
+            }],
+        };
+
+        let exported = serde_json::to_string(&bundle).unwrap();
+        let imported = serde_json::from_str::<SchemaImportRequest>(&exported).unwrap();
+
+        let types = imported.bundle.tables[0]
+            .fields
+            .iter()
+            .map(|x| x.field_type)
+            .collect::<Vec<FieldType>>();
+        assert_eq!(types, ALL_TYPES.to_vec());
+        assert_eq!(imported.on_conflict, OnConflict::Fail);
+    }
+
+    #[test]
+    fn field_types_are_exported_by_name() {
+        let value = serde_json::to_value(FieldType::Varchar).unwrap();
+        assert_eq!(value, serde_json::json!("String"));
+    }
+}
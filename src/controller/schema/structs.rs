@@ -0,0 +1,123 @@
+use chrono::NaiveDateTime;
+use derive_more::derive::Debug;
+use diesel::Insertable;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    controller::{fields::structs::CreateField, tables::structs::SkippedTable},
+    models::cms::permission_model::PermissionType,
+};
+
+pub const BUNDLE_VERSION: i32 = 1;
+
+/// Portable description of a set of tables, ids are only kept to report how they were remapped
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaBundle {
+    pub version: i32,
+    pub exported_at: Option<NaiveDateTime>,
+    pub tables: Vec<BundleTable>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleTable {
+    pub id: Option<i32>,
+    pub name: String,
+    pub description: String,
+    pub is_view: bool,
+    pub is_active: bool,
+    pub view_sql: Option<String>,
+    pub capacity: Option<i32>,
+    #[serde(default)]
+    pub audit: bool,
+    #[serde(default)]
+    pub history: bool,
+    pub fields: Vec<CreateField>,
+    #[serde(default)]
+    pub permissions: Vec<BundlePermission>,
+    #[serde(default)]
+    pub customizations: Vec<BundleCustomization>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BundlePermission {
+    pub permission: PermissionType,
+    pub allow: bool,
+}
+
+/// Users are referenced by email since ids differ between instances
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleCustomization {
+    pub path: String,
+    pub run_on: String,
+    pub created_by: Option<String>,
+    pub updated_by: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportQuery {
+    /// Comma separated table names, every table is exported when missing
+    pub tables: Option<String>,
+}
+
+impl ExportQuery {
+    pub fn names(&self) -> Option<Vec<String>> {
+        self.tables.as_ref().map(|tables| {
+            tables
+                .split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect()
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnConflict {
+    Skip,
+    #[default]
+    Fail,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaImportRequest {
+    #[serde(default)]
+    pub on_conflict: OnConflict,
+    #[serde(flatten)]
+    pub bundle: SchemaBundle,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
+#[diesel(table_name = crate::schema::customizations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct CreateCustomization {
+    pub path: String,
+    pub table_id: i32,
+    pub run_on: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub created_by: i32,
+    pub updated_by: i32,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RemappedTable {
+    pub name: String,
+    pub source_id: Option<i32>,
+    pub id: i32,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaImportResult {
+    pub imported: Vec<RemappedTable>,
+    pub skipped: Vec<SkippedTable>,
+}
@@ -0,0 +1,348 @@
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::sql_query;
+use serde_json::{Map, Value};
+
+use super::structs::CloneTableRequest;
+use super::structs::Create;
+use crate::controller::custom::custom_controller::add_params;
+use crate::controller::fields::field_permission_controller::FieldRules;
+use crate::controller::fields::structs::CreateField;
+use crate::controller::tables::permissions::structs::CreateTablePermission;
+use crate::models::cms::fields_model::Field;
+use crate::models::cms::permission_model::PermissionType;
+use crate::models::cms::permission_model::TablePermissions;
+use crate::models::cms::table_model::Table;
+use crate::models::db::catalog::Catalog;
+use crate::models::db::catalog::CatalogColumn;
+use crate::models::db::connection::establish_connection;
+use crate::models::db::locks::lock_table;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::fields::dsl as fields_dsl;
+use crate::schema::tables::dsl as tables_dsl;
+use crate::schema::tables_permissions::dsl as permissions_dsl;
+use crate::utils::sql::TableQueryBuilder;
+use crate::utils::sql::AUDIT_COLUMNS;
+
+/// Creates a new table with the structure, and optionally the rows, of an existing one
+pub struct CloneController;
+
+impl CloneController {
+    pub fn clone_table<S: AsRef<str>>(
+        source_name: S,
+        request: CloneTableRequest,
+    ) -> Result<Table, ReturnError> {
+        let source_name = source_name.as_ref();
+        let connection = &mut establish_connection();
+
+        connection.transaction(|conn| {
+            let source = tables_dsl::tables
+                .filter(tables_dsl::name.eq(source_name))
+                .filter(tables_dsl::is_deleted.eq(false))
+                .first::<Table>(conn)
+                .optional()
+                .map_err(|err| ReturnError::new(err.to_string(), source_name))?
+                .ok_or(ReturnError::without_value(format!(
+                    "Table \"{}\" not found",
+                    source_name
+                )))?;
+            if source.is_view {
+                return Err(ReturnError::without_value(format!(
+                    "Table \"{}\" is a view and cannot be cloned",
+                    source.name
+                )));
+            }
+
+            let mut table = Create::new(
+                request.name.clone(),
+                request
+                    .description
+                    .clone()
+                    .unwrap_or(source.description.clone()),
+                Some(false),
+                Some(source.is_active),
+                Some(false),
+                None,
+                source.capacity,
+            );
+            table.audit = Some(source.audit);
+            table.history = Some(source.history);
+            table.normalize_name();
+            table.validate()?;
+
+            // Always lock in name order so two clones in opposite directions cannot deadlock
+            let mut names = [source.name.as_str(), table.name.as_str()];
+            names.sort();
+            for name in names {
+                lock_table(conn, name)?;
+            }
+
+            let exists = tables_dsl::tables
+                .filter(tables_dsl::name.eq(&table.name))
+                .filter(tables_dsl::is_deleted.eq(false))
+                .count()
+                .get_result::<i64>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &table))?;
+            if exists > 0 {
+                return Err(ReturnError::new(
+                    format!("Table \"{}\" already exists", table.name),
+                    &table,
+                ));
+            }
+
+            let source_fields = fields_dsl::fields
+                .filter(fields_dsl::table_id.eq(source.id))
+                .order(fields_dsl::id)
+                .load::<Field>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), source.id))?;
+
+            let res_table = insert_into(tables_dsl::tables)
+                .values(&table)
+                .get_result::<Table>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &table))?;
+
+            let fields: Vec<CreateField> = source_fields
+                .iter()
+                .map(|x| {
+                    let mut field = x.clone().to();
+                    field.id = None;
+                    field.created_at = None;
+                    field.updated_at = None;
+                    field.set_table(res_table.id);
+                    field
+                })
+                .collect();
+            insert_into(fields_dsl::fields)
+                .values(&fields)
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &fields))?;
+
+            let builder = TableQueryBuilder::from_create(table.clone(), fields);
+            Self::execute(conn, builder.build_create_table())?;
+            if let Some(trigger) = builder.build_audit_trigger() {
+                Self::execute(conn, trigger)?;
+            }
+
+            Self::copy_indexes(conn, &source, &res_table, &source_fields)?;
+            Self::copy_permissions(conn, &source, &res_table)?;
+
+            if request.copy_data.unwrap_or(false) {
+                Self::copy_rows(
+                    conn,
+                    &source,
+                    &res_table,
+                    &source_fields,
+                    request.filter.as_ref(),
+                )?;
+            }
+            Ok(res_table)
+        })
+    }
+
+    fn execute(conn: &mut PgConnection, query: String) -> Result<(), ReturnError> {
+        sql_query(&query)
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|err| ReturnError::new(err.to_string(), &query))
+    }
+
+    fn copy_indexes(
+        conn: &mut PgConnection,
+        source: &Table,
+        target: &Table,
+        fields: &[Field],
+    ) -> Result<(), ReturnError> {
+        let (schema, name) = Catalog::split_name(&source.name);
+        let indexes = Catalog::indexes(conn, schema, name)?;
+        if indexes.is_empty() {
+            return Ok(());
+        }
+        let source_columns = Catalog::columns(conn, schema, Some(name))?;
+        let (schema, name) = Catalog::split_name(&target.name);
+        let target_columns = Catalog::columns(conn, schema, Some(name))?;
+        // Columns of the clone are named from the fields and may differ from the source ones
+        let mut renames = Vec::new();
+        for field in fields {
+            let from = &Self::column_of(&source_columns, field)?.column_name;
+            let to = &Self::column_of(&target_columns, field)?.column_name;
+            if from != to {
+                renames.push((from.clone(), to.clone()));
+            }
+        }
+
+        for index in indexes {
+            let method = index.method().ok_or(ReturnError::new(
+                format!("Index \"{}\" cannot be copied", index.index_name),
+                &index,
+            ))?;
+            Self::execute(
+                conn,
+                TableQueryBuilder::create_index(
+                    &target.name,
+                    index.is_unique,
+                    &Self::rename_columns(method, &renames),
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the renamed columns of an index definition, e.g. `btree ("UserName")`.
+    /// String literals of partial index predicates are left untouched.
+    fn rename_columns(definition: &str, renames: &[(String, String)]) -> String {
+        let renamed = |name: &str| {
+            renames
+                .iter()
+                .find(|(from, _)| from == name)
+                .map(|(_, to)| format!("\"{}\"", to.replace('"', "\"\"")))
+        };
+        let chars = definition.chars().collect::<Vec<char>>();
+        let mut result = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let start = i;
+            match chars[i] {
+                quote @ ('\'' | '"') => {
+                    // `''` and `""` escape the quote inside literals and identifiers
+                    i += 1;
+                    while i < chars.len() {
+                        if chars[i] == quote && chars.get(i + 1) == Some(&quote) {
+                            i += 2;
+                        } else if chars[i] == quote {
+                            i += 1;
+                            break;
+                        } else {
+                            i += 1;
+                        }
+                    }
+                    let token = chars[start..i].iter().collect::<String>();
+                    let name = token
+                        .get(1..token.len().saturating_sub(1))
+                        .map(|x| x.replace("\"\"", "\""));
+                    match name.filter(|_| quote == '"').and_then(|x| renamed(&x)) {
+                        Some(name) => result.push_str(&name),
+                        None => result.push_str(&token),
+                    }
+                }
+                c if c.is_ascii_lowercase() || c == '_' => {
+                    while i < chars.len()
+                        && (chars[i].is_ascii_lowercase()
+                            || chars[i].is_ascii_digit()
+                            || chars[i] == '_'
+                            || chars[i] == '$')
+                    {
+                        i += 1;
+                    }
+                    let token = chars[start..i].iter().collect::<String>();
+                    result.push_str(&renamed(&token).unwrap_or(token));
+                }
+                c => {
+                    result.push(c);
+                    i += 1;
+                }
+            }
+        }
+        result
+    }
+
+    fn copy_permissions(
+        conn: &mut PgConnection,
+        source: &Table,
+        target: &Table,
+    ) -> Result<(), ReturnError> {
+        let permissions = permissions_dsl::tables_permissions
+            .filter(permissions_dsl::table_id.eq(source.id))
+            .order(permissions_dsl::id)
+            .load::<TablePermissions>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), source.id))?
+            .iter()
+            .map(|x| {
+                PermissionType::from_string(&x.permission)
+                    .map(|permission| CreateTablePermission::new(target.id, permission, x.allow))
+            })
+            .collect::<Result<Vec<CreateTablePermission>, ReturnError>>()?;
+
+        insert_into(permissions_dsl::tables_permissions)
+            .values(&permissions)
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), &permissions))?;
+        Ok(())
+    }
+
+    fn column_of<'a>(
+        columns: &'a [CatalogColumn],
+        field: &Field,
+    ) -> Result<&'a CatalogColumn, ReturnError> {
+        columns
+            .iter()
+            .find(|x| x.matches_field(&field.name))
+            .ok_or(ReturnError::without_value(format!(
+                "Column for field \"{}\" not found",
+                field.name
+            )))
+    }
+
+    fn copy_rows(
+        conn: &mut PgConnection,
+        source: &Table,
+        target: &Table,
+        fields: &[Field],
+        filter: Option<&Map<String, Value>>,
+    ) -> Result<(), ReturnError> {
+        let (schema, name) = Catalog::split_name(&source.name);
+        let source_columns = Catalog::columns(conn, schema, Some(name))?;
+        let (schema, name) = Catalog::split_name(&target.name);
+        let target_columns = Catalog::columns(conn, schema, Some(name))?;
+
+        let mut from = Vec::new();
+        let mut to = Vec::new();
+        for field in fields {
+            from.push(Self::column_of(&source_columns, field)?.column_name.clone());
+            to.push(Self::column_of(&target_columns, field)?.column_name.clone());
+        }
+        // Copied rows keep their history, both tables share the audit setting
+        if source.audit {
+            for column in AUDIT_COLUMNS {
+                from.push(column.to_string());
+                to.push(column.to_string());
+            }
+        }
+
+        let empty = Map::new();
+        let filter = filter.unwrap_or(&empty);
+        let mut conditions = String::new();
+        for (i, key) in filter.keys().enumerate() {
+            let field = fields
+                .iter()
+                .find(|x| x.name.to_lowercase() == key.to_lowercase())
+                .ok_or(ReturnError::without_value(format!(
+                    "Column \"{}\" not found",
+                    key
+                )))?;
+            let column = Self::column_of(&source_columns, field)?;
+            conditions.push_str(if i == 0 { "WHERE " } else { " AND " });
+            conditions.push_str(&format!("{} = ${}", column.column_name, i + 1));
+        }
+
+        let query = sql_query(TableQueryBuilder::copy_rows(
+            &source.name,
+            &target.name,
+            &from,
+            &to,
+            &conditions,
+        ));
+        add_params(fields.iter(), filter, query, &FieldRules::NONE)?
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), filter))?;
+
+        // Copied ids were inserted explicitly, the sequence has to catch up with them
+        for field in fields.iter().filter(|x| x.is_auto_increment) {
+            let column = Self::column_of(&target_columns, field)?;
+            Self::execute(
+                conn,
+                TableQueryBuilder::reset_sequence(&target.name, &column.column_name),
+            )?;
+        }
+        Ok(())
+    }
+}
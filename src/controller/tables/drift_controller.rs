@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use diesel::delete;
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::update;
+
+use super::import_controller::ImportController;
+use super::structs::ColumnDrift;
+use super::structs::Create;
+use super::structs::RepairDirection;
+use super::structs::RepairDriftRequest;
+use super::structs::RepairDriftResult;
+use super::structs::SkippedTable;
+use super::structs::TableDrift;
+use crate::controller::fields::structs::CreateField;
+use crate::controller::fields::types::FieldType;
+use crate::models::cms::fields_model::Field;
+use crate::models::cms::table_model::Table;
+use crate::models::db::catalog::column_matches;
+use crate::models::db::catalog::Catalog;
+use crate::models::db::catalog::CatalogColumn;
+use crate::models::db::connection::establish_connection;
+use crate::models::db::locks::lock_table;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::customizations::dsl as customizations_dsl;
+use crate::schema::fields::dsl as fields_dsl;
+use crate::schema::row_policies::dsl as policies_dsl;
+use crate::schema::tables::dsl as tables_dsl;
+use crate::schema::tables_permissions::dsl as permissions_dsl;
+use crate::utils::sql::FieldQueryBuilder;
+use crate::utils::sql::TableQueryBuilder;
+use crate::utils::sql::AUDIT_COLUMNS;
+
+/// Compares the CMS metadata with the physical tables and reconciles them
+pub struct DriftController;
+
+impl DriftController {
+    pub fn report() -> Result<Vec<TableDrift>, ReturnError> {
+        let connection = &mut establish_connection();
+        let tables = Self::load_tables(connection, None)?;
+        let columns = Self::load_columns(connection, &tables)?;
+
+        let mut drifts = Vec::new();
+        for (table, fields) in &tables {
+            let drift = Self::diff(table, fields, Self::columns_of(&columns, &table.name));
+            if !drift.is_empty() {
+                drifts.push(drift);
+            }
+        }
+        Ok(drifts)
+    }
+
+    pub fn repair(request: RepairDriftRequest) -> Result<RepairDriftResult, ReturnError> {
+        let connection = &mut establish_connection();
+        let tables = Self::load_tables(connection, request.tables.as_ref())?;
+        let columns = Self::load_columns(connection, &tables)?;
+
+        let mut result = RepairDriftResult::default();
+        for (table, fields) in &tables {
+            let table_columns = Self::columns_of(&columns, &table.name);
+            let drift = Self::diff(table, fields, table_columns);
+            if drift.is_empty() {
+                continue;
+            }
+
+            // Every table is repaired on its own so a failure does not hold back the others
+            let repaired = connection.transaction(|conn| {
+                lock_table(conn, &table.name)?;
+                match request.direction {
+                    RepairDirection::Database => Self::repair_database(conn, table, fields, &drift),
+                    RepairDirection::Metadata => {
+                        Self::repair_metadata(conn, table, fields, table_columns, &drift)
+                    }
+                }
+            });
+            match repaired {
+                Ok(_) => result.repaired.push(drift),
+                Err(err) => result.failed.push(SkippedTable {
+                    name: table.name.clone(),
+                    reason: err.error_msg,
+                }),
+            }
+        }
+        Ok(result)
+    }
+
+    fn load_tables(
+        conn: &mut PgConnection,
+        names: Option<&Vec<String>>,
+    ) -> Result<Vec<(Table, Vec<Field>)>, ReturnError> {
+        // Views have no physical table to compare with
+        let mut query = tables_dsl::tables
+            .filter(tables_dsl::is_view.eq(false))
+            .filter(tables_dsl::is_deleted.eq(false))
+            .order(tables_dsl::id)
+            .into_boxed();
+        if let Some(names) = names {
+            query = query.filter(tables_dsl::name.eq_any(names));
+        }
+        let tables = query
+            .load::<Table>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), names))?;
+
+        if let Some(names) = names {
+            for name in names {
+                if !tables.iter().any(|x| &x.name == name) {
+                    return Err(ReturnError::new(
+                        format!("Table \"{}\" not found", name),
+                        names,
+                    ));
+                }
+            }
+        }
+
+        let fields = Field::belonging_to(&tables)
+            .order(fields_dsl::id)
+            .load::<Field>(conn)
+            .map_err(|err| ReturnError::without_value(err.to_string()))?
+            .grouped_by(&tables);
+
+        Ok(tables.into_iter().zip(fields).collect())
+    }
+
+    fn load_columns(
+        conn: &mut PgConnection,
+        tables: &[(Table, Vec<Field>)],
+    ) -> Result<HashMap<String, Vec<CatalogColumn>>, ReturnError> {
+        let mut columns: HashMap<String, Vec<CatalogColumn>> = HashMap::new();
+        for (table, _) in tables {
+            let (schema, name) = Catalog::split_name(&table.name);
+            let table_columns = Catalog::columns(conn, schema, Some(name))?;
+            columns.insert(table.name.clone(), table_columns);
+        }
+        Ok(columns)
+    }
+
+    fn columns_of<'a>(
+        columns: &'a HashMap<String, Vec<CatalogColumn>>,
+        table: &str,
+    ) -> &'a [CatalogColumn] {
+        columns.get(table).map(|x| x.as_slice()).unwrap_or_default()
+    }
+
+    fn matches(field: &Field, column: &CatalogColumn) -> bool {
+        column.matches_field(&field.name)
+    }
+
+    fn expects_not_null(field: &Field) -> bool {
+        field.is_primary_key || field.is_required
+    }
+
+    fn nullability(not_null: bool) -> String {
+        if not_null {
+            "NOT NULL".to_string()
+        } else {
+            "NULL".to_string()
+        }
+    }
+
+    fn diff(table: &Table, fields: &[Field], columns: &[CatalogColumn]) -> TableDrift {
+        let mut drift = TableDrift {
+            table: table.name.clone(),
+            ..Default::default()
+        };
+
+        if columns.is_empty() {
+            drift.missing_table = true;
+            return drift;
+        }
+
+        for field in fields {
+            let column = match columns.iter().find(|x| Self::matches(field, x)) {
+                Some(column) => column,
+                None => {
+                    drift.missing_columns.push(field.name.clone());
+                    continue;
+                }
+            };
+
+            let field_type = FieldType::from_string(&field.field_type).ok();
+            if field_type.is_none() || FieldType::from_pg_type(&column.udt_name) != field_type {
+                drift.type_mismatches.push(ColumnDrift {
+                    column: column.column_name.clone(),
+                    expected: field_type
+                        .map(|x| x.to_pg_type())
+                        .unwrap_or(field.field_type.clone()),
+                    actual: column.udt_name.clone(),
+                });
+            }
+
+            // Required fields are checked by the API, their columns are created nullable
+            let not_null = Self::expects_not_null(field);
+            if (!column.is_nullable && !not_null) || (column.is_nullable && field.is_primary_key) {
+                drift.nullability_mismatches.push(ColumnDrift {
+                    column: column.column_name.clone(),
+                    expected: Self::nullability(not_null),
+                    actual: Self::nullability(!column.is_nullable),
+                });
+            }
+        }
+
+        for column in columns {
+            let audit_column = table.audit && AUDIT_COLUMNS.contains(&column.column_name.as_str());
+            if !audit_column && !fields.iter().any(|x| Self::matches(x, column)) {
+                drift.extra_columns.push(column.column_name.clone());
+            }
+        }
+        drift
+    }
+
+    fn execute(conn: &mut PgConnection, query: String) -> Result<(), ReturnError> {
+        sql_query(&query)
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|err| ReturnError::new(err.to_string(), &query))
+    }
+
+    fn repair_database(
+        conn: &mut PgConnection,
+        table: &Table,
+        fields: &[Field],
+        drift: &TableDrift,
+    ) -> Result<(), ReturnError> {
+        let create_fields: Vec<CreateField> = fields.iter().map(|x| x.clone().to()).collect();
+
+        if drift.missing_table {
+            let mut create = Create::new(
+                table.name.clone(),
+                table.description.clone(),
+                Some(table.is_view),
+                Some(table.is_active),
+                Some(table.is_deleted),
+                table.view_sql.clone(),
+                table.capacity,
+            );
+            create.audit = Some(table.audit);
+            create.history = Some(table.history);
+            let builder = TableQueryBuilder::from_create(create, create_fields);
+            Self::execute(conn, builder.build_create_table())?;
+            if let Some(trigger) = builder.build_audit_trigger() {
+                Self::execute(conn, trigger)?;
+            }
+            return Ok(());
+        }
+
+        let missing: Vec<&CreateField> = create_fields
+            .iter()
+            .filter(|x| drift.missing_columns.contains(&x.name))
+            .collect();
+        if !missing.is_empty() {
+            Self::execute(
+                conn,
+                FieldQueryBuilder::from_vec(&table.name, missing).build_add(),
+            )?;
+        }
+
+        for column in &drift.extra_columns {
+            Self::execute(conn, FieldQueryBuilder::drop_column(&table.name, column))?;
+        }
+
+        for mismatch in &drift.type_mismatches {
+            let field = Self::field_of(fields, mismatch)?;
+            let field_type = FieldType::from_string(&field.field_type)?;
+            Self::execute(
+                conn,
+                FieldQueryBuilder::alter_type(&table.name, &mismatch.column, field_type),
+            )?;
+        }
+
+        for mismatch in &drift.nullability_mismatches {
+            let field = Self::field_of(fields, mismatch)?;
+            Self::execute(
+                conn,
+                FieldQueryBuilder::alter_not_null(
+                    &table.name,
+                    &mismatch.column,
+                    Self::expects_not_null(field),
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn repair_metadata(
+        conn: &mut PgConnection,
+        table: &Table,
+        fields: &[Field],
+        columns: &[CatalogColumn],
+        drift: &TableDrift,
+    ) -> Result<(), ReturnError> {
+        let now = chrono::Utc::now().naive_utc();
+
+        if drift.missing_table {
+            delete(
+                permissions_dsl::tables_permissions.filter(permissions_dsl::table_id.eq(table.id)),
+            )
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), &table.name))?;
+            delete(
+                customizations_dsl::customizations
+                    .filter(customizations_dsl::table_id.eq(table.id)),
+            )
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), &table.name))?;
+            delete(policies_dsl::row_policies.filter(policies_dsl::table_id.eq(table.id)))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &table.name))?;
+            delete(fields_dsl::fields.filter(fields_dsl::table_id.eq(table.id)))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &table.name))?;
+            delete(tables_dsl::tables.filter(tables_dsl::id.eq(table.id)))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &table.name))?;
+            return Ok(());
+        }
+
+        let missing: Vec<i32> = fields
+            .iter()
+            .filter(|x| drift.missing_columns.contains(&x.name))
+            .map(|x| x.id)
+            .collect();
+        delete(fields_dsl::fields.filter(fields_dsl::id.eq_any(&missing)))
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), &missing))?;
+
+        let mut extra = Vec::new();
+        for column in columns
+            .iter()
+            .filter(|x| drift.extra_columns.contains(&x.column_name))
+        {
+            let mut field = ImportController::to_field(column)?;
+            field.set_table(table.id);
+            extra.push(field);
+        }
+        insert_into(fields_dsl::fields)
+            .values(&extra)
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), &extra))?;
+
+        for mismatch in &drift.type_mismatches {
+            let field = Self::field_of(fields, mismatch)?;
+            let field_type = match FieldType::from_pg_type(&mismatch.actual) {
+                Some(field_type) => field_type,
+                None => {
+                    return Err(ReturnError::new(
+                        format!(
+                            "Column \"{}\" has unsupported type \"{}\"",
+                            mismatch.column, mismatch.actual
+                        ),
+                        mismatch,
+                    ));
+                }
+            };
+            update(fields_dsl::fields.filter(fields_dsl::id.eq(field.id)))
+                .set((
+                    fields_dsl::field_type.eq(field_type),
+                    fields_dsl::updated_at.eq(now),
+                ))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), mismatch))?;
+        }
+
+        for mismatch in &drift.nullability_mismatches {
+            let field = Self::field_of(fields, mismatch)?;
+            if field.is_primary_key {
+                return Err(ReturnError::new(
+                    format!("Primary key \"{}\" cannot be nullable", mismatch.column),
+                    mismatch,
+                ));
+            }
+            update(fields_dsl::fields.filter(fields_dsl::id.eq(field.id)))
+                .set((
+                    fields_dsl::is_required.eq(mismatch.actual == "NOT NULL"),
+                    fields_dsl::updated_at.eq(now),
+                ))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), mismatch))?;
+        }
+        Ok(())
+    }
+
+    fn field_of<'a>(fields: &'a [Field], mismatch: &ColumnDrift) -> Result<&'a Field, ReturnError> {
+        fields
+            .iter()
+            .find(|x| column_matches(&mismatch.column, &x.name))
+            .ok_or(ReturnError::new(
+                format!("Field for column \"{}\" not found", mismatch.column),
+                mismatch,
+            ))
+    }
+}
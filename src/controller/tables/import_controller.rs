@@ -0,0 +1,243 @@
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::result::OptionalExtension;
+
+use super::structs::Create;
+use super::structs::ImportResult;
+use super::structs::ImportTableRequest;
+use super::structs::ImportedTable;
+use super::structs::SkippedTable;
+use super::trash_controller::TRASH_PREFIX;
+use crate::controller::fields::structs::CreateField;
+use crate::controller::fields::types::FieldType;
+use crate::models::cms::fields_model::Field;
+use crate::models::cms::permission_model::DefaultPermissions;
+use crate::models::cms::permission_model::TablePermissions;
+use crate::models::cms::table_model::Table;
+use crate::models::db::catalog::Catalog;
+use crate::models::db::catalog::CatalogColumn;
+use crate::models::db::catalog::DEFAULT_SCHEMA;
+use crate::models::db::catalog::SYSTEM_TABLES;
+use crate::models::db::connection::establish_connection;
+use crate::models::db::locks::lock_table;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::fields::dsl as fields_dsl;
+use crate::schema::tables::dsl as tables_dsl;
+use crate::schema::tables_permissions::dsl as permissions_dsl;
+use crate::utils::string_utils::is_plain_identifier;
+
+/// Adopts tables created outside the server into the CMS metadata
+pub struct ImportController;
+
+impl ImportController {
+    pub fn import(request: ImportTableRequest) -> Result<ImportResult, ReturnError> {
+        let schema = request.schema.clone().unwrap_or(DEFAULT_SCHEMA.to_string());
+
+        if !is_plain_identifier(&schema) {
+            return Err(ReturnError::new(
+                format!("Invalid schema \"{}\"", schema),
+                &request,
+            ));
+        }
+
+        let connection = &mut establish_connection();
+
+        connection.transaction(|conn| {
+            let columns = Catalog::columns(conn, &schema, request.table.as_deref())?;
+
+            let names = match &request.table {
+                Some(table) => {
+                    if columns.is_empty() {
+                        return Err(ReturnError::new(
+                            format!("Table \"{}.{}\" not found", schema, table),
+                            &request,
+                        ));
+                    }
+                    vec![table.clone()]
+                }
+                None => Catalog::tables(conn, &schema)?,
+            };
+
+            let mut result = ImportResult::default();
+            for name in names {
+                let table_columns: Vec<&CatalogColumn> =
+                    columns.iter().filter(|x| x.table_name == name).collect();
+
+                let imported = lock_table(conn, Self::registered_name(&schema, &name))
+                    .and_then(|_| Self::check_importable(conn, &schema, &name))
+                    .and_then(|_| {
+                        let fields = Self::to_fields(&table_columns)?;
+                        Self::register(conn, &schema, &name, request.description.clone(), fields)
+                    });
+
+                match imported {
+                    Ok(imported) => result.imported.push(imported),
+                    // A single table import reports the reason, a schema import carries on
+                    Err(err) if request.table.is_some() => return Err(err),
+                    Err(err) => result.skipped.push(SkippedTable {
+                        name,
+                        reason: err.error_msg,
+                    }),
+                }
+            }
+            Ok(result)
+        })
+    }
+
+    /// Name the table will be served under, tables outside the default schema keep their qualifier
+    pub fn registered_name<S: AsRef<str>>(schema: S, table: S) -> String {
+        if schema.as_ref() == DEFAULT_SCHEMA {
+            table.as_ref().to_string()
+        } else {
+            format!("{}.{}", schema.as_ref(), table.as_ref())
+        }
+    }
+
+    fn check_importable(
+        conn: &mut PgConnection,
+        schema: &str,
+        name: &str,
+    ) -> Result<(), ReturnError> {
+        if schema == DEFAULT_SCHEMA && SYSTEM_TABLES.contains(&name) {
+            return Err(ReturnError::without_value(format!(
+                "Table \"{}\" is a system table",
+                name
+            )));
+        }
+        if !is_plain_identifier(name) {
+            return Err(ReturnError::without_value(format!(
+                "Table \"{}\" must have a lowercase name",
+                name
+            )));
+        }
+        if name.starts_with(TRASH_PREFIX) {
+            return Err(ReturnError::without_value(format!(
+                "Table \"{}\" is in the trash",
+                name
+            )));
+        }
+
+        let registered_name = Self::registered_name(schema, name);
+        let registered = tables_dsl::tables
+            .filter(tables_dsl::name.eq(&registered_name))
+            .first::<Table>(conn)
+            .optional()
+            .map_err(|err| ReturnError::new(err.to_string(), &registered_name))?;
+
+        if registered.is_some() {
+            return Err(ReturnError::without_value(format!(
+                "Table \"{}\" already exists",
+                registered_name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Builds the field metadata describing an existing column
+    pub fn to_field(column: &CatalogColumn) -> Result<CreateField, ReturnError> {
+        if !is_plain_identifier(&column.column_name) {
+            return Err(ReturnError::new(
+                format!(
+                    "Column \"{}\" must have a lowercase name",
+                    column.column_name
+                ),
+                column,
+            ));
+        }
+        let field_type = match FieldType::from_pg_type(&column.udt_name) {
+            Some(field_type) => field_type,
+            None => {
+                return Err(ReturnError::new(
+                    format!(
+                        "Column \"{}\" has unsupported type \"{}\"",
+                        column.column_name, column.udt_name
+                    ),
+                    column,
+                ));
+            }
+        };
+
+        let is_auto_increment = column.is_primary_key && column.is_auto_increment();
+        let default_value = if is_auto_increment {
+            None
+        } else {
+            column.column_default.clone()
+        };
+
+        Ok(CreateField {
+            id: None,
+            name: column.column_name.clone(),
+            description: None,
+            field_type,
+            table_id: None,
+            is_required: Some(!column.is_nullable && !column.is_primary_key),
+            is_primary_key: Some(column.is_primary_key),
+            is_auto_increment: Some(is_auto_increment),
+            is_generated: Some(false),
+            default_value,
+            is_unique: Some(column.is_unique || column.is_primary_key),
+            created_at: None,
+            updated_at: None,
+            custom_expression: None,
+        })
+    }
+
+    fn to_fields(columns: &[&CatalogColumn]) -> Result<Vec<CreateField>, ReturnError> {
+        let fields = columns
+            .iter()
+            .map(|column| Self::to_field(column))
+            .collect::<Result<Vec<CreateField>, ReturnError>>()?;
+
+        if fields.iter().filter(|x| x.is_pk()).count() != 1 {
+            return Err(ReturnError::without_value(
+                "Table must have a single column primary key".to_string(),
+            ));
+        }
+        Ok(fields)
+    }
+
+    fn register(
+        conn: &mut PgConnection,
+        schema: &str,
+        name: &str,
+        description: Option<String>,
+        fields: Vec<CreateField>,
+    ) -> Result<ImportedTable, ReturnError> {
+        let description = description.unwrap_or(format!("Imported from {}.{}", schema, name));
+        let table = Create::new(
+            Self::registered_name(schema, name),
+            description,
+            Some(false),
+            Some(true),
+            Some(false),
+            None,
+            None,
+        );
+
+        let table = insert_into(tables_dsl::tables)
+            .values(&table)
+            .get_result::<Table>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), &table))?;
+
+        let fields: Vec<CreateField> = fields
+            .into_iter()
+            .map(|mut field| {
+                field.set_table(table.id);
+                field
+            })
+            .collect();
+
+        let fields = insert_into(fields_dsl::fields)
+            .values(&fields)
+            .get_results::<Field>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), &fields))?;
+
+        let permissions = TablePermissions::default_permissions(table.id);
+        insert_into(permissions_dsl::tables_permissions)
+            .values(&permissions)
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), &permissions))?;
+
+        Ok(ImportedTable { table, fields })
+    }
+}
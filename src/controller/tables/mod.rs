@@ -1,3 +1,9 @@
+pub mod clone_controller;
+pub mod drift_controller;
+pub mod import_controller;
+pub mod rename_controller;
+pub mod row_policy_controller;
 pub mod table_controller;
 pub mod structs;
-pub mod permissions;
\ No newline at end of file
+pub mod permissions;
+pub mod trash_controller;
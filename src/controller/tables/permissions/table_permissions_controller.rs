@@ -8,7 +8,9 @@ use serde_json::json;
 
 use super::structs::CreateTablePermission;
 use super::structs::UpdateTablePermission;
+use crate::controller::login::auth_controller::Claims;
 use crate::controller::tables::table_controller::TableController;
+use crate::controller::users::role_controller::RoleController;
 use crate::controller::Controller;
 use crate::controller::GenericValue;
 use crate::controller::QueryParams;
@@ -17,6 +19,7 @@ use crate::models::cms::permission_model::DefaultPermissions;
 use crate::models::cms::permission_model::PermissionType;
 use crate::models::cms::permission_model::TablePermissions;
 use crate::models::cms::permission_model::PERMISSION_TYPES;
+use crate::models::cms::table_model::Table;
 use crate::models::db::connection::establish_connection;
 
 use crate::routes::utils::reponses::ReturnError;
@@ -186,6 +189,34 @@ impl TablePermissionsController {
             }
         }
     }
+    /// Same rule as the data API middleware: public permissions need no token, the rest need API
+    /// rights and a role granting the permission
+    pub fn authorize(
+        table: &Table,
+        permission: PermissionType,
+        claims: Option<&Claims>,
+    ) -> Result<(), ReturnError> {
+        // API keys stay within their scopes, even on public tables
+        if claims.is_some_and(|x| !x.allows(&table.name, permission.http_method())) {
+            return Err(ReturnError::new("Not authorized".to_string(), &table.name));
+        }
+        let permissions = Self::find_by_table_id(table.id)?;
+        if TablePermissions::check(permissions, permission) {
+            return Ok(());
+        }
+        match claims {
+            Some(claims) if claims.api_rights && claims.admin_rights => Ok(()),
+            Some(claims) if claims.api_rights => match claims.user_id() {
+                Some(user_id) if RoleController::allows(user_id, &table.name, permission)? => {
+                    Ok(())
+                }
+                _ => Err(ReturnError::new("Not authorized".to_string(), &table.name)),
+            },
+            Some(_) => Err(ReturnError::new("Not authorized".to_string(), &table.name)),
+            None => Err(ReturnError::new("Token missing".to_string(), &table.name)),
+        }
+    }
+
     pub fn exists<S: AsRef<str>>(table_id: i32, permission_name: S) -> bool {
         let connection = &mut establish_connection();
         let mut query = permissions_dsl::tables_permissions.into_boxed();
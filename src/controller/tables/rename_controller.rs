@@ -0,0 +1,139 @@
+use std::env;
+
+use diesel::delete;
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::sql_query;
+use dotenvy::dotenv;
+
+use super::import_controller::ImportController;
+use crate::models::cms::table_model::Table;
+use crate::models::db::catalog::Catalog;
+use crate::models::db::connection::establish_connection;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::table_aliases::dsl as aliases_dsl;
+use crate::schema::tables::dsl as tables_dsl;
+use crate::utils::sql::TableQueryBuilder;
+
+pub const DEFAULT_REDIRECT_DAYS: i64 = 30;
+
+/// Renames physical tables along with the objects named after them
+pub struct RenameController;
+
+impl RenameController {
+    /// Days the old name of a renamed table keeps redirecting, `0` disables the redirect
+    pub fn redirect_days() -> i64 {
+        dotenv().ok();
+        env::var("TABLE_RENAME_REDIRECT_DAYS")
+            .ok()
+            .and_then(|x| x.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_REDIRECT_DAYS)
+    }
+
+    /// Renames the table and its sequences, constraints and indexes, the caller holds both locks
+    pub fn rename(
+        conn: &mut PgConnection,
+        table: &Table,
+        new_name: &str,
+    ) -> Result<(), ReturnError> {
+        let (schema, old) = Catalog::split_name(&table.name);
+        let (new_schema, new) = Catalog::split_name(new_name);
+        if schema != new_schema {
+            return Err(ReturnError::without_value(format!(
+                "Table \"{}\" cannot be moved to schema \"{}\"",
+                table.name, new_schema
+            )));
+        }
+
+        let taken = tables_dsl::tables
+            .filter(tables_dsl::name.eq(new_name))
+            .filter(tables_dsl::is_deleted.eq(false))
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), new_name))?;
+        if taken > 0 {
+            return Err(ReturnError::without_value(format!(
+                "Table \"{}\" already exists",
+                new_name
+            )));
+        }
+
+        // Read before the rename, the catalog lookup goes by table name
+        let dependents = Catalog::dependents(conn, schema, old)?;
+        Self::execute(conn, TableQueryBuilder::rename_table(&table.name, new))?;
+
+        // Postgres names generated objects `{table}_...`, objects named otherwise are left alone
+        let prefix = format!("{}_", old);
+        for dependent in dependents {
+            let suffix = match dependent.name.strip_prefix(&prefix) {
+                Some(suffix) => suffix,
+                None => continue,
+            };
+            let renamed = format!("{}_{}", new, suffix);
+            let qualified = ImportController::registered_name(schema, &dependent.name);
+            let query = match dependent.kind.as_str() {
+                "sequence" => TableQueryBuilder::rename_sequence(&qualified, &renamed),
+                "index" => TableQueryBuilder::rename_index(&qualified, &renamed),
+                _ => TableQueryBuilder::rename_constraint(new_name, &dependent.name, &renamed),
+            };
+            Self::execute(conn, query)?;
+        }
+
+        Self::add_alias(conn, table, new_name)
+    }
+
+    fn add_alias(
+        conn: &mut PgConnection,
+        table: &Table,
+        new_name: &str,
+    ) -> Result<(), ReturnError> {
+        let now = chrono::Utc::now().naive_utc();
+        // The new name wins over a redirect that still pointed it to another table
+        delete(
+            aliases_dsl::table_aliases.filter(
+                aliases_dsl::name
+                    .eq(new_name)
+                    .or(aliases_dsl::name.eq(&table.name))
+                    .or(aliases_dsl::expires_at.lt(now)),
+            ),
+        )
+        .execute(conn)
+        .map_err(|err| ReturnError::new(err.to_string(), new_name))?;
+
+        let days = Self::redirect_days();
+        if days <= 0 {
+            return Ok(());
+        }
+        insert_into(aliases_dsl::table_aliases)
+            .values((
+                aliases_dsl::table_id.eq(table.id),
+                aliases_dsl::name.eq(&table.name),
+                aliases_dsl::expires_at.eq(now + chrono::Duration::days(days)),
+                aliases_dsl::created_at.eq(now),
+            ))
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|err| ReturnError::new(err.to_string(), &table.name))
+    }
+
+    /// Current name of a table that used to be called `name`, while the redirect lasts
+    pub fn resolve<S: AsRef<str>>(name: S) -> Result<Option<String>, ReturnError> {
+        let connection = &mut establish_connection();
+        aliases_dsl::table_aliases
+            .inner_join(tables_dsl::tables)
+            .filter(aliases_dsl::name.eq(name.as_ref()))
+            .filter(aliases_dsl::expires_at.gt(chrono::Utc::now().naive_utc()))
+            .filter(tables_dsl::is_deleted.eq(false))
+            .select(tables_dsl::name)
+            .first::<String>(connection)
+            .optional()
+            .map_err(|err| ReturnError::new(err.to_string(), name.as_ref()))
+    }
+
+    fn execute(conn: &mut PgConnection, query: String) -> Result<(), ReturnError> {
+        sql_query(&query)
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|err| ReturnError::new(err.to_string(), &query))
+    }
+}
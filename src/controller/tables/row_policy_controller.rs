@@ -0,0 +1,275 @@
+use diesel::delete;
+use diesel::insert_into;
+use diesel::prelude::*;
+
+use super::structs::CreateRowPolicy;
+use super::table_controller::TableController;
+use super::trash_controller::TrashController;
+use crate::controller::fields::field_controller::FieldController;
+use crate::controller::fields::types::FieldType;
+use crate::models::cms::fields_model::Field;
+use crate::models::cms::row_policy_model::{PolicyOperation, RowPolicy, OWNER_POLICY, TEAM_POLICY};
+use crate::models::cms::table_model::Table;
+use crate::models::db::catalog::Catalog;
+use crate::models::db::connection::establish_connection;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::fields::dsl as fields_dsl;
+use crate::schema::row_policies::dsl;
+use crate::schema::tables::dsl as tables_dsl;
+
+/// Who rows are read or written for, admins are not bound by row policies
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Viewer {
+    pub user: Option<i32>,
+    pub admin: bool,
+}
+
+/// Row policies are kept as metadata and turned into a condition on every row query.
+/// Postgres RLS is not used: the server connects as the owner of the CMS tables, which bypasses it.
+pub struct RowPolicyController;
+
+impl RowPolicyController {
+    pub fn find_all(table_name: &str) -> Result<Vec<RowPolicy>, ReturnError> {
+        let table = TableController::find_by_name(table_name)?;
+        Self::find_by_table_id(table.id)
+    }
+
+    fn find_by_table_id(table_id: i32) -> Result<Vec<RowPolicy>, ReturnError> {
+        let connection = &mut establish_connection();
+        dsl::row_policies
+            .filter(dsl::table_id.eq(table_id))
+            .order(dsl::id.asc())
+            .load::<RowPolicy>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), table_id))
+    }
+
+    pub fn create(table_name: &str, policy: CreateRowPolicy) -> Result<RowPolicy, ReturnError> {
+        let table = TableController::find_by_name(table_name)?;
+        let name = policy.name.trim().to_string();
+        if name.is_empty() {
+            return Err(ReturnError::new(
+                "Policy name is required".to_string(),
+                &policy,
+            ));
+        }
+        let fields = FieldController::find_all_by_table_name(&table.name)?;
+        let field = Self::field(&fields, &policy.field, &table.name)?;
+        let (membership_table, team_field, user_field) = match policy.kind.as_str() {
+            OWNER_POLICY => {
+                if FieldType::from_string(&field.field_type)? != FieldType::Integer {
+                    return Err(ReturnError::new(
+                        format!(r#"Owner field "{}" must hold user ids"#, field.name),
+                        &policy,
+                    ));
+                }
+                (None, None, None)
+            }
+            TEAM_POLICY => {
+                let missing = || {
+                    ReturnError::new(
+                        "Team policies need membershipTable, membershipTeamField and membershipUserField"
+                            .to_string(),
+                        &policy,
+                    )
+                };
+                let membership = TableController::find_by_name(
+                    policy.membership_table.as_ref().ok_or_else(missing)?,
+                )?;
+                let members = FieldController::find_all_by_table_name(&membership.name)?;
+                let team_field = Self::field(
+                    &members,
+                    policy.membership_team_field.as_ref().ok_or_else(missing)?,
+                    &membership.name,
+                )?;
+                let user_field = Self::field(
+                    &members,
+                    policy.membership_user_field.as_ref().ok_or_else(missing)?,
+                    &membership.name,
+                )?;
+                (
+                    Some(membership.id),
+                    Some(team_field.id),
+                    Some(user_field.id),
+                )
+            }
+            kind => {
+                return Err(ReturnError::new(
+                    format!(
+                        "Invalid policy kind `{}`, expected `{}` or `{}`",
+                        kind, OWNER_POLICY, TEAM_POLICY
+                    ),
+                    &policy,
+                ))
+            }
+        };
+        let operations = policy.operations.clone().unwrap_or(vec![
+            PolicyOperation::Read,
+            PolicyOperation::Update,
+            PolicyOperation::Delete,
+        ]);
+
+        let connection = &mut establish_connection();
+        let taken = dsl::row_policies
+            .filter(dsl::table_id.eq(table.id))
+            .filter(dsl::name.eq(&name))
+            .count()
+            .get_result::<i64>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &policy))?;
+        if taken > 0 {
+            return Err(ReturnError::new(
+                format!(
+                    r#"Policy "{}" already exists on table "{}""#,
+                    name, table.name
+                ),
+                &policy,
+            ));
+        }
+        insert_into(dsl::row_policies)
+            .values((
+                dsl::table_id.eq(table.id),
+                dsl::name.eq(&name),
+                dsl::kind.eq(&policy.kind),
+                dsl::field_id.eq(field.id),
+                dsl::membership_table_id.eq(membership_table),
+                dsl::membership_team_field_id.eq(team_field),
+                dsl::membership_user_field_id.eq(user_field),
+                dsl::operations.eq(serde_json::to_value(operations).unwrap()),
+                dsl::created_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .get_result::<RowPolicy>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &policy))
+    }
+
+    pub fn delete(table_name: &str, id: i32) -> Result<RowPolicy, ReturnError> {
+        let table = TableController::find_by_name(table_name)?;
+        let connection = &mut establish_connection();
+        delete(
+            dsl::row_policies
+                .filter(dsl::id.eq(id))
+                .filter(dsl::table_id.eq(table.id)),
+        )
+        .get_result::<RowPolicy>(connection)
+        .optional()
+        .map_err(|err| ReturnError::new(err.to_string(), id))?
+        .ok_or(ReturnError::new(
+            format!("Policy with id: {} not found", id),
+            id,
+        ))
+    }
+
+    /// Whether the viewer is bound by policies on the table, history snapshots can not be filtered
+    pub fn restricts(
+        table: &Table,
+        operation: PolicyOperation,
+        viewer: Viewer,
+    ) -> Result<bool, ReturnError> {
+        Self::predicate(table, operation, viewer).map(|x| x.is_some())
+    }
+
+    /// SQL condition on the rows of `table` aliased `t`, `None` when the viewer reaches every row.
+    /// The user id is inlined, it comes from the token and never from the request. Fields and the
+    /// membership table are named as they are now, a trashed membership table by its trash name.
+    pub fn predicate(
+        table: &Table,
+        operation: PolicyOperation,
+        viewer: Viewer,
+    ) -> Result<Option<String>, ReturnError> {
+        if viewer.admin {
+            return Ok(None);
+        }
+        let policies = Self::find_by_table_id(table.id)?
+            .into_iter()
+            .filter(|x| x.covers(operation))
+            .collect::<Vec<_>>();
+        if policies.is_empty() {
+            return Ok(None);
+        }
+        let user = match viewer.user {
+            Some(user) => user,
+            None => return Ok(Some("FALSE".to_string())),
+        };
+        let field_ids = policies
+            .iter()
+            .flat_map(|x| {
+                [
+                    Some(x.field_id),
+                    x.membership_team_field_id,
+                    x.membership_user_field_id,
+                ]
+            })
+            .flatten()
+            .collect::<Vec<i32>>();
+        let table_ids = policies
+            .iter()
+            .filter_map(|x| x.membership_table_id)
+            .collect::<Vec<i32>>();
+        let connection = &mut establish_connection();
+        let fields = fields_dsl::fields
+            .filter(fields_dsl::id.eq_any(&field_ids))
+            .load::<Field>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &field_ids))?;
+        let tables = tables_dsl::tables
+            .filter(tables_dsl::id.eq_any(&table_ids))
+            .load::<Table>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &table_ids))?;
+        let column = |policy: &RowPolicy, id: Option<i32>| {
+            fields
+                .iter()
+                .find(|x| Some(x.id) == id)
+                .map(|x| Self::quote(&x.name))
+                .ok_or(ReturnError::new(
+                    format!(r#"Policy "{}" points to a missing field"#, policy.name),
+                    policy.id,
+                ))
+        };
+
+        let conditions = policies
+            .iter()
+            .map(|policy| {
+                let field = column(policy, Some(policy.field_id))?;
+                if policy.kind != TEAM_POLICY {
+                    return Ok(format!("t.{} = {}", field, user));
+                }
+                let members = tables
+                    .iter()
+                    .find(|x| Some(x.id) == policy.membership_table_id)
+                    .ok_or(ReturnError::new(
+                        format!(r#"Policy "{}" has no membership table"#, policy.name),
+                        policy.id,
+                    ))?;
+                let members = match members.is_deleted {
+                    true => TrashController::trashed_name(members),
+                    false => members.name.clone(),
+                };
+                Ok(format!(
+                    "t.{} IN (SELECT m.{} FROM {} m WHERE m.{} = {})",
+                    field,
+                    column(policy, policy.membership_team_field_id)?,
+                    Self::quote_table(&members),
+                    column(policy, policy.membership_user_field_id)?,
+                    user
+                ))
+            })
+            .collect::<Result<Vec<String>, ReturnError>>()?;
+        Ok(Some(format!("({})", conditions.join(" OR "))))
+    }
+
+    fn field<'a>(fields: &'a [Field], name: &str, table: &str) -> Result<&'a Field, ReturnError> {
+        fields
+            .iter()
+            .find(|x| x.name == name)
+            .ok_or(ReturnError::without_value(format!(
+                r#"Field "{}" not found in table "{}""#,
+                name, table
+            )))
+    }
+
+    fn quote(name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    fn quote_table(name: &str) -> String {
+        let (schema, name) = Catalog::split_name(name);
+        format!("{}.{}", Self::quote(schema), Self::quote(name))
+    }
+}
@@ -3,10 +3,13 @@ use derive_more::derive::Debug;
 use diesel::{prelude::Identifiable, AsChangeset, Insertable};
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use crate::{
-    controller::fields::structs::CreateField, models::cms::table_model::Table,
+    controller::fields::structs::CreateField,
+    models::cms::{fields_model::Field, row_policy_model::PolicyOperation, table_model::Table},
     routes::utils::reponses::ReturnError,
+    utils::sql::AUDIT_COLUMNS,
 };
 
 #[derive(Serialize, Deserialize, AsChangeset, Clone, Debug)]
@@ -23,6 +26,7 @@ pub struct Update {
     pub capacity: Option<i32>,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub history: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -44,6 +48,8 @@ pub struct CreateTableRequest {
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
     pub fields: Option<Vec<CreateField>>,
+    pub audit: Option<bool>,
+    pub history: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Insertable, Clone, Debug, Identifiable, PartialEq)]
@@ -61,6 +67,8 @@ pub struct Create {
     pub capacity: Option<i32>,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub audit: Option<bool>,
+    pub history: Option<bool>,
 }
 
 impl Create {
@@ -84,9 +92,15 @@ impl Create {
             capacity,
             created_at: None,
             updated_at: None,
+            audit: None,
+            history: None,
         }
     }
 
+    pub fn is_audited(&self) -> bool {
+        self.audit.is_some_and(|x| x)
+    }
+
     pub fn to(&self) -> Table {
         Table {
             id: self.id.unwrap(),
@@ -99,11 +113,15 @@ impl Create {
             capacity: self.capacity,
             created_at: self.created_at,
             updated_at: self.updated_at,
+            version: 1,
+            deleted_at: None,
+            audit: self.is_audited(),
+            history: self.history.unwrap_or(false),
         }
     }
     pub fn from(table_request: CreateTableRequest) -> (Create, Vec<CreateField>) {
         let fields = table_request.fields.unwrap_or_default();
-        let table = Create::new(
+        let mut table = Create::new(
             table_request.name,
             table_request.description,
             table_request.is_view,
@@ -112,6 +130,8 @@ impl Create {
             table_request.view_sql,
             table_request.capacity,
         );
+        table.audit = table_request.audit;
+        table.history = table_request.history;
         (table, fields)
     }
 
@@ -153,9 +173,130 @@ impl Create {
         Ok(())
     }
 
+    /// Audit columns are added by the server, fields cannot take their names
+    pub fn validate_fields(&self, fields: &[CreateField]) -> Result<(), ReturnError> {
+        if !self.is_audited() {
+            return Ok(());
+        }
+        if let Some(field) = fields
+            .iter()
+            .find(|x| AUDIT_COLUMNS.contains(&x.name.to_lowercase().as_str()))
+        {
+            return self.this_error(format!(
+                "Field \"{}\" is an audit column and is added automatically",
+                field.name
+            ));
+        }
+        Ok(())
+    }
+
     pub fn normalize_name(&mut self) {
         self.name = self.name.trim().to_string();
         self.name = self.name.to_lowercase();
         self.name = self.name.replace(" ", "_");
     }
-}
\ No newline at end of file
+}
+/// `filter` restricts the copied rows to the ones whose fields equal the given values
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CloneTableRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub copy_data: Option<bool>,
+    pub filter: Option<Map<String, Value>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportTableRequest {
+    pub schema: Option<String>,
+    pub table: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedTable {
+    pub table: Table,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedTable {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub imported: Vec<ImportedTable>,
+    pub skipped: Vec<SkippedTable>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnDrift {
+    pub column: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Differences between the `tables`/`fields` metadata and the live catalog
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TableDrift {
+    pub table: String,
+    pub missing_table: bool,
+    pub missing_columns: Vec<String>,
+    pub extra_columns: Vec<String>,
+    pub type_mismatches: Vec<ColumnDrift>,
+    pub nullability_mismatches: Vec<ColumnDrift>,
+}
+
+impl TableDrift {
+    pub fn is_empty(&self) -> bool {
+        !self.missing_table
+            && self.missing_columns.is_empty()
+            && self.extra_columns.is_empty()
+            && self.type_mismatches.is_empty()
+            && self.nullability_mismatches.is_empty()
+    }
+}
+
+/// `metadata` rewrites `fields` to match the database, `database` alters the tables to match `fields`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RepairDirection {
+    Metadata,
+    Database,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RepairDriftRequest {
+    pub direction: RepairDirection,
+    pub tables: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RepairDriftResult {
+    pub repaired: Vec<TableDrift>,
+    pub failed: Vec<SkippedTable>,
+}
+
+/// `membership*` names the table linking users to teams, for `team` policies only.
+/// `operations` defaults to every operation.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateRowPolicy {
+    pub name: String,
+    pub kind: String,
+    pub field: String,
+    pub membership_table: Option<String>,
+    pub membership_team_field: Option<String>,
+    pub membership_user_field: Option<String>,
+    pub operations: Option<Vec<PolicyOperation>>,
+}
@@ -1,6 +1,5 @@
 use anyhow::Result;
 
-use diesel::delete;
 use diesel::insert_into;
 use diesel::prelude::*;
 use diesel::sql_query;
@@ -12,15 +11,21 @@ use super::permissions::table_permissions_controller::TablePermissionsController
 use super::structs::Create;
 use super::structs::CreateTableRequest;
 use super::structs::Update;
+use super::import_controller::ImportController;
+use super::rename_controller::RenameController;
+use super::trash_controller::TrashController;
 use crate::controller::fields::structs::CreateField;
 use crate::controller::fields::utils::validate_fields;
 use crate::controller::Controller;
 use crate::controller::GenericValue;
 use crate::controller::QueryParams;
+use crate::controller::Versioned;
 use crate::controller::API_LIMIT;
 use crate::models::cms::permission_model::DefaultPermissions;
 use crate::models::cms::permission_model::TablePermissions;
+use crate::models::db::catalog::Catalog;
 use crate::models::db::connection::establish_connection;
+use crate::models::db::locks::lock_table;
 
 use crate::models::cms::table_model::Table;
 use crate::routes::utils::reponses::ReturnError;
@@ -36,34 +41,15 @@ impl Controller<Table, CreateTableRequest> for TableController {
         let connection = &mut establish_connection();
 
         let transaction: std::result::Result<Table, ReturnError> = connection.transaction(|conn| {
-            let query = delete(tables_dsl::tables).filter(tables_dsl::id.eq(&id));
+            let table = tables_dsl::tables
+                .filter(tables_dsl::id.eq(&id))
+                .filter(tables_dsl::is_deleted.eq(false))
+                .first::<Table>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), id))?;
+            lock_table(conn, &table.name)?;
 
-            match query.get_result::<Table>(conn) {
-                Ok(res) => {
-                    let drop_sql = TableQueryBuilder::drop_table(&res.name);
-                    let drop_table = sql_query(drop_sql).execute(conn);
-
-                    match drop_table {
-                        Ok(_) => {
-                            return Ok(res); // if Successful, return the deleted data
-                        }
-                        Err(err) => {
-                            return Err(ReturnError {
-                                error_msg: err.to_string(),
-                                values: Some(id.into()),
-                            }
-                            .into());
-                        }
-                    }
-                }
-                Err(err) => {
-                    return Err(ReturnError {
-                        error_msg: err.to_string(),
-                        values: Some(id.into()),
-                    }
-                    .into());
-                }
-            }
+            // Tables go to the trash first, they are only dropped once purged
+            TrashController::trash(conn, &table)
         });
 
         transaction
@@ -110,10 +96,27 @@ impl Controller<Table, CreateTableRequest> for TableController {
         if validation_result.is_err() {
             return Err(validation_result.unwrap_err());
         }
+        table.validate_fields(&fields)?;
 
         let connection = &mut establish_connection();
 
         let transaction: std::result::Result<Table, ReturnError> = connection.transaction(|conn| {
+            lock_table(conn, &table.name)?;
+
+            // Another request may have created it while we waited for the lock
+            let exists = tables_dsl::tables
+                .filter(tables_dsl::name.eq(&table.name))
+                .filter(tables_dsl::is_deleted.eq(false))
+                .count()
+                .get_result::<i64>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &table))?;
+            if exists > 0 {
+                return Err(ReturnError::new(
+                    format!("Table \"{}\" already exists", table.name),
+                    &table,
+                ));
+            }
+
             let query = insert_into(tables_dsl::tables).values(&table);
             match query.get_result::<Table>(conn) {
                 Ok(res_table) => {
@@ -136,6 +139,11 @@ impl Controller<Table, CreateTableRequest> for TableController {
                             let create_table = sql_query(query_table).execute(conn);
                             match create_table {
                                 Ok(_) => {
+                                    if let Some(trigger) = builder.build_audit_trigger() {
+                                        sql_query(&trigger).execute(conn).map_err(|err| {
+                                            ReturnError::new(err.to_string(), &trigger)
+                                        })?;
+                                    }
                                     let values =
                                         TablePermissions::default_permissions(res_table.id);
                                     let query = insert_into(permissions_dsl::tables_permissions)
@@ -187,7 +195,7 @@ impl Controller<Table, CreateTableRequest> for TableController {
     }
     fn update(table_id: i32, new_table: GenericValue) -> Result<Table, ReturnError> {
         // cast Any to Update
-        let new_table = new_table.to::<Update>();
+        let new_table = new_table.to::<Versioned<Update>>();
         if new_table.is_err() {
             return Err(ReturnError {
                 error_msg: new_table.unwrap_err().to_string(),
@@ -195,29 +203,82 @@ impl Controller<Table, CreateTableRequest> for TableController {
             }
             .into());
         }
-        let new_table = new_table.unwrap();
+        let mut new_table = new_table.unwrap();
         let connection = &mut establish_connection();
-        match update(tables_dsl::tables)
-            .set(&new_table)
-            .filter(tables_dsl::id.eq(table_id))
-            .get_result::<Table>(connection)
-        {
-            Ok(res) => {
-                // TODO: Adicionar SQL para realizar o update da tabela
-                return Ok(res); // if Successful, return the ID of the inserted table
+        connection.transaction(|conn| {
+            let old = tables_dsl::tables
+                .filter(tables_dsl::id.eq(table_id))
+                .filter(tables_dsl::is_deleted.eq(false))
+                .first::<Table>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &new_table))?;
+
+            // A new name goes through the same rules as on creation, the schema is kept apart
+            let rename = match new_table.data.name.as_ref() {
+                Some(name) => {
+                    let (schema, name) = Catalog::split_name(name);
+                    let mut renamed = Create::new(
+                        name.to_string(),
+                        old.description.clone(),
+                        None,
+                        None,
+                        None,
+                        None,
+                        None,
+                    );
+                    renamed.normalize_name();
+                    renamed.validate()?;
+                    let name = ImportController::registered_name(schema, &renamed.name);
+                    new_table.data.name = Some(name.clone());
+                    Some(name).filter(|x| *x != old.name)
+                }
+                None => None,
+            };
+
+            lock_table(conn, &old.name)?;
+            if let Some(name) = rename.as_ref() {
+                lock_table(conn, name)?;
             }
-            Err(err) => {
-                return Err(ReturnError {
-                    error_msg: err.to_string(),
-                    values: Some(serde_json::to_value(new_table).unwrap()),
+
+            // Re-read under the lock, a concurrent edit may have committed meanwhile
+            let current = tables_dsl::tables
+                .filter(tables_dsl::id.eq(table_id))
+                .filter(tables_dsl::is_deleted.eq(false))
+                .first::<Table>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &new_table))?;
+            new_table.check(&old.name, current.version)?;
+            // The lock was taken on the name read before it, a rename also changes the version
+            let old = current;
+
+            if let Some(name) = rename.as_ref() {
+                RenameController::rename(conn, &old, name)?;
+            }
+
+            match update(tables_dsl::tables)
+                .set((
+                    &new_table.data,
+                    tables_dsl::version.eq(tables_dsl::version + 1),
+                ))
+                .filter(tables_dsl::id.eq(table_id))
+                .get_result::<Table>(conn)
+            {
+                Ok(res) => {
+                    return Ok(res); // if Successful, return the ID of the inserted table
+                }
+                Err(err) => {
+                    return Err(ReturnError {
+                        error_msg: err.to_string(),
+                        values: Some(serde_json::to_value(new_table).unwrap()),
+                    }
+                    .into()); // if Successful, return the ID of the inserted table
                 }
-                .into()); // if Successful, return the ID of the inserted table
             }
-        }
+        })
     }
     fn find_all(query_params: QueryParams) -> Result<Vec<Table>, ReturnError> {
         let connection = &mut establish_connection();
-        let mut query = tables_dsl::tables.into_boxed();
+        let mut query = tables_dsl::tables
+            .filter(tables_dsl::is_deleted.eq(false))
+            .into_boxed();
 
         if let Some(id_query) = query_params.id {
             query = query.filter(tables_dsl::id.eq(id_query)); // Search for a unique table
@@ -243,6 +304,7 @@ impl Controller<Table, CreateTableRequest> for TableController {
         let connection: &mut PgConnection = &mut establish_connection();
         let mut query = tables_dsl::tables.into_boxed();
         query = query.filter(tables_dsl::id.eq(id)); // Search for a unique table
+        query = query.filter(tables_dsl::is_deleted.eq(false)); // Trashed tables are hidden
         match query.first::<Table>(connection) {
             Ok(results) => return Ok(results),
             Err(err) => {
@@ -262,6 +324,7 @@ impl TableController {
         let connection: &mut PgConnection = &mut establish_connection();
         let mut query = tables_dsl::tables.into_boxed();
         query = query.filter(tables_dsl::name.eq(name.as_ref()));
+        query = query.filter(tables_dsl::is_deleted.eq(false)); // Trashed tables are hidden
         match query.first::<Table>(connection) {
             Ok(results) => return Ok(results),
             Err(err) => {
@@ -285,54 +348,27 @@ impl TableController {
             }
             .into());
         }
-        let id = Self::find_by_name(name)?.id;
+        let table = Self::find_by_name(name)?;
         let transaction = connection.transaction(|conn| {
-            let query = delete(tables_dsl::tables).filter(tables_dsl::id.eq(&id));
+            lock_table(conn, name)?;
 
-            match query.execute(conn) {
-                Ok(_) => {
-                    let drop_sql = TableQueryBuilder::drop_table(&name);
-                    let delete_sql = TableQueryBuilder::delete_fields(&name);
-                    let delete_fields = sql_query(delete_sql).execute(conn);
-
-                    match delete_fields {
-                        Ok(_) => {
-                            let drop_table = sql_query(drop_sql).execute(conn);
-                            match drop_table {
-                                Ok(_) => {
-                                    let json = json!({"status":"Ok","table":&name});
-                                    return Ok(json);
-                                }
-                                Err(err) => {
-                                    return Err(ReturnError {
-                                        error_msg: err.to_string(),
-                                        values: Some(id.into()),
-                                    }
-                                    .into());
-                                }
-                            }
-                        }
-                        Err(err) => {
-                            return Err(ReturnError {
-                                error_msg: err.to_string(),
-                                values: Some(id.into()),
-                            }
-                            .into());
-                        }
-                    }
-                }
-                Err(err) => {
-                    return Err(ReturnError {
-                        error_msg: err.to_string(),
-                        values: Some(id.into()),
-                    }
-                    .into());
-                }
-            }
+            // Tables go to the trash first, they are only dropped once purged
+            TrashController::trash(conn, &table)?;
+            let json = json!({"status":"Ok","table":&name});
+            Ok(json)
         });
 
         transaction
     }
+    /// Field changes alter the table as well, so they invalidate copies of it held by clients
+    pub fn bump_version(conn: &mut PgConnection, table_id: i32) -> Result<(), ReturnError> {
+        update(tables_dsl::tables)
+            .filter(tables_dsl::id.eq(table_id))
+            .set(tables_dsl::version.eq(tables_dsl::version + 1))
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|err| ReturnError::new(err.to_string(), table_id))
+    }
     pub fn get_table_permissions<S: AsRef<str>>(
         table_name: S,
     ) -> Result<Vec<TablePermissions>, ReturnError> {
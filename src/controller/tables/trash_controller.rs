@@ -0,0 +1,181 @@
+use std::env;
+
+use diesel::delete;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::update;
+use dotenvy::dotenv;
+use log::error;
+
+use super::import_controller::ImportController;
+use crate::models::cms::table_model::Table;
+use crate::models::db::catalog::Catalog;
+use crate::models::db::connection::establish_connection;
+use crate::models::db::locks::lock_table;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::customizations::dsl as customizations_dsl;
+use crate::schema::fields::dsl as fields_dsl;
+use crate::schema::row_policies::dsl as policies_dsl;
+use crate::schema::tables::dsl as tables_dsl;
+use crate::schema::tables_permissions::dsl as permissions_dsl;
+use crate::utils::sql::TableQueryBuilder;
+
+/// Physical tables in the trash are renamed to `_del_{id}_{name}`
+pub const TRASH_PREFIX: &str = "_del_";
+pub const DEFAULT_PURGE_DAYS: i64 = 30;
+
+/// Deleted tables are kept around for `TRASH_PURGE_DAYS` before being dropped
+pub struct TrashController;
+
+impl TrashController {
+    pub fn purge_days() -> i64 {
+        dotenv().ok();
+        env::var("TRASH_PURGE_DAYS")
+            .ok()
+            .and_then(|x| x.parse::<i64>().ok())
+            .unwrap_or(DEFAULT_PURGE_DAYS)
+    }
+
+    /// Name of the physical table while it sits in the trash, schema qualified like `name`
+    pub fn trashed_name(table: &Table) -> String {
+        let (schema, name) = Catalog::split_name(&table.name);
+        ImportController::registered_name(schema, &Self::trashed_table(table.id, name))
+    }
+
+    fn trashed_table(id: i32, name: &str) -> String {
+        format!("{}{}_{}", TRASH_PREFIX, id, name)
+    }
+
+    /// Hides the table from the API and moves its data out of the way, the caller holds the lock
+    pub fn trash(conn: &mut PgConnection, table: &Table) -> Result<Table, ReturnError> {
+        let (_, name) = Catalog::split_name(&table.name);
+        let rename =
+            TableQueryBuilder::rename_table(&table.name, &Self::trashed_table(table.id, name));
+        sql_query(&rename)
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), &rename))?;
+
+        update(tables_dsl::tables)
+            .filter(tables_dsl::id.eq(table.id))
+            .set((
+                tables_dsl::is_deleted.eq(true),
+                tables_dsl::deleted_at.eq(Some(chrono::Utc::now().naive_utc())),
+                tables_dsl::version.eq(tables_dsl::version + 1),
+            ))
+            .get_result::<Table>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), table.id))
+    }
+
+    pub fn find_all() -> Result<Vec<Table>, ReturnError> {
+        let connection = &mut establish_connection();
+        tables_dsl::tables
+            .filter(tables_dsl::is_deleted.eq(true))
+            .order(tables_dsl::deleted_at.desc())
+            .load::<Table>(connection)
+            .map_err(|err| ReturnError::without_value(err.to_string()))
+    }
+
+    fn find_trashed(conn: &mut PgConnection, id: i32) -> Result<Table, ReturnError> {
+        tables_dsl::tables
+            .filter(tables_dsl::id.eq(id))
+            .filter(tables_dsl::is_deleted.eq(true))
+            .first::<Table>(conn)
+            .optional()
+            .map_err(|err| ReturnError::new(err.to_string(), id))?
+            .ok_or(ReturnError::new(
+                format!("Table with id {} not found in trash", id),
+                id,
+            ))
+    }
+
+    pub fn restore(id: i32) -> Result<Table, ReturnError> {
+        let connection = &mut establish_connection();
+        connection.transaction(|conn| {
+            let table = Self::find_trashed(conn, id)?;
+            lock_table(conn, &table.name)?;
+
+            // The name may have been reused while the table was in the trash
+            let taken = tables_dsl::tables
+                .filter(tables_dsl::name.eq(&table.name))
+                .filter(tables_dsl::is_deleted.eq(false))
+                .count()
+                .get_result::<i64>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), id))?;
+            if taken > 0 {
+                return Err(ReturnError::new(
+                    format!("Table \"{}\" already exists", table.name),
+                    id,
+                ));
+            }
+
+            let (_, name) = Catalog::split_name(&table.name);
+            let rename = TableQueryBuilder::rename_table(&Self::trashed_name(&table), name);
+            sql_query(&rename)
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &rename))?;
+
+            update(tables_dsl::tables)
+                .filter(tables_dsl::id.eq(id))
+                .set((
+                    tables_dsl::is_deleted.eq(false),
+                    tables_dsl::deleted_at.eq(None::<chrono::NaiveDateTime>),
+                    tables_dsl::version.eq(tables_dsl::version + 1),
+                ))
+                .get_result::<Table>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), id))
+        })
+    }
+
+    /// Drops a trashed table and every piece of metadata pointing to it
+    pub fn purge(id: i32) -> Result<Table, ReturnError> {
+        let connection = &mut establish_connection();
+        connection.transaction(|conn| {
+            let table = Self::find_trashed(conn, id)?;
+            lock_table(conn, &table.name)?;
+
+            let drop_sql = TableQueryBuilder::drop_table(&Self::trashed_name(&table));
+            sql_query(&drop_sql)
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &drop_sql))?;
+
+            delete(permissions_dsl::tables_permissions.filter(permissions_dsl::table_id.eq(id)))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), id))?;
+            delete(customizations_dsl::customizations.filter(customizations_dsl::table_id.eq(id)))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), id))?;
+            // Policies point to the fields, policies of other tables still pointing here refuse the purge
+            delete(policies_dsl::row_policies.filter(policies_dsl::table_id.eq(id)))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), id))?;
+            delete(fields_dsl::fields.filter(fields_dsl::table_id.eq(id)))
+                .execute(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), id))?;
+            delete(tables_dsl::tables.filter(tables_dsl::id.eq(id)))
+                .get_result::<Table>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), id))
+        })
+    }
+
+    /// Purges every table that has been in the trash longer than the purge period. A table that
+    /// cannot be dropped is logged and left for the next run, the others are still purged.
+    pub fn purge_expired() -> Result<Vec<Table>, ReturnError> {
+        let limit = chrono::Utc::now().naive_utc() - chrono::Duration::days(Self::purge_days());
+        let connection = &mut establish_connection();
+        let expired = tables_dsl::tables
+            .filter(tables_dsl::is_deleted.eq(true))
+            .filter(tables_dsl::deleted_at.lt(limit))
+            .select(tables_dsl::id)
+            .load::<i32>(connection)
+            .map_err(|err| ReturnError::without_value(err.to_string()))?;
+
+        let mut purged = Vec::new();
+        for id in expired {
+            match Self::purge(id) {
+                Ok(table) => purged.push(table),
+                Err(err) => error!("Could not purge table {} from the trash: {}", id, err),
+            }
+        }
+        Ok(purged)
+    }
+}
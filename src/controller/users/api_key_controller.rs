@@ -0,0 +1,146 @@
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::update;
+use serde::{Deserialize, Serialize};
+
+use crate::controller::login::auth_controller::{AuthController, AuthError, Claims};
+use crate::controller::login::token_controller::TokenController;
+use crate::models::cms::permission_model::PERMISSION_TYPES_HTTP_EQUIVALENT;
+use crate::models::db::connection::establish_connection;
+use crate::models::users::api_key_model::{ApiKey, ANY};
+use crate::models::users::users_model::User;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::api_keys::dsl;
+
+use super::structs::CreateApiKey;
+
+pub const KEY_PREFIX: &str = "ak_";
+/// Characters of the key kept in `prefix`
+pub const PREFIX_LENGTH: usize = 11;
+/// Seconds between two writes of `last_used_at` for the same key
+pub const LAST_USED_PRECISION_SECS: i64 = 60;
+
+/// Key returned once, when it is created
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+pub struct ApiKeyController;
+
+impl ApiKeyController {
+    pub fn find_all(user_id: i32) -> Result<Vec<ApiKey>, ReturnError> {
+        let connection = &mut establish_connection();
+        dsl::api_keys
+            .filter(dsl::user_id.eq(user_id))
+            .order(dsl::id.asc())
+            .load::<ApiKey>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), user_id))
+    }
+
+    pub fn create(user_id: i32, new_key: CreateApiKey) -> Result<CreatedApiKey, ReturnError> {
+        if new_key.name.trim().is_empty() {
+            return Err(ReturnError::new(
+                "API key name is required".to_string(),
+                &new_key,
+            ));
+        }
+        if new_key.scopes.is_empty() {
+            return Err(ReturnError::new(
+                "API key needs at least one scope".to_string(),
+                &new_key,
+            ));
+        }
+        for scope in &new_key.scopes {
+            for method in &scope.methods {
+                let known = method == ANY
+                    || PERMISSION_TYPES_HTTP_EQUIVALENT
+                        .iter()
+                        .any(|(_, http)| http.eq_ignore_ascii_case(method));
+                if !known {
+                    return Err(ReturnError::new(
+                        format!("Invalid method `{}` in API key scope", method),
+                        &new_key,
+                    ));
+                }
+            }
+        }
+
+        let key = format!("{}{}", KEY_PREFIX, TokenController::random_hex(24));
+        let connection = &mut establish_connection();
+        let api_key = insert_into(dsl::api_keys)
+            .values((
+                dsl::user_id.eq(user_id),
+                dsl::name.eq(new_key.name.trim()),
+                dsl::prefix.eq(&key[..PREFIX_LENGTH]),
+                dsl::key_hash.eq(TokenController::hash(&key)),
+                dsl::scopes.eq(serde_json::to_value(&new_key.scopes).unwrap()),
+                dsl::expires_at.eq(new_key.expires_at),
+                dsl::created_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .get_result::<ApiKey>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), &new_key))?;
+
+        Ok(CreatedApiKey { key, api_key })
+    }
+
+    /// Revoked keys are kept so their last use stays visible
+    pub fn revoke(user_id: i32, id: i32) -> Result<ApiKey, ReturnError> {
+        let connection = &mut establish_connection();
+        update(
+            dsl::api_keys
+                .filter(dsl::id.eq(id))
+                .filter(dsl::user_id.eq(user_id)),
+        )
+        .set(dsl::revoked_at.eq(chrono::Utc::now().naive_utc()))
+        .get_result::<ApiKey>(connection)
+        .optional()
+        .map_err(|err| ReturnError::new(err.to_string(), id))?
+        .ok_or(ReturnError::new(
+            format!("API key with id: {} not found", id),
+            id,
+        ))
+    }
+
+    /// Loads the key and its user, with claims restricted to the key scopes
+    pub fn authenticate(key: &str) -> Result<(Claims, User), AuthError> {
+        let now = chrono::Utc::now().naive_utc();
+        let connection = &mut establish_connection();
+        let api_key = dsl::api_keys
+            .filter(dsl::key_hash.eq(TokenController::hash(key)))
+            .first::<ApiKey>(connection)
+            .optional()
+            .map_err(|err| AuthError::Db(ReturnError::without_value(err.to_string())))?
+            .ok_or(AuthError::BadKey)?;
+        if api_key.revoked_at.is_some() || api_key.expires_at.is_some_and(|x| x < now) {
+            return Err(AuthError::BadKey);
+        }
+
+        let user = AuthController::active_user(api_key.user_id)?;
+
+        let stale = now - chrono::Duration::seconds(LAST_USED_PRECISION_SECS);
+        if api_key.last_used_at.is_none_or(|x| x < stale) {
+            update(dsl::api_keys.filter(dsl::id.eq(api_key.id)))
+                .set(dsl::last_used_at.eq(now))
+                .execute(connection)
+                .map_err(|err| AuthError::Db(ReturnError::new(err.to_string(), api_key.id)))?;
+        }
+
+        let claims = Claims {
+            exp: api_key
+                .expires_at
+                .map_or(usize::MAX, |x| x.and_utc().timestamp() as usize),
+            api_rights: user.api_rights,
+            admin_rights: user.admin,
+            sub: Some(user.id.to_string()),
+            iat: api_key.created_at.and_utc().timestamp() as usize,
+            jti: format!("api-key-{}", api_key.id),
+            iss: AuthController::issuer(),
+            scopes: Some(api_key.scopes()),
+        };
+        Ok((claims, user))
+    }
+}
@@ -1,3 +1,5 @@
+pub mod api_key_controller;
+pub mod role_controller;
 pub mod structs;
 pub mod user_controller;
 pub mod utils;
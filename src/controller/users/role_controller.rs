@@ -0,0 +1,365 @@
+use std::env;
+
+use diesel::delete;
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::update;
+use dotenvy::dotenv;
+use serde::{Deserialize, Serialize};
+
+use crate::models::cms::permission_model::PermissionType;
+use crate::models::db::connection::establish_connection;
+use crate::models::users::permissions_model::UserPermissions;
+use crate::models::users::role_model::{Role, RoleGrant, ANY, RESERVED_SCOPES, SCHEMA_GRANT};
+use crate::models::users::users_model::User;
+use crate::routes::utils::reponses::ReturnError;
+use crate::schema::role_grants::dsl as grants_dsl;
+use crate::schema::roles::dsl;
+use crate::schema::users_permissions::dsl as user_permissions_dsl;
+use crate::schema::users_roles::dsl as user_roles_dsl;
+
+use super::structs::{CreateRole, GrantData, UpdateRole};
+
+pub const DEFAULT_ROLE: &str = "editor";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleDetails {
+    #[serde(flatten)]
+    pub role: Role,
+    pub grants: Vec<RoleGrant>,
+    pub user_ids: Vec<i32>,
+}
+
+/// Roles grant methods on tables to the users they are assigned to.
+/// `users_permissions` rows override the roles of a user for a method on every table.
+pub struct RoleController;
+
+impl RoleController {
+    pub fn find_all() -> Result<Vec<RoleDetails>, ReturnError> {
+        let connection = &mut establish_connection();
+        let roles = dsl::roles
+            .order(dsl::id.asc())
+            .load::<Role>(connection)
+            .map_err(|err| ReturnError::without_value(err.to_string()))?;
+        roles
+            .into_iter()
+            .map(|role| Self::details(connection, role))
+            .collect()
+    }
+
+    pub fn find(id: i32) -> Result<RoleDetails, ReturnError> {
+        let connection = &mut establish_connection();
+        let role = Self::find_in(connection, id)?;
+        Self::details(connection, role)
+    }
+
+    pub fn create(new_role: CreateRole) -> Result<RoleDetails, ReturnError> {
+        let name = new_role.name.trim().to_string();
+        if name.is_empty() {
+            return Err(ReturnError::new(
+                "Role name is required".to_string(),
+                new_role,
+            ));
+        }
+        let grants = Self::normalize(&new_role.grants)?;
+        let connection = &mut establish_connection();
+        connection.transaction::<_, ReturnError, _>(|conn| {
+            if Self::name_taken(conn, &name, None)? {
+                return Err(ReturnError::new(
+                    format!(r#"Role "{}" already exists"#, name),
+                    &new_role,
+                ));
+            }
+            let role = insert_into(dsl::roles)
+                .values((
+                    dsl::name.eq(&name),
+                    dsl::description.eq(&new_role.description),
+                    dsl::created_at.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .get_result::<Role>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &new_role))?;
+            Self::replace_grants(conn, role.id, &grants)?;
+            Self::details(conn, role)
+        })
+    }
+
+    pub fn update(id: i32, changes: UpdateRole) -> Result<RoleDetails, ReturnError> {
+        let grants = match changes.grants.as_ref() {
+            Some(grants) => Some(Self::normalize(grants)?),
+            None => None,
+        };
+        let connection = &mut establish_connection();
+        connection.transaction::<_, ReturnError, _>(|conn| {
+            let mut role = Self::find_in(conn, id)?;
+            if let Some(name) = changes.name.as_ref().map(|x| x.trim()) {
+                if name.is_empty() {
+                    return Err(ReturnError::new(
+                        "Role name is required".to_string(),
+                        &changes,
+                    ));
+                }
+                if Self::name_taken(conn, name, Some(id))? {
+                    return Err(ReturnError::new(
+                        format!(r#"Role "{}" already exists"#, name),
+                        &changes,
+                    ));
+                }
+                role.name = name.to_string();
+            }
+            if changes.description.is_some() {
+                role.description = changes.description.clone();
+            }
+            let role = update(dsl::roles.filter(dsl::id.eq(id)))
+                .set((
+                    dsl::name.eq(&role.name),
+                    dsl::description.eq(&role.description),
+                ))
+                .get_result::<Role>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &changes))?;
+            if let Some(grants) = grants.as_ref() {
+                Self::replace_grants(conn, id, grants)?;
+            }
+            Self::details(conn, role)
+        })
+    }
+
+    /// Grants and assignments go with the role
+    pub fn delete(id: i32) -> Result<Role, ReturnError> {
+        let connection = &mut establish_connection();
+        delete(dsl::roles.filter(dsl::id.eq(id)))
+            .get_result::<Role>(connection)
+            .optional()
+            .map_err(|err| ReturnError::new(err.to_string(), id))?
+            .ok_or(ReturnError::new(
+                format!("Role with id: {} not found", id),
+                id,
+            ))
+    }
+
+    pub fn assign(role_id: i32, user_id: i32) -> Result<RoleDetails, ReturnError> {
+        let connection = &mut establish_connection();
+        let role = Self::find_in(connection, role_id)?;
+        insert_into(user_roles_dsl::users_roles)
+            .values((
+                user_roles_dsl::user_id.eq(user_id),
+                user_roles_dsl::role_id.eq(role_id),
+            ))
+            .on_conflict_do_nothing()
+            .execute(connection)
+            .map_err(|err| {
+                let message = err.to_string();
+                if message.contains("foreign key") {
+                    ReturnError::new(format!("User with id: {} not found", user_id), user_id)
+                } else {
+                    ReturnError::new(message, user_id)
+                }
+            })?;
+        Self::details(connection, role)
+    }
+
+    /// Gives a new user with API rights the `DEFAULT_ROLE`, if that role exists
+    pub fn assign_default(conn: &mut PgConnection, user: &User) -> Result<(), ReturnError> {
+        dotenv().ok();
+        let name = env::var("DEFAULT_ROLE").unwrap_or(DEFAULT_ROLE.to_string());
+        if !user.api_rights || name.is_empty() {
+            return Ok(());
+        }
+        let role_id = dsl::roles
+            .filter(dsl::name.eq(&name))
+            .select(dsl::id)
+            .first::<i32>(conn)
+            .optional()
+            .map_err(|err| ReturnError::new(err.to_string(), &name))?;
+        match role_id {
+            Some(role_id) => insert_into(user_roles_dsl::users_roles)
+                .values((
+                    user_roles_dsl::user_id.eq(user.id),
+                    user_roles_dsl::role_id.eq(role_id),
+                ))
+                .on_conflict_do_nothing()
+                .execute(conn)
+                .map(|_| ())
+                .map_err(|err| ReturnError::new(err.to_string(), user.id)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn unassign(role_id: i32, user_id: i32) -> Result<RoleDetails, ReturnError> {
+        let connection = &mut establish_connection();
+        let role = Self::find_in(connection, role_id)?;
+        delete(
+            user_roles_dsl::users_roles
+                .filter(user_roles_dsl::role_id.eq(role_id))
+                .filter(user_roles_dsl::user_id.eq(user_id)),
+        )
+        .execute(connection)
+        .map_err(|err| ReturnError::new(err.to_string(), user_id))?;
+        Self::details(connection, role)
+    }
+
+    /// Whether the user may use the method on the table. A `users_permissions` row for the method
+    /// decides on its own, otherwise one of the user's roles has to grant it. Grants on `*` only
+    /// cover CMS tables, see `RESERVED_SCOPES`.
+    pub fn allows(
+        user_id: i32,
+        table: &str,
+        permission: PermissionType,
+    ) -> Result<bool, ReturnError> {
+        let connection = &mut establish_connection();
+        let overrides = user_permissions_dsl::users_permissions
+            .filter(user_permissions_dsl::user_id.eq(user_id))
+            .load::<UserPermissions>(connection)
+            .map_err(|err| ReturnError::new(err.to_string(), user_id))?;
+        let matching = overrides
+            .iter()
+            .filter(|x| PermissionType::from_string(&x.permission).ok() == Some(permission))
+            .collect::<Vec<_>>();
+        if !matching.is_empty() {
+            // A denial wins over an allowance
+            return Ok(matching.iter().all(|x| x.allow));
+        }
+
+        Self::granted(
+            connection,
+            user_id,
+            table,
+            &[ANY.to_string(), permission.to_string()],
+        )
+    }
+
+    /// Whether one of the user's roles holds the `Schema` grant
+    pub fn is_schema_admin(user_id: i32) -> Result<bool, ReturnError> {
+        let connection = &mut establish_connection();
+        Self::granted(connection, user_id, ANY, &[SCHEMA_GRANT.to_string()])
+    }
+
+    fn granted(
+        conn: &mut PgConnection,
+        user_id: i32,
+        table: &str,
+        permissions: &[String],
+    ) -> Result<bool, ReturnError> {
+        let role_ids = user_roles_dsl::users_roles
+            .filter(user_roles_dsl::user_id.eq(user_id))
+            .select(user_roles_dsl::role_id);
+        let tables = match RESERVED_SCOPES.contains(&table) {
+            true => vec![table],
+            false => vec![ANY, table],
+        };
+        grants_dsl::role_grants
+            .filter(grants_dsl::role_id.eq_any(role_ids))
+            .filter(grants_dsl::table_name.eq_any(tables))
+            .filter(grants_dsl::permission.eq_any(permissions))
+            .count()
+            .get_result::<i64>(conn)
+            .map(|x| x > 0)
+            .map_err(|err| ReturnError::new(err.to_string(), user_id))
+    }
+
+    /// Grants with their permission spelled as stored, `Schema` is only given on `*`
+    fn normalize(grants: &[GrantData]) -> Result<Vec<GrantData>, ReturnError> {
+        grants
+            .iter()
+            .map(|grant| {
+                let table = grant.table.trim().to_string();
+                if table.is_empty() {
+                    return Err(ReturnError::new(
+                        "Grant table is required".to_string(),
+                        grant,
+                    ));
+                }
+                let permission = if grant.permission == ANY {
+                    ANY.to_string()
+                } else if grant.permission.eq_ignore_ascii_case(SCHEMA_GRANT) {
+                    if table != ANY {
+                        return Err(ReturnError::new(
+                            format!("`{}` is only granted on `*`", SCHEMA_GRANT),
+                            grant,
+                        ));
+                    }
+                    SCHEMA_GRANT.to_string()
+                } else {
+                    PermissionType::from_string(&grant.permission)?.to_string()
+                };
+                Ok(GrantData { table, permission })
+            })
+            .collect()
+    }
+
+    fn replace_grants(
+        conn: &mut PgConnection,
+        role_id: i32,
+        grants: &[GrantData],
+    ) -> Result<(), ReturnError> {
+        delete(grants_dsl::role_grants.filter(grants_dsl::role_id.eq(role_id)))
+            .execute(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), role_id))?;
+        if grants.is_empty() {
+            return Ok(());
+        }
+        let values = grants
+            .iter()
+            .map(|grant| {
+                (
+                    grants_dsl::role_id.eq(role_id),
+                    grants_dsl::table_name.eq(&grant.table),
+                    grants_dsl::permission.eq(&grant.permission),
+                )
+            })
+            .collect::<Vec<_>>();
+        insert_into(grants_dsl::role_grants)
+            .values(values)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|err| ReturnError::new(err.to_string(), grants))
+    }
+
+    fn find_in(conn: &mut PgConnection, id: i32) -> Result<Role, ReturnError> {
+        dsl::roles
+            .filter(dsl::id.eq(id))
+            .first::<Role>(conn)
+            .optional()
+            .map_err(|err| ReturnError::new(err.to_string(), id))?
+            .ok_or(ReturnError::new(
+                format!("Role with id: {} not found", id),
+                id,
+            ))
+    }
+
+    fn name_taken(
+        conn: &mut PgConnection,
+        name: &str,
+        except: Option<i32>,
+    ) -> Result<bool, ReturnError> {
+        let mut query = dsl::roles.filter(dsl::name.eq(name)).into_boxed();
+        if let Some(id) = except {
+            query = query.filter(dsl::id.ne(id));
+        }
+        query
+            .count()
+            .get_result::<i64>(conn)
+            .map(|x| x > 0)
+            .map_err(|err| ReturnError::new(err.to_string(), name))
+    }
+
+    fn details(conn: &mut PgConnection, role: Role) -> Result<RoleDetails, ReturnError> {
+        let grants = grants_dsl::role_grants
+            .filter(grants_dsl::role_id.eq(role.id))
+            .order(grants_dsl::id.asc())
+            .load::<RoleGrant>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), role.id))?;
+        let user_ids = user_roles_dsl::users_roles
+            .filter(user_roles_dsl::role_id.eq(role.id))
+            .select(user_roles_dsl::user_id)
+            .order(user_roles_dsl::user_id.asc())
+            .load::<i32>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), role.id))?;
+        Ok(RoleDetails {
+            role,
+            grants,
+            user_ids,
+        })
+    }
+}
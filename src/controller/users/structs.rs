@@ -2,6 +2,8 @@ use chrono::NaiveDateTime;
 use diesel::{AsChangeset, Insertable};
 use serde::{Deserialize, Serialize};
 
+use crate::models::users::api_key_model::ApiKeyScope;
+
 #[derive(Serialize, Deserialize, AsChangeset, Clone, Debug)]
 #[diesel(table_name = crate::schema::users)]
 #[serde(rename_all = "camelCase")]
@@ -13,6 +15,9 @@ pub struct Update {
     pub api_rights: Option<bool>,
     pub admin: Option<bool>,
     pub updated_at: Option<NaiveDateTime>,
+    /// Cleared whenever an admin sets `blocked`, the block is then theirs
+    #[serde(skip)]
+    pub auto_blocked_at: Option<Option<NaiveDateTime>>,
 }
 
 #[derive(Serialize, Deserialize, Insertable, Clone, Debug)]
@@ -29,3 +34,43 @@ pub struct Create {
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKey {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GrantData {
+    /// Table name, `*` for every table
+    #[serde(default = "any_table")]
+    pub table: String,
+    /// `Query`, `Create`, `Replace`, `Update`, `Delete`, `*` or `Schema`
+    pub permission: String,
+}
+
+fn any_table() -> String {
+    "*".to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateRole {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub grants: Vec<GrantData>,
+}
+
+/// `grants` replaces every grant of the role when given
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRole {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub grants: Option<Vec<GrantData>>,
+}
@@ -4,6 +4,7 @@ use diesel::delete;
 use diesel::insert_into;
 use diesel::prelude::*;
 use diesel::update;
+use log::error;
 
 use crate::controller::Controller;
 use crate::controller::QueryParams;
@@ -13,6 +14,7 @@ use crate::models::users::users_model::User;
 use crate::routes::utils::reponses::ReturnError;
 use crate::schema::users::dsl;
 
+use super::role_controller::RoleController;
 use super::structs::Create;
 use super::structs::Update;
 use super::utils::password::PasswordUtils;
@@ -46,21 +48,19 @@ impl Controller<User, Create, Update> for UserController {
             });
         }
 
-        new_user.password = PasswordUtils::hash(new_user.password); // Hash password
-        let query = insert_into(dsl::users).values(&new_user);
-
-        match query.get_result::<User>(connection) {
-            Ok(res) => {
-                return Ok(res);
-            }
-            Err(err) => {
-                return Err(ReturnError::new(err.to_string(), new_user));
-            }
-        }
+        new_user.password = PasswordUtils::hash(new_user.password)?; // Hash password
+        connection.transaction(|conn| {
+            let query = insert_into(dsl::users).values(&new_user);
+            let user = query
+                .get_result::<User>(conn)
+                .map_err(|err| ReturnError::new(err.to_string(), &new_user))?;
+            RoleController::assign_default(conn, &user)?;
+            Ok(user)
+        })
     }
     fn update(id: i32, mut new_user: Update) -> Result<User, ReturnError> {
         if new_user.password.is_some() {
-            new_user.password = Some(PasswordUtils::hash(new_user.password.unwrap()));
+            new_user.password = Some(PasswordUtils::hash(new_user.password.unwrap())?);
         }
 
         let connection = &mut establish_connection();
@@ -118,7 +118,13 @@ impl UserController {
             return false;
         }
 
-        new_user.password = PasswordUtils::hash(new_user.password); // Hash password
+        new_user.password = match PasswordUtils::hash(new_user.password) {
+            Ok(hash) => hash,
+            Err(err) => {
+                error!("Cannot hash the password of the default admin: {}", err);
+                return false;
+            }
+        };
         let query = insert_into(dsl::users).values(&new_user);
         match query.get_result::<User>(connection) {
             Ok(_) => {
@@ -139,6 +145,15 @@ impl UserController {
             Err(err) => return Err(ReturnError::new(err.to_string(), user_email)),
         }
     }
+    /// Replaces the stored hash only, unlike `update` which hashes a new password
+    pub fn set_password_hash(user_id: i32, hash: &str) -> Result<(), ReturnError> {
+        let connection = &mut establish_connection();
+        update(dsl::users.filter(dsl::id.eq(user_id)))
+            .set(dsl::password.eq(hash))
+            .execute(connection)
+            .map(|_| ())
+            .map_err(|err| ReturnError::new(err.to_string(), user_id))
+    }
 }
 
 fn user_exists(email: &str) -> bool {
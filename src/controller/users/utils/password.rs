@@ -1,23 +1,92 @@
 use std::env;
 
+use argon2::{Config, Variant, Version};
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
 use dotenvy::dotenv;
-pub struct PasswordUtils;
 
-impl PasswordUtils {
-    pub fn hash(password: String) -> String {
+use crate::routes::utils::reponses::ReturnError;
+
+/// OWASP recommended Argon2id parameters
+pub const DEFAULT_MEMORY_KIB: u32 = 19456;
+pub const DEFAULT_ITERATIONS: u32 = 2;
+pub const DEFAULT_PARALLELISM: u32 = 1;
+/// Bytes of the random salt of each password
+pub const SALT_LENGTH: usize = 16;
+
+/// Argon2id cost, from `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS` and `ARGON2_PARALLELISM`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl PasswordParams {
+    pub fn from_env() -> PasswordParams {
         dotenv().ok();
+        let var = |name: &str, default: u32| {
+            env::var(name)
+                .ok()
+                .and_then(|x| x.parse::<u32>().ok())
+                .unwrap_or(default)
+        };
+        PasswordParams {
+            memory_kib: var("ARGON2_MEMORY_KIB", DEFAULT_MEMORY_KIB),
+            iterations: var("ARGON2_ITERATIONS", DEFAULT_ITERATIONS),
+            parallelism: var("ARGON2_PARALLELISM", DEFAULT_PARALLELISM),
+        }
+    }
 
-        let binding = env::var("SALT").expect("SALT must be set");
-        let salt = binding.as_bytes();
+    fn config<'a>(&self) -> Config<'a> {
+        Config {
+            variant: Variant::Argon2id,
+            version: Version::Version13,
+            mem_cost: self.memory_kib,
+            time_cost: self.iterations,
+            lanes: self.parallelism,
+            ..Config::rfc9106_low_mem()
+        }
+    }
+}
 
-        let password = password.as_bytes();
-        let config = argon2::Config::default();
+pub struct PasswordUtils;
 
-        let hash = argon2::hash_encoded(password, salt, &config).unwrap();
+impl PasswordUtils {
+    /// PHC string of the password, with a random salt and the configured parameters
+    pub fn hash(password: String) -> Result<String, ReturnError> {
+        let salt: [u8; SALT_LENGTH] = rand::random();
+        argon2::hash_encoded(
+            password.as_bytes(),
+            &salt,
+            &PasswordParams::from_env().config(),
+        )
+        .map_err(|err| ReturnError::without_value(format!("Cannot hash password: {}", err)))
+    }
 
-        return hash;
+    /// Fails instead of answering `false` when the stored hash cannot be read
+    pub fn verify(password: &str, hash: &str) -> Result<bool, ReturnError> {
+        argon2::verify_encoded(hash, password.as_bytes()).map_err(|err| {
+            ReturnError::without_value(format!("Stored password hash is invalid: {}", err))
+        })
     }
-    pub fn verify(password: &str, hash: &str) -> bool {
-        argon2::verify_encoded(hash, password.as_bytes()).unwrap()
+
+    /// Whether the hash predates the current parameters or was made with the global `SALT`
+    /// that every password used to share
+    pub fn needs_rehash(hash: &str) -> bool {
+        let params = PasswordParams::from_env();
+        let expected = format!(
+            "$argon2id$v=19$m={},t={},p={}$",
+            params.memory_kib, params.iterations, params.parallelism
+        );
+        let salt = match hash.strip_prefix(&expected).and_then(|x| x.split_once('$')) {
+            Some((salt, _)) => salt,
+            None => return true,
+        };
+        dotenv().ok();
+        let legacy = env::var("SALT")
+            .ok()
+            .is_some_and(|x| STANDARD_NO_PAD.encode(x.as_bytes()) == salt);
+        legacy || salt.len() < STANDARD_NO_PAD.encode([0; SALT_LENGTH]).len()
     }
-}
\ No newline at end of file
+}
@@ -1,15 +1,21 @@
 use actix_server::config::query_cfg;
 
+use actix_server::controller::login::jwt_keys::JwtKeys;
+use actix_server::controller::tables::trash_controller::TrashController;
 use actix_server::controller::users::structs::Create;
 use actix_server::controller::users::user_controller;
-use actix_server::middlewares::{CHECK_LOGIN, SHOULD_CHECK_LOGIN};
+use actix_server::middlewares::{CHECK_LOGIN, IDEMPOTENCY, SHOULD_CHECK_LOGIN};
 use actix_server::models::db::connection::db_poll;
 use actix_server::routes::scopes::Scopes;
+use actix_server::utils::mailer::mailer;
 
 use actix_web::middleware::{Compress, DefaultHeaders, Logger, NormalizePath};
-use actix_web::{App, HttpServer};
+use actix_web::{rt, App, HttpServer};
 use env_logger::Env;
-use log::info;
+use log::{error, info};
+use std::time::Duration;
+
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -18,6 +24,10 @@ async fn main() -> std::io::Result<()> {
     if init_server().await {
         info!("Created default user!");
     }
+    spawn_trash_purge();
+    // A misconfigured key or mailer stops the server here instead of failing the first login
+    JwtKeys::get();
+    mailer();
     let db_poll = db_poll();
     HttpServer::new(move || {
         App::new()
@@ -29,18 +39,46 @@ async fn main() -> std::io::Result<()> {
             .wrap(NormalizePath::new(
                 actix_web::middleware::TrailingSlash::Always,
             )) // Normalize trailing slash(Resolve the "/" at ending of a endpoint)
-            .service(Scopes::posts_scope())
-            .service(Scopes::users_scope().wrap(CHECK_LOGIN))
+            // The last `wrap` runs first, scope-wide login checks come before idempotency keys
+            .service(Scopes::posts_scope().wrap(IDEMPOTENCY))
+            .service(Scopes::users_scope().wrap(IDEMPOTENCY).wrap(CHECK_LOGIN))
+            .service(Scopes::roles_scope().wrap(CHECK_LOGIN))
             .service(Scopes::login_scope())
+            .service(Scopes::logout_scope())
+            .service(Scopes::auth_scope())
+            .service(Scopes::well_known_scope())
             .service(Scopes::fields_scope().wrap(CHECK_LOGIN))
             .service(Scopes::tables_scope().wrap(CHECK_LOGIN))
-            .service(Scopes::custom_scope().wrap(SHOULD_CHECK_LOGIN))
+            .service(Scopes::schema_scope().wrap(CHECK_LOGIN))
+            .service(
+                Scopes::custom_scope()
+                    .wrap(IDEMPOTENCY)
+                    .wrap(SHOULD_CHECK_LOGIN),
+            )
     })
     .bind(("127.0.0.1", 8080))?
     .run()
     .await
 }
 
+/// Periodically drops the tables that stayed in the trash longer than `TRASH_PURGE_DAYS`
+fn spawn_trash_purge() {
+    rt::spawn(async {
+        let mut interval = rt::time::interval(TRASH_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match rt::task::spawn_blocking(TrashController::purge_expired).await {
+                Ok(Ok(purged)) if !purged.is_empty() => {
+                    info!("Purged {} table(s) from the trash", purged.len())
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => error!("Could not purge the trash: {}", err),
+                Err(err) => error!("Could not purge the trash: {}", err),
+            }
+        }
+    });
+}
+
 async fn init_server() -> bool {
     let created = user_controller::UserController::create_default_admin({
         Create {
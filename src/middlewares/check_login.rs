@@ -3,16 +3,24 @@ use std::future::{ready, Ready};
 use actix_web::{
     body::EitherBody,
     dev::{self, Service, ServiceRequest, ServiceResponse, Transform},
+    http::Method,
     Error, HttpMessage, HttpResponse,
 };
 use futures_util::future::LocalBoxFuture;
 
 use crate::{
-    controller::login::auth_controller::AuthController, routes::utils::reponses::ReturnError,
+    controller::{login::current_user::CurrentUser, users::role_controller::RoleController},
+    models::cms::permission_model::TablePermissions,
+    routes::utils::reponses::ReturnError,
 };
 
 pub struct CheckLogin;
 
+/// Routes managing tables and fields, `/tables/{table}/fields` included
+const SCHEMA_SCOPES: [&str; 2] = ["tables", "schema"];
+/// Every user manages their own API keys
+const SELF_SERVICE_PATH: &str = "/users/api-keys";
+
 impl<S, B> Transform<S, ServiceRequest> for CheckLogin
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
@@ -50,38 +58,45 @@ where
         let path = request.path().to_string();
         let path = path.split("/").filter(|x| !x.is_empty()).last().unwrap();
 
-        // Check if has token
-        let is_logged_in = request.headers().get("Authorization").is_some();
         let mut error_ret = ReturnError {
             error_msg: "Token missing".to_string(),
             values: Some(path.into()),
         };
         // Check if token is missing
-        if !is_logged_in {
+        let authenticated = match super::authenticate(&request) {
+            Some(authenticated) => authenticated,
+            None => {
+                let (request, _pl) = request.into_parts();
+                // Get path variable
+                let path = request.path().to_string();
+                let path = path.split("/").filter(|x| !x.is_empty()).last().unwrap();
+                error_ret.values = Some(path.into());
+                let response = HttpResponse::Unauthorized()
+                    .json(error_ret)
+                    // constructed responses map to "right" body
+                    .map_into_right_body();
+
+                return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+            }
+        };
+
+        if let Err(err) = authenticated {
             let (request, _pl) = request.into_parts();
-            // Get path variable
-            let path = request.path().to_string();
-            let path = path.split("/").filter(|x| !x.is_empty()).last().unwrap();
-            error_ret.values = Some(path.into());
-            let response = HttpResponse::Unauthorized()
+            let status = super::refusal_status(&err);
+            let err: ReturnError = err.into();
+            error_ret.error_msg = err.error_msg;
+            let response = HttpResponse::build(status)
                 .json(error_ret)
                 // constructed responses map to "right" body
                 .map_into_right_body();
 
             return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
         }
-        let token = request
-            .headers()
-            .get("Authorization")
-            .unwrap()
-            .to_str()
-            .expect("Error getting token");
-
-        let (authorized, claims) = AuthController::verify_jwt(token.to_owned());
 
-        if !authorized {
+        let (claims, user) = authenticated.unwrap();
+        if !user.api_rights {
             let (request, _pl) = request.into_parts();
-            error_ret.error_msg = "Token invalid".to_string();
+            error_ret.error_msg = "Not authorized".to_string();
             let response = HttpResponse::Unauthorized()
                 .json(error_ret)
                 // constructed responses map to "right" body
@@ -90,11 +105,17 @@ where
             return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
         }
 
-        let claims = claims.unwrap();
-        if !claims.api_rights {
+        // API key scopes name these routes by their first segment: `users`, `tables`...
+        let scope = request
+            .path()
+            .split("/")
+            .find(|x| !x.is_empty())
+            .unwrap_or_default()
+            .to_string();
+        if !claims.allows(&scope, request.method().as_str()) {
             let (request, _pl) = request.into_parts();
-            error_ret.error_msg = "Not authorized".to_string();
-            let response = HttpResponse::Unauthorized()
+            error_ret.error_msg = "API key scope does not allow this request".to_string();
+            let response = HttpResponse::Forbidden()
                 .json(error_ret)
                 // constructed responses map to "right" body
                 .map_into_right_body();
@@ -102,7 +123,45 @@ where
             return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
         }
 
+        // Roles hold grants on these routes by the same name, writes to the schema need `Schema`
+        let allowed = if user.admin || request.path().starts_with(SELF_SERVICE_PATH) {
+            Ok(true)
+        } else if SCHEMA_SCOPES.contains(&scope.as_str()) {
+            match request.method() == Method::GET {
+                true => Ok(true),
+                false => RoleController::is_schema_admin(user.id),
+            }
+        } else {
+            match TablePermissions::resolve_method(request.method().as_str()) {
+                Ok(permission) => RoleController::allows(user.id, &scope, permission),
+                Err(_) => Ok(false),
+            }
+        };
+        match allowed {
+            Ok(true) => {}
+            Ok(false) => {
+                let (request, _pl) = request.into_parts();
+                error_ret.error_msg = "Not authorized".to_string();
+                let response = HttpResponse::Forbidden()
+                    .json(error_ret)
+                    // constructed responses map to "right" body
+                    .map_into_right_body();
+
+                return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+            }
+            Err(err) => {
+                let (request, _pl) = request.into_parts();
+                let response = HttpResponse::InternalServerError()
+                    .json(err)
+                    // constructed responses map to "right" body
+                    .map_into_right_body();
+
+                return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+            }
+        }
+
         request.extensions_mut().insert(claims);
+        request.extensions_mut().insert(CurrentUser(user));
 
         let res = self.service.call(request);
 
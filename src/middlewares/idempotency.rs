@@ -0,0 +1,179 @@
+use std::future::{ready, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{self, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::{ErrorInternalServerError, PayloadError},
+    http::{Method, StatusCode},
+    web::Bytes,
+    Error, HttpMessage, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use futures_util::stream::{self, Stream};
+use log::error;
+
+use crate::{
+    controller::{
+        idempotency::idempotency_controller::{IdempotencyController, IdempotencyState},
+        login::current_user::CurrentUser,
+    },
+    routes::utils::reponses::ReturnError,
+};
+
+pub const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+pub const IDEMPOTENT_REPLAYED: &str = "Idempotent-Replayed";
+
+/// Replays the stored response of a `POST` sent again with the same `Idempotency-Key`.
+/// Keys belong to the authenticated user, or to anonymous requests when there is none.
+/// Only successful responses are stored, a failed request can be retried with its key.
+pub struct Idempotency;
+
+impl<S, B> Transform<S, ServiceRequest> for Idempotency
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = IdempotencyMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(IdempotencyMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+pub struct IdempotencyMiddleware<S> {
+    service: Rc<S>,
+}
+
+fn release(user_id: Option<i32>, key: &str) {
+    if let Err(err) = IdempotencyController::release(user_id, key) {
+        error!("Could not release idempotency key \"{}\": {}", key, err);
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for IdempotencyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, mut request: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let key = match request.headers().get(IDEMPOTENCY_KEY) {
+            Some(key) if request.method() == Method::POST => key.to_str().ok().map(String::from),
+            _ => None,
+        };
+        let key = match key {
+            Some(key) => key,
+            None => {
+                let res = service.call(request);
+                return Box::pin(
+                    async move { res.await.map(ServiceResponse::map_into_boxed_body) },
+                );
+            }
+        };
+
+        Box::pin(async move {
+            // The body is read to fingerprint the request, then handed back to the handler
+            let payload = request.extract::<Bytes>().await?;
+            let fingerprint = IdempotencyController::fingerprint(
+                request.method().as_str(),
+                request.path(),
+                request.query_string(),
+                &payload,
+            );
+            // Set by the login middlewares when they run first, authenticated here otherwise
+            let current = request.extensions().get::<CurrentUser>().map(|x| x.0.id);
+            let user_id = current.or_else(|| {
+                super::authenticate(&request)
+                    .and_then(|x| x.ok())
+                    .map(|(_, user)| user.id)
+            });
+            let stream: Pin<Box<dyn Stream<Item = Result<Bytes, PayloadError>>>> =
+                Box::pin(stream::once(async move { Ok(payload) }));
+            request.set_payload(Payload::from(stream));
+
+            let state = match IdempotencyController::begin(user_id, &key, &fingerprint) {
+                Ok(state) => state,
+                Err(err) => return Ok(request.into_response(HttpResponse::BadRequest().json(err))),
+            };
+            match state {
+                IdempotencyState::New => {}
+                IdempotencyState::Mismatch => {
+                    let error_ret = ReturnError {
+                        error_msg: "Idempotency key was already used for a different request"
+                            .to_string(),
+                        values: Some(key.into()),
+                    };
+                    return Ok(
+                        request.into_response(HttpResponse::UnprocessableEntity().json(error_ret))
+                    );
+                }
+                IdempotencyState::InProgress => {
+                    let error_ret = ReturnError {
+                        error_msg: "A request with this idempotency key is still in progress"
+                            .to_string(),
+                        values: Some(key.into()),
+                    };
+                    return Ok(request.into_response(HttpResponse::Conflict().json(error_ret)));
+                }
+                IdempotencyState::Replay(stored) => {
+                    let status = stored
+                        .status_code
+                        .and_then(|x| StatusCode::from_u16(x as u16).ok())
+                        .unwrap_or(StatusCode::OK);
+                    let response = HttpResponse::build(status)
+                        .insert_header((IDEMPOTENT_REPLAYED, "true"))
+                        .body(stored.response_body.unwrap_or_default());
+                    return Ok(request.into_response(response));
+                }
+            }
+
+            let response = match service.call(request).await {
+                Ok(response) => response,
+                Err(err) => {
+                    release(user_id, &key);
+                    return Err(err);
+                }
+            };
+            let status = response.status();
+            if !status.is_success() {
+                release(user_id, &key);
+                return Ok(response.map_into_boxed_body());
+            }
+
+            let (request, response) = response.into_parts();
+            let (response, body) = response.into_parts();
+            let body = match body::to_bytes(body).await {
+                Ok(body) => body,
+                Err(_) => {
+                    release(user_id, &key);
+                    return Err(ErrorInternalServerError("Could not read the response"));
+                }
+            };
+            if let Err(err) = IdempotencyController::complete(
+                user_id,
+                &key,
+                status.as_u16(),
+                &String::from_utf8_lossy(&body),
+            ) {
+                error!("Could not store idempotency key \"{}\": {}", key, err);
+            }
+            let response = response.set_body(body).map_into_boxed_body();
+            Ok(ServiceResponse::new(request, response))
+        })
+    }
+}
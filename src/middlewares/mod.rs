@@ -1,4 +1,42 @@
+use actix_web::{dev::ServiceRequest, http::StatusCode};
+
+use crate::{
+    controller::{
+        login::auth_controller::{AuthController, AuthError, Claims},
+        users::api_key_controller::ApiKeyController,
+    },
+    models::users::users_model::User,
+};
+
 mod check_login;
+mod idempotency;
 mod should_check_login;
 pub const CHECK_LOGIN: check_login::CheckLogin = check_login::CheckLogin;
 pub const SHOULD_CHECK_LOGIN: should_check_login::ShouldCheckLogin = should_check_login::ShouldCheckLogin;
+pub const IDEMPOTENCY: idempotency::Idempotency = idempotency::Idempotency;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Authenticates the `X-Api-Key` header, or else the `Authorization` token.
+/// `None` when the request carries neither.
+fn authenticate(request: &ServiceRequest) -> Option<Result<(Claims, User), AuthError>> {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|x| x.to_str().ok())
+            .map(|x| x.to_owned())
+    };
+    if let Some(key) = header(API_KEY_HEADER) {
+        return Some(ApiKeyController::authenticate(&key));
+    }
+    header("Authorization").map(AuthController::authenticate)
+}
+
+/// A credential that could not be checked is a server error, not a refusal
+fn refusal_status(err: &AuthError) -> StatusCode {
+    match err {
+        AuthError::Db(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::UNAUTHORIZED,
+    }
+}
@@ -3,17 +3,19 @@ use std::future::{ready, Ready};
 use actix_web::{
     body::EitherBody,
     dev::{self, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
     Error, HttpMessage, HttpResponse,
 };
 use futures_util::future::LocalBoxFuture;
 
 use crate::{
     controller::{
-        login::auth_controller::AuthController,
+        login::{auth_controller::AuthError, current_user::CurrentUser},
         tables::{
             permissions::table_permissions_controller::TablePermissionsController,
-            table_controller::TableController,
+            rename_controller::RenameController, table_controller::TableController,
         },
+        users::role_controller::RoleController,
     },
     models::cms::permission_model::TablePermissions,
     routes::utils::reponses::ReturnError,
@@ -21,6 +23,19 @@ use crate::{
 
 pub struct ShouldCheckLogin;
 
+const BATCH_SEGMENT: &str = "_batch";
+const MGET_SEGMENT: &str = "/_mget";
+
+/// Table name of a `/custom/{table}/...` path, falls back to the last segment
+fn table_segment(path: &str) -> Option<&str> {
+    let mut segments = path.split("/").filter(|x| !x.is_empty());
+    let last = segments.clone().last();
+    match segments.position(|x| x == "custom") {
+        Some(_) => segments.next().or(last),
+        None => last,
+    }
+}
+
 impl<S, B> Transform<S, ServiceRequest> for ShouldCheckLogin
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
@@ -61,14 +76,64 @@ where
         let full_path = request.path().to_string();
         // remove origin from full_path
         // let full_path = Uri::from_static(&full_path);
-        let path = full_path
-            .split("/")
-            .filter(|x| !x.is_empty())
-            .last()
-            .unwrap();
+        let path = table_segment(&full_path).unwrap();
+
+        // Batches span several tables, every operation is authorized by the handler
+        if path == BATCH_SEGMENT {
+            if let Some(authenticated) = super::authenticate(&request) {
+                match authenticated {
+                    Ok((claims, user)) => {
+                        request.extensions_mut().insert(claims);
+                        request.extensions_mut().insert(CurrentUser(user));
+                    }
+                    Err(err) => {
+                        let (request, _pl) = request.into_parts();
+                        let status = super::refusal_status(&err);
+                        let err: ReturnError = err.into();
+                        let error_ret = ReturnError {
+                            error_msg: err.error_msg,
+                            values: Some(full_path.into()),
+                        };
+                        let response = HttpResponse::build(status)
+                            .json(error_ret)
+                            // constructed responses map to "right" body
+                            .map_into_right_body();
+
+                        return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+                    }
+                }
+            }
+
+            let res = self.service.call(request);
+            return Box::pin(async move {
+                // forwarded responses map to "left" body
+                res.await.map(ServiceResponse::map_into_left_body)
+            });
+        }
 
         let table = TableController::find_by_name(path);
         if table.is_err() {
+            // Renamed tables keep answering on their old name for a while
+            if let Ok(Some(current)) = RenameController::resolve(path) {
+                let mut location =
+                    full_path.replacen(&format!("/{}/", path), &format!("/{}/", current), 1);
+                if !request.query_string().is_empty() {
+                    location.push('?');
+                    location.push_str(request.query_string());
+                }
+                let (request, _pl) = request.into_parts();
+                let error_ret = ReturnError {
+                    error_msg: format!(r#"Table "{}" was renamed to "{}""#, path, current),
+                    values: Some(current.into()),
+                };
+                let response = HttpResponse::PermanentRedirect()
+                    .insert_header((header::LOCATION, location))
+                    .json(error_ret)
+                    // constructed responses map to "right" body
+                    .map_into_right_body();
+
+                return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+            }
             let (request, _pl) = request.into_parts();
             let error_ret = ReturnError {
                 error_msg: format!(r#"Could not find "{}""#, path),
@@ -83,7 +148,26 @@ where
         }
         let table = table.unwrap();
 
-        let method = request.method().as_str().to_uppercase();
+        if !table.is_active {
+            let (request, _pl) = request.into_parts();
+            let error_ret = ReturnError {
+                error_msg: format!(r#"Table "{}" is inactive"#, path),
+                values: Some(path.into()),
+            };
+            let response = HttpResponse::Locked()
+                .json(error_ret)
+                // constructed responses map to "right" body
+                .map_into_right_body();
+
+            return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+        }
+
+        // `_mget` is a read sent as a POST, it needs the query permission
+        let method = if full_path.trim_end_matches('/').ends_with(MGET_SEGMENT) {
+            "GET".to_string()
+        } else {
+            request.method().as_str().to_uppercase()
+        };
 
         let table_permissions = TablePermissionsController::find_by_table_id(table.id);
         if table_permissions.is_err() {
@@ -100,7 +184,7 @@ where
             return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
         }
         let table_permissions = table_permissions.unwrap();
-        let permission = TablePermissions::resolve_method(method);
+        let permission = TablePermissions::resolve_method(&method);
         if permission.is_err() {
             let (request, _pl) = request.into_parts();
             let error_ret = ReturnError {
@@ -118,6 +202,47 @@ where
         let can_bypass = TablePermissions::check(table_permissions, permission);
 
         if can_bypass {
+            // Public tables still record who wrote a row when a valid token comes along
+            if let Some(authenticated) = super::authenticate(&request) {
+                match authenticated {
+                    Ok((claims, _)) if !claims.allows(path, &method) => {
+                        let (request, _pl) = request.into_parts();
+                        let error_ret = ReturnError {
+                            error_msg: "API key scope does not allow this request".to_string(),
+                            values: Some(full_path.into()),
+                        };
+                        let response = HttpResponse::Forbidden()
+                            .json(error_ret)
+                            // constructed responses map to "right" body
+                            .map_into_right_body();
+
+                        return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+                    }
+                    Ok((claims, user)) => {
+                        request.extensions_mut().insert(claims);
+                        request.extensions_mut().insert(CurrentUser(user));
+                    }
+                    // A bad or revoked credential is ignored here, a blocked or deleted user
+                    // or a failed check is not
+                    Err(AuthError::BadToken | AuthError::BadKey | AuthError::Revoked) => {}
+                    Err(err) => {
+                        let (request, _pl) = request.into_parts();
+                        let status = super::refusal_status(&err);
+                        let err: ReturnError = err.into();
+                        let error_ret = ReturnError {
+                            error_msg: err.error_msg,
+                            values: Some(full_path.into()),
+                        };
+                        let response = HttpResponse::build(status)
+                            .json(error_ret)
+                            // constructed responses map to "right" body
+                            .map_into_right_body();
+
+                        return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+                    }
+                }
+            }
+
             let res = self.service.call(request);
             return Box::pin(async move {
                 // forwarded responses map to "left" body
@@ -125,40 +250,47 @@ where
             });
         }
 
-        // Check if has token
-        let is_logged_in = request.headers().get("Authorization").is_some();
         let mut error_ret = ReturnError {
             error_msg: "Token missing".to_string(),
-            values: Some(full_path.into()),
+            values: Some(full_path.clone().into()),
         };
         // Check if token is missing
-        if !is_logged_in {
-            let (request, _pl) = request.into_parts();
-            // Get path variable
-            let path = request.path().to_string();
-            let path = path.split("/").filter(|x| !x.is_empty()).last().unwrap();
+        let authenticated = match super::authenticate(&request) {
+            Some(authenticated) => authenticated,
+            None => {
+                let (request, _pl) = request.into_parts();
+                // Get path variable
+                let path = request.path().to_string();
+                let path = path.split("/").filter(|x| !x.is_empty()).last().unwrap();
 
-            error_ret.error_msg = "Token missing".to_string();
-            error_ret.values = Some(path.into());
-            let response = HttpResponse::Unauthorized()
+                error_ret.error_msg = "Token missing".to_string();
+                error_ret.values = Some(path.into());
+                let response = HttpResponse::Unauthorized()
+                    .json(error_ret)
+                    // constructed responses map to "right" body
+                    .map_into_right_body();
+
+                return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+            }
+        };
+
+        if let Err(err) = authenticated {
+            let (request, _pl) = request.into_parts();
+            let status = super::refusal_status(&err);
+            let err: ReturnError = err.into();
+            error_ret.error_msg = err.error_msg;
+            let response = HttpResponse::build(status)
                 .json(error_ret)
                 // constructed responses map to "right" body
                 .map_into_right_body();
 
             return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
         }
-        let token = request
-            .headers()
-            .get("Authorization")
-            .unwrap()
-            .to_str()
-            .expect("Error getting token");
-
-        let (authorized, claims) = AuthController::verify_jwt(token.to_owned());
 
-        if !authorized {
+        let (claims, user) = authenticated.unwrap();
+        if !user.api_rights {
             let (request, _pl) = request.into_parts();
-            error_ret.error_msg = "Token invalid".to_string();
+            error_ret.error_msg = "Not authorized".to_string();
             let response = HttpResponse::Unauthorized()
                 .json(error_ret)
                 // constructed responses map to "right" body
@@ -167,11 +299,38 @@ where
             return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
         }
 
-        let claims = claims.unwrap();
-        if !claims.api_rights {
+        // Tables that are not public are reached through the roles of the user
+        let allowed = match user.admin {
+            true => Ok(true),
+            false => RoleController::allows(user.id, path, permission),
+        };
+        match allowed {
+            Ok(true) => {}
+            Ok(false) => {
+                let (request, _pl) = request.into_parts();
+                error_ret.error_msg = "Not authorized".to_string();
+                let response = HttpResponse::Forbidden()
+                    .json(error_ret)
+                    // constructed responses map to "right" body
+                    .map_into_right_body();
+
+                return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+            }
+            Err(err) => {
+                let (request, _pl) = request.into_parts();
+                let response = HttpResponse::InternalServerError()
+                    .json(err)
+                    // constructed responses map to "right" body
+                    .map_into_right_body();
+
+                return Box::pin(async { Ok(ServiceResponse::new(request, response)) });
+            }
+        }
+
+        if !claims.allows(path, &method) {
             let (request, _pl) = request.into_parts();
-            error_ret.error_msg = "Not authorized".to_string();
-            let response = HttpResponse::Unauthorized()
+            error_ret.error_msg = "API key scope does not allow this request".to_string();
+            let response = HttpResponse::Forbidden()
                 .json(error_ret)
                 // constructed responses map to "right" body
                 .map_into_right_body();
@@ -180,6 +339,7 @@ where
         }
 
         request.extensions_mut().insert(claims);
+        request.extensions_mut().insert(CurrentUser(user));
 
         let res = self.service.call(request);
 
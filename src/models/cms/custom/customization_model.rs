@@ -0,0 +1,30 @@
+use super::super::table_model::Table;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Identifiable,
+    Associations,
+    Queryable,
+    PartialEq,
+    Debug,
+    Selectable,
+    Serialize,
+    Deserialize,
+    Clone,
+)]
+#[serde(rename_all = "camelCase")]
+#[diesel(table_name = crate::schema::customizations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(belongs_to(Table))]
+pub struct Customization {
+    pub id: i32,
+    pub path: String,
+    pub table_id: i32,
+    pub run_on: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub created_by: i32,
+    pub updated_by: i32,
+}
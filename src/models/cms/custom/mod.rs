@@ -1 +1,2 @@
-pub mod customization_model;
\ No newline at end of file
+pub mod customization_model;
+pub mod row_history_model;
@@ -0,0 +1,33 @@
+use super::super::table_model::Table;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Snapshot of a row of a CMS table, taken after every write through the data API
+#[derive(
+    Identifiable,
+    Associations,
+    Queryable,
+    PartialEq,
+    Debug,
+    Selectable,
+    Serialize,
+    Deserialize,
+    Clone,
+)]
+#[serde(rename_all = "camelCase")]
+#[diesel(table_name = crate::schema::row_history)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(belongs_to(Table))]
+pub struct RowHistory {
+    pub id: i32,
+    pub table_id: i32,
+    pub row_id: String,
+    pub version: i32,
+    pub operation: String,
+    /// The row after the change, or as it was before being deleted
+    pub data: Value,
+    pub changed_by: Option<i32>,
+    pub changed_at: NaiveDateTime,
+}
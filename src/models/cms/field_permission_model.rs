@@ -0,0 +1,76 @@
+use super::fields_model::Field;
+use crate::models::users::role_model::Role;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::routes::utils::reponses::ReturnError;
+
+/// How the users of a role see a field, fields without a rule for the role are `full`
+#[derive(
+    Identifiable,
+    Associations,
+    Queryable,
+    PartialEq,
+    Debug,
+    Selectable,
+    Serialize,
+    Deserialize,
+    Clone,
+)]
+#[diesel(table_name = crate::schema::field_permissions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[diesel(belongs_to(Field))]
+#[diesel(belongs_to(Role))]
+pub struct FieldPermission {
+    pub id: i32,
+    pub field_id: i32,
+    pub role_id: i32,
+    /// `FieldAccess` other than `full`
+    pub access: String,
+    /// Trailing characters left readable by `masked`
+    pub mask_visible: i32,
+    pub created_at: NaiveDateTime,
+}
+
+/// Ordered from the most to the least restrictive
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum FieldAccess {
+    Hidden,
+    Masked,
+    ReadOnly,
+    Full,
+}
+
+impl FieldAccess {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FieldAccess::Hidden => "hidden",
+            FieldAccess::Masked => "masked",
+            FieldAccess::ReadOnly => "readOnly",
+            FieldAccess::Full => "full",
+        }
+    }
+
+    pub fn from_string<S: AsRef<str>>(access: S) -> Result<FieldAccess, ReturnError> {
+        let access = access.as_ref();
+        [
+            FieldAccess::Hidden,
+            FieldAccess::Masked,
+            FieldAccess::ReadOnly,
+            FieldAccess::Full,
+        ]
+        .into_iter()
+        .find(|x| x.as_str().eq_ignore_ascii_case(access))
+        .ok_or(ReturnError::without_value(format!(
+            "Invalid field access `{}`, expected `hidden`, `masked` or `readOnly`",
+            access
+        )))
+    }
+
+    pub fn can_write(&self) -> bool {
+        *self == FieldAccess::Full
+    }
+}
@@ -34,4 +34,5 @@ pub struct Field {
     pub is_unique: bool,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub version: i32,
 }
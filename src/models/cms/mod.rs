@@ -1,4 +1,7 @@
 pub mod custom;
+pub mod field_permission_model;
 pub mod fields_model;
+pub mod row_policy_model;
+pub mod table_alias_model;
 pub mod table_model;
 pub mod permission_model;
\ No newline at end of file
@@ -117,6 +117,17 @@ impl Serialize for PermissionType {
     }
 }
 impl PermissionType {
+    /// HTTP method of the permission, see `PERMISSION_TYPES_HTTP_EQUIVALENT`
+    pub fn http_method(&self) -> &'static str {
+        match self {
+            PermissionType::Query => "GET",
+            PermissionType::Create => "POST",
+            PermissionType::Replace => "PUT",
+            PermissionType::Update => "PATCH",
+            PermissionType::Delete => "DELETE",
+        }
+    }
+
     pub fn from_string(s: &str) -> Result<Self, ReturnError> {
         match s.to_lowercase().as_str() {
             "create" => Ok(PermissionType::Create),
@@ -0,0 +1,56 @@
+use super::table_model::Table;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const OWNER_POLICY: &str = "owner";
+pub const TEAM_POLICY: &str = "team";
+
+/// Limits the rows of a table a user can reach. Policies of a table add up:
+/// a row is reachable when one of the policies covering the operation matches it.
+#[derive(
+    Identifiable,
+    Associations,
+    Queryable,
+    PartialEq,
+    Debug,
+    Selectable,
+    Serialize,
+    Deserialize,
+    Clone,
+)]
+#[serde(rename_all = "camelCase")]
+#[diesel(table_name = crate::schema::row_policies)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(belongs_to(Table))]
+pub struct RowPolicy {
+    pub id: i32,
+    pub table_id: i32,
+    pub name: String,
+    /// `owner` or `team`
+    pub kind: String,
+    /// `PolicyOperation` list
+    pub operations: Value,
+    pub created_at: NaiveDateTime,
+    /// Owner field, or team field of `team` policies. References are ids so renames keep them
+    pub field_id: i32,
+    pub membership_table_id: Option<i32>,
+    pub membership_team_field_id: Option<i32>,
+    pub membership_user_field_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum PolicyOperation {
+    Read,
+    Update,
+    Delete,
+}
+
+impl RowPolicy {
+    pub fn covers(&self, operation: PolicyOperation) -> bool {
+        serde_json::from_value::<Vec<PolicyOperation>>(self.operations.clone())
+            .is_ok_and(|x| x.contains(&operation))
+    }
+}
@@ -0,0 +1,28 @@
+use super::table_model::Table;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Former name of a renamed table, served as a redirect until it expires
+#[derive(
+    Identifiable,
+    Associations,
+    Queryable,
+    PartialEq,
+    Debug,
+    Selectable,
+    Serialize,
+    Deserialize,
+    Clone,
+)]
+#[serde(rename_all = "camelCase")]
+#[diesel(table_name = crate::schema::table_aliases)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(belongs_to(Table))]
+pub struct TableAlias {
+    pub id: i32,
+    pub table_id: i32,
+    pub name: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
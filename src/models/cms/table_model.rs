@@ -17,4 +17,10 @@ pub struct Table {
     pub is_deleted: bool,
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
+    pub version: i32,
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Whether the physical table carries the `created_*`/`updated_*` audit columns
+    pub audit: bool,
+    /// Whether writes through the data API keep a snapshot of every row version
+    pub history: bool,
 }
\ No newline at end of file
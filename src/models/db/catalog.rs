@@ -0,0 +1,261 @@
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{Bool, Integer, Nullable, Text};
+use serde::{Deserialize, Serialize};
+
+use crate::routes::utils::reponses::ReturnError;
+use crate::utils::string_utils::to_snake_case;
+
+pub const DEFAULT_SCHEMA: &str = "public";
+
+/// Tables owned by the server itself, they must never be adopted or served as CMS tables
+pub const SYSTEM_TABLES: &[&str] = &[
+    "__diesel_schema_migrations",
+    "api_keys",
+    "customizations",
+    "field_permissions",
+    "fields",
+    "idempotency_keys",
+    "login_attempts",
+    "posts",
+    "refresh_tokens",
+    "revoked_tokens",
+    "role_grants",
+    "roles",
+    "row_history",
+    "row_policies",
+    "table_aliases",
+    "tables",
+    "tables_permissions",
+    "user_tokens",
+    "users",
+    "users_permissions",
+    "users_roles",
+];
+
+/// A column of a physical table, as seen by `information_schema` and `pg_catalog`
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogColumn {
+    #[diesel(sql_type = Text)]
+    pub table_schema: String,
+    #[diesel(sql_type = Text)]
+    pub table_name: String,
+    #[diesel(sql_type = Text)]
+    pub column_name: String,
+    #[diesel(sql_type = Text)]
+    pub udt_name: String,
+    #[diesel(sql_type = Integer)]
+    pub ordinal_position: i32,
+    #[diesel(sql_type = Bool)]
+    pub is_nullable: bool,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub column_default: Option<String>,
+    #[diesel(sql_type = Bool)]
+    pub is_identity: bool,
+    #[diesel(sql_type = Bool)]
+    pub is_primary_key: bool,
+    #[diesel(sql_type = Bool)]
+    pub is_unique: bool,
+}
+
+/// Columns created through `build_fields` keep the field name, the ones added later are snake cased
+pub fn column_matches(column: &str, field_name: &str) -> bool {
+    column == to_snake_case(field_name) || column == field_name.to_lowercase()
+}
+
+impl CatalogColumn {
+    pub fn matches_field(&self, field_name: &str) -> bool {
+        column_matches(&self.column_name, field_name)
+    }
+
+    /// Serial and identity columns get their value from a sequence
+    pub fn is_auto_increment(&self) -> bool {
+        self.is_identity
+            || self
+                .column_default
+                .as_ref()
+                .is_some_and(|x| x.starts_with("nextval("))
+    }
+}
+
+/// A secondary index, the ones backing primary key and unique constraints are left out
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogIndex {
+    #[diesel(sql_type = Text)]
+    pub index_name: String,
+    #[diesel(sql_type = Bool)]
+    pub is_unique: bool,
+    #[diesel(sql_type = Text)]
+    pub definition: String,
+}
+
+impl CatalogIndex {
+    /// Everything after `USING` in the definition, e.g. `btree (name)`
+    pub fn method(&self) -> Option<&str> {
+        self.definition
+            .split_once(" USING ")
+            .map(|(_, method)| method)
+    }
+}
+
+/// A sequence, constraint or index belonging to a table, `kind` tells which
+#[derive(QueryableByName, Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogDependent {
+    #[diesel(sql_type = Text)]
+    pub kind: String,
+    #[diesel(sql_type = Text)]
+    pub name: String,
+}
+
+#[derive(QueryableByName, Debug, Clone)]
+struct CatalogTable {
+    #[diesel(sql_type = Text)]
+    table_name: String,
+}
+
+pub struct Catalog;
+
+impl Catalog {
+    /// Splits a registered table name into its schema and table parts
+    pub fn split_name(name: &str) -> (&str, &str) {
+        name.split_once('.').unwrap_or((DEFAULT_SCHEMA, name))
+    }
+
+    /// Base tables of a schema, in alphabetical order
+    pub fn tables<S: AsRef<str>>(
+        conn: &mut PgConnection,
+        schema: S,
+    ) -> Result<Vec<String>, ReturnError> {
+        let query = sql_query(
+            "SELECT table_name::text AS table_name
+            FROM information_schema.tables
+            WHERE table_schema = $1 AND table_type = 'BASE TABLE'
+            ORDER BY table_name",
+        )
+        .bind::<Text, _>(schema.as_ref());
+
+        query
+            .load::<CatalogTable>(conn)
+            .map(|tables| tables.into_iter().map(|x| x.table_name).collect())
+            .map_err(|err| ReturnError::new(err.to_string(), schema.as_ref()))
+    }
+
+    /// Columns of a schema, optionally restricted to a single table, ordered by table and position
+    pub fn columns<S: AsRef<str>>(
+        conn: &mut PgConnection,
+        schema: S,
+        table: Option<&str>,
+    ) -> Result<Vec<CatalogColumn>, ReturnError> {
+        let query = sql_query(
+            "SELECT
+                c.table_schema::text AS table_schema,
+                c.table_name::text AS table_name,
+                c.column_name::text AS column_name,
+                c.udt_name::text AS udt_name,
+                c.ordinal_position::int AS ordinal_position,
+                (c.is_nullable = 'YES') AS is_nullable,
+                c.column_default::text AS column_default,
+                (c.is_identity = 'YES') AS is_identity,
+                EXISTS (
+                    SELECT 1 FROM pg_catalog.pg_index i
+                    WHERE i.indrelid = cl.oid AND i.indisprimary
+                        AND i.indnatts = 1 AND i.indkey[0] = a.attnum
+                ) AS is_primary_key,
+                EXISTS (
+                    SELECT 1 FROM pg_catalog.pg_index i
+                    WHERE i.indrelid = cl.oid AND i.indisunique
+                        AND i.indnatts = 1 AND i.indkey[0] = a.attnum
+                ) AS is_unique
+            FROM information_schema.columns c
+            JOIN information_schema.tables t
+                ON t.table_schema = c.table_schema AND t.table_name = c.table_name
+            JOIN pg_catalog.pg_namespace n ON n.nspname = c.table_schema
+            JOIN pg_catalog.pg_class cl ON cl.relnamespace = n.oid AND cl.relname = c.table_name
+            JOIN pg_catalog.pg_attribute a ON a.attrelid = cl.oid AND a.attname = c.column_name
+            WHERE c.table_schema = $1
+                AND t.table_type = 'BASE TABLE'
+                AND ($2::text IS NULL OR c.table_name = $2)
+            ORDER BY c.table_name, c.ordinal_position",
+        )
+        .bind::<Text, _>(schema.as_ref())
+        .bind::<Nullable<Text>, _>(table);
+
+        query
+            .load::<CatalogColumn>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), schema.as_ref()))
+    }
+
+    /// Indexes of a table that are not created through a column constraint
+    pub fn indexes<S: AsRef<str>>(
+        conn: &mut PgConnection,
+        schema: S,
+        table: S,
+    ) -> Result<Vec<CatalogIndex>, ReturnError> {
+        let query = sql_query(
+            "SELECT
+                ic.relname::text AS index_name,
+                i.indisunique AS is_unique,
+                pg_catalog.pg_get_indexdef(i.indexrelid)::text AS definition
+            FROM pg_catalog.pg_index i
+            JOIN pg_catalog.pg_class ic ON ic.oid = i.indexrelid
+            JOIN pg_catalog.pg_class t ON t.oid = i.indrelid
+            JOIN pg_catalog.pg_namespace n ON n.oid = t.relnamespace
+            WHERE n.nspname = $1
+                AND t.relname = $2
+                AND NOT EXISTS (
+                    SELECT 1 FROM pg_catalog.pg_constraint c WHERE c.conindid = i.indexrelid
+                )
+            ORDER BY ic.relname",
+        )
+        .bind::<Text, _>(schema.as_ref())
+        .bind::<Text, _>(table.as_ref());
+
+        query
+            .load::<CatalogIndex>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), table.as_ref()))
+    }
+
+    /// Sequences owned by the columns of a table, its constraints and its other indexes
+    pub fn dependents<S: AsRef<str>>(
+        conn: &mut PgConnection,
+        schema: S,
+        table: S,
+    ) -> Result<Vec<CatalogDependent>, ReturnError> {
+        let query = sql_query(
+            "WITH target AS (
+                SELECT t.oid FROM pg_catalog.pg_class t
+                JOIN pg_catalog.pg_namespace n ON n.oid = t.relnamespace
+                WHERE n.nspname = $1 AND t.relname = $2
+            )
+            SELECT 'sequence' AS kind, s.relname::text AS name
+            FROM pg_catalog.pg_class s
+            JOIN pg_catalog.pg_depend d ON d.objid = s.oid
+                AND d.classid = 'pg_catalog.pg_class'::regclass
+                AND d.refclassid = 'pg_catalog.pg_class'::regclass
+            WHERE s.relkind = 'S' AND d.refobjid IN (SELECT oid FROM target)
+            UNION
+            SELECT 'constraint' AS kind, c.conname::text AS name
+            FROM pg_catalog.pg_constraint c
+            WHERE c.conrelid IN (SELECT oid FROM target)
+            UNION
+            SELECT 'index' AS kind, ic.relname::text AS name
+            FROM pg_catalog.pg_index i
+            JOIN pg_catalog.pg_class ic ON ic.oid = i.indexrelid
+            WHERE i.indrelid IN (SELECT oid FROM target)
+                AND NOT EXISTS (
+                    SELECT 1 FROM pg_catalog.pg_constraint c WHERE c.conindid = i.indexrelid
+                )
+            ORDER BY kind, name",
+        )
+        .bind::<Text, _>(schema.as_ref())
+        .bind::<Text, _>(table.as_ref());
+
+        query
+            .load::<CatalogDependent>(conn)
+            .map_err(|err| ReturnError::new(err.to_string(), table.as_ref()))
+    }
+}
@@ -0,0 +1,18 @@
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::Text;
+
+use crate::routes::utils::reponses::ReturnError;
+
+/// Takes a transaction scoped advisory lock on a CMS table, so metadata inserts and the
+/// matching `ALTER TABLE` statements of concurrent requests never interleave.
+/// The lock is released when the surrounding transaction ends.
+pub fn lock_table<S: AsRef<str>>(conn: &mut PgConnection, name: S) -> Result<(), ReturnError> {
+    let key = format!("cms_table:{}", name.as_ref());
+    sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+        .bind::<Text, _>(&key)
+        .execute(conn)
+        .map(|_| ())
+        .map_err(|err| ReturnError::new(err.to_string(), &key))
+}
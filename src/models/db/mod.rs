@@ -1,2 +1,4 @@
+pub mod catalog;
 pub mod connection;
 pub mod driver_connection;
+pub mod locks;
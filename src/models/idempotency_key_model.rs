@@ -0,0 +1,20 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Response stored under an `Idempotency-Key`, `status_code` stays empty while the request runs
+#[derive(Queryable, PartialEq, Debug, Selectable, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+#[diesel(table_name = crate::schema::idempotency_keys)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct IdempotencyKey {
+    pub id: i32,
+    pub key: String,
+    pub fingerprint: String,
+    pub status_code: Option<i32>,
+    pub response_body: Option<String>,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    /// Namespace of the key, `None` for anonymous requests
+    pub user_id: Option<i32>,
+}
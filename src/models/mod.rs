@@ -1,5 +1,6 @@
 // Default models
 pub mod db;
+pub mod idempotency_key_model;
 pub mod posts_model;
 pub mod users;
 
@@ -0,0 +1,64 @@
+use super::users_model::User;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Hash of an API key, the key itself is only shown when it is created.
+/// `prefix` is the start of the key, kept to tell keys apart.
+#[derive(
+    Identifiable,
+    Associations,
+    Queryable,
+    PartialEq,
+    Debug,
+    Selectable,
+    Serialize,
+    Deserialize,
+    Clone,
+)]
+#[diesel(table_name = crate::schema::api_keys)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[diesel(belongs_to(User))]
+pub struct ApiKey {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub prefix: String,
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    /// `ApiKeyScope` list
+    pub scopes: Value,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl ApiKey {
+    pub fn scopes(&self) -> Vec<ApiKeyScope> {
+        serde_json::from_value(self.scopes.clone()).unwrap_or_default()
+    }
+}
+
+pub const ANY: &str = "*";
+
+/// Table and HTTP methods a key may use, `*` stands for any of them.
+/// Routes outside `/custom` are matched by their first segment, like `users` or `tables`.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyScope {
+    pub table: String,
+    pub methods: Vec<String>,
+}
+
+impl ApiKeyScope {
+    pub fn allows(&self, table: &str, method: &str) -> bool {
+        (self.table == ANY || self.table == table)
+            && self
+                .methods
+                .iter()
+                .any(|x| x == ANY || x.eq_ignore_ascii_case(method))
+    }
+}
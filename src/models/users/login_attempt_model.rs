@@ -0,0 +1,17 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Failed logins of one `subject`, `email:<email>` or `ip:<address>`. Rows of unknown
+/// emails are kept too, so a locked account looks the same whether it exists or not.
+#[derive(Identifiable, Queryable, PartialEq, Debug, Selectable, Serialize, Deserialize, Clone)]
+#[diesel(table_name = crate::schema::login_attempts)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct LoginAttempt {
+    pub id: i32,
+    pub subject: String,
+    pub failures: i32,
+    pub locked_until: Option<NaiveDateTime>,
+    pub last_failure_at: NaiveDateTime,
+}
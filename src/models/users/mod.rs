@@ -1,2 +1,7 @@
 pub mod users_model;
-pub mod permissions_model;
\ No newline at end of file
+pub mod permissions_model;
+pub mod refresh_token_model;
+pub mod api_key_model;
+pub mod role_model;
+pub mod user_token_model;
+pub mod login_attempt_model;
@@ -0,0 +1,34 @@
+use super::users_model::User;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Hash of a refresh token. Tokens rotated from one login share a `family`,
+/// `used_at` is set once the token was exchanged for a new one.
+#[derive(
+    Identifiable,
+    Associations,
+    Queryable,
+    PartialEq,
+    Debug,
+    Selectable,
+    Serialize,
+    Deserialize,
+    Clone,
+)]
+#[diesel(table_name = crate::schema::refresh_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[diesel(belongs_to(User))]
+pub struct RefreshToken {
+    pub id: i32,
+    pub user_id: i32,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub family: String,
+    pub access_jti: String,
+    pub used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
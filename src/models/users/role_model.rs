@@ -0,0 +1,68 @@
+use super::users_model::User;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// `*` in a grant stands for any table or any method
+pub const ANY: &str = "*";
+/// Grant of table and field management, given on every table
+pub const SCHEMA_GRANT: &str = "Schema";
+/// Routes that are not CMS tables, a `*` grant does not cover them: only a grant naming them does
+pub const RESERVED_SCOPES: [&str; 3] = ["users", "roles", "posts"];
+
+#[derive(Identifiable, Queryable, PartialEq, Debug, Selectable, Serialize, Deserialize, Clone)]
+#[diesel(table_name = crate::schema::roles)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+pub struct Role {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// A permission a role holds on a table, see `ANY` and `SCHEMA_GRANT`
+#[derive(
+    Identifiable,
+    Associations,
+    Queryable,
+    PartialEq,
+    Debug,
+    Selectable,
+    Serialize,
+    Deserialize,
+    Clone,
+)]
+#[diesel(table_name = crate::schema::role_grants)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[diesel(belongs_to(Role))]
+pub struct RoleGrant {
+    pub id: i32,
+    pub role_id: i32,
+    pub table_name: String,
+    pub permission: String,
+}
+
+#[derive(
+    Identifiable,
+    Associations,
+    Queryable,
+    PartialEq,
+    Debug,
+    Selectable,
+    Serialize,
+    Deserialize,
+    Insertable,
+    Clone,
+)]
+#[diesel(table_name = crate::schema::users_roles)]
+#[diesel(primary_key(user_id, role_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[serde(rename_all = "camelCase")]
+#[diesel(belongs_to(User))]
+#[diesel(belongs_to(Role))]
+pub struct UserRole {
+    pub user_id: i32,
+    pub role_id: i32,
+}
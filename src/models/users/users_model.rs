@@ -2,7 +2,7 @@ use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Identifiable, Queryable, PartialEq, Debug, Selectable, Serialize, Deserialize)]
+#[derive(Identifiable, Queryable, PartialEq, Debug, Selectable, Serialize, Deserialize, Clone)]
 #[diesel(table_name = crate::schema::users)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 #[serde(rename_all = "camelCase")]
@@ -17,4 +17,7 @@ pub struct User {
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime>,
     pub picture: Option<String>,
+    pub email_verified_at: Option<NaiveDateTime>,
+    /// When failed logins blocked the user, `None` for users blocked by an admin
+    pub auto_blocked_at: Option<NaiveDateTime>,
 }
@@ -5,11 +5,14 @@ use crate::{
     middlewares::CHECK_LOGIN,
     services::{
         cms::{
-            core::{field::FieldRoute, table::TableRoute},
+            core::{field::FieldRoute, schema::SchemaRoute, table::TableRoute},
             custom::custom::CustomRoute,
         },
         posts::PostsRoute,
-        users::{auth::auth::AuthService, core::users::UsersRoute},
+        users::{
+            auth::auth::AuthService,
+            core::{api_keys::ApiKeysRoute, roles::RolesRoute, users::UsersRoute},
+        },
     },
 };
 
@@ -46,6 +49,10 @@ impl Scopes {
 
     pub fn users_scope() -> actix_web::Scope {
         actix_web::web::scope("users")
+            // Registered first so `api-keys` is not taken for a user id
+            .route("/api-keys/", web::get().to(ApiKeysRoute::find_all))
+            .route("/api-keys/", web::post().to(ApiKeysRoute::create))
+            .route("/api-keys/{id}/", web::delete().to(ApiKeysRoute::revoke))
             .route("/", web::post().to(UsersRoute::create))
             .route("/{id}/", web::get().to(UsersRoute::find))
             .route("/", web::get().to(UsersRoute::find_all))
@@ -53,9 +60,36 @@ impl Scopes {
             .route("/{id}/", web::delete().to(UsersRoute::delete))
     }
 
+    pub fn roles_scope() -> actix_web::Scope {
+        actix_web::web::scope("roles")
+            .route("/", web::post().to(RolesRoute::create))
+            .route("/", web::get().to(RolesRoute::find_all))
+            .route("/{id}/", web::get().to(RolesRoute::find))
+            .route("/{id}/", web::patch().to(RolesRoute::update))
+            .route("/{id}/", web::delete().to(RolesRoute::delete))
+            .route("/{id}/users/{user_id}/", web::put().to(RolesRoute::assign))
+            .route(
+                "/{id}/users/{user_id}/",
+                web::delete().to(RolesRoute::unassign),
+            )
+    }
+
     pub fn tables_scope() -> actix_web::Scope {
         actix_web::web::scope("tables")
             .route("/", web::post().to(TableRoute::create))
+            .route("/import/", web::post().to(TableRoute::import))
+            .route("/drift/", web::get().to(TableRoute::drift))
+            .route("/drift/repair/", web::post().to(TableRoute::repair_drift))
+            .route("/trash/", web::get().to(TableRoute::find_trash))
+            .route("/trash/{id}/restore/", web::post().to(TableRoute::restore))
+            .route("/trash/{id}/", web::delete().to(TableRoute::purge))
+            .route("/{id}/clone/", web::post().to(TableRoute::clone))
+            .route("/{id}/policies/", web::get().to(TableRoute::find_policies))
+            .route("/{id}/policies/", web::post().to(TableRoute::create_policy))
+            .route(
+                "/{id}/policies/{policy_id}/",
+                web::delete().to(TableRoute::delete_policy),
+            )
             .route("/{id}/", web::get().to(TableRoute::find_table_by_name))
             .route("/", web::get().to(TableRoute::find_all))
             .route("/{id}/", web::patch().to(TableRoute::update))
@@ -65,6 +99,18 @@ impl Scopes {
     pub fn fields_scope() -> actix_web::Scope {
         actix_web::web::scope("/tables/{table_name}/fields")
             .route("/", web::post().to(FieldRoute::create))
+            .route(
+                "/{field_name}/permissions/",
+                web::get().to(FieldRoute::find_permissions),
+            )
+            .route(
+                "/{field_name}/permissions/",
+                web::put().to(FieldRoute::set_permission),
+            )
+            .route(
+                "/{field_name}/permissions/{role_id}/",
+                web::delete().to(FieldRoute::delete_permission),
+            )
             .route("/{id}/", web::get().to(FieldRoute::find))
             .route("/", web::get().to(FieldRoute::find_all))
             .route("/{id}/", web::patch().to(FieldRoute::update))
@@ -73,15 +119,62 @@ impl Scopes {
                 web::delete().to(FieldRoute::delete_by_name),
             )
     }
+    pub fn schema_scope() -> actix_web::Scope {
+        actix_web::web::scope("schema")
+            .route("/export/", web::get().to(SchemaRoute::export))
+            .route("/import/", web::post().to(SchemaRoute::import))
+    }
+
     pub fn custom_scope() -> actix_web::Scope {
         actix_web::web::scope("/custom")
+            // Registered first so `_batch` is not taken for a table name
+            .route("/_batch/", web::post().to(CustomRoute::batch))
             .route("/{table_name}/", web::get().to(CustomRoute::find_all))
             .route("/{table_name}/{id}/", web::get().to(CustomRoute::find_one))
+            .route(
+                "/{table_name}/_mget/",
+                web::post().to(CustomRoute::find_many),
+            )
             // .route("/", web::get().to(CustomRoute::find_test))
             .route("/{table_name}/", web::post().to(CustomRoute::create))
+            .route("/{table_name}/{id}/", web::patch().to(CustomRoute::update))
+            .route("/{table_name}/{id}/", web::delete().to(CustomRoute::delete))
+            .route(
+                "/{table_name}/{id}/history/",
+                web::get().to(CustomRoute::history),
+            )
+            .route(
+                "/{table_name}/{id}/history/{version}/restore/",
+                web::post().to(CustomRoute::restore),
+            )
     }
 
     pub fn login_scope() -> actix_web::Scope {
-        actix_web::web::scope("login").route("/", web::post().to(AuthService::login))
+        actix_web::web::scope("login")
+            .route("/", web::post().to(AuthService::login))
+            .route("/refresh/", web::post().to(AuthService::refresh))
+    }
+
+    /// Paths get a trailing slash from `NormalizePath`, so this serves `/.well-known/jwks.json`
+    pub fn well_known_scope() -> actix_web::Scope {
+        actix_web::web::scope(".well-known").route("/jwks.json/", web::get().to(AuthService::jwks))
+    }
+
+    /// Account recovery, the tokens come from mails so these routes take no login
+    pub fn auth_scope() -> actix_web::Scope {
+        actix_web::web::scope("auth")
+            .route(
+                "/forgot-password/",
+                web::post().to(AuthService::forgot_password),
+            )
+            .route(
+                "/reset-password/",
+                web::post().to(AuthService::reset_password),
+            )
+            .route("/verify-email/", web::post().to(AuthService::verify_email))
+    }
+
+    pub fn logout_scope() -> actix_web::Scope {
+        actix_web::web::scope("logout").route("/", web::post().to(AuthService::logout))
     }
 }
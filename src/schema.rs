@@ -1,5 +1,23 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_keys (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 16]
+        prefix -> Varchar,
+        #[max_length = 64]
+        key_hash -> Varchar,
+        scopes -> Jsonb,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     customizations (id) {
         id -> Int4,
@@ -14,6 +32,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    field_permissions (id) {
+        id -> Int4,
+        field_id -> Int4,
+        role_id -> Int4,
+        #[max_length = 10]
+        access -> Varchar,
+        mask_visible -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     fields (id) {
         id -> Int4,
@@ -32,6 +62,33 @@ diesel::table! {
         is_unique -> Bool,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
+        version -> Int4,
+    }
+}
+
+diesel::table! {
+    idempotency_keys (id) {
+        id -> Int4,
+        #[max_length = 255]
+        key -> Varchar,
+        #[max_length = 64]
+        fingerprint -> Varchar,
+        status_code -> Nullable<Int4>,
+        response_body -> Nullable<Text>,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+        user_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    login_attempts (id) {
+        id -> Int4,
+        #[max_length = 300]
+        subject -> Varchar,
+        failures -> Int4,
+        locked_until -> Nullable<Timestamp>,
+        last_failure_at -> Timestamp,
     }
 }
 
@@ -46,6 +103,95 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    refresh_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        #[max_length = 32]
+        family -> Varchar,
+        #[max_length = 64]
+        access_jti -> Varchar,
+        used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    revoked_tokens (jti) {
+        #[max_length = 64]
+        jti -> Varchar,
+        expires_at -> Timestamp,
+        revoked_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    role_grants (id) {
+        id -> Int4,
+        role_id -> Int4,
+        #[max_length = 255]
+        table_name -> Varchar,
+        #[max_length = 9]
+        permission -> Varchar,
+    }
+}
+
+diesel::table! {
+    roles (id) {
+        id -> Int4,
+        #[max_length = 50]
+        name -> Varchar,
+        description -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    row_policies (id) {
+        id -> Int4,
+        table_id -> Int4,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 10]
+        kind -> Varchar,
+        operations -> Jsonb,
+        created_at -> Timestamp,
+        field_id -> Int4,
+        membership_table_id -> Nullable<Int4>,
+        membership_team_field_id -> Nullable<Int4>,
+        membership_user_field_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    row_history (id) {
+        id -> Int4,
+        table_id -> Int4,
+        row_id -> Text,
+        version -> Int4,
+        #[max_length = 7]
+        operation -> Varchar,
+        data -> Jsonb,
+        changed_by -> Nullable<Int4>,
+        changed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    table_aliases (id) {
+        id -> Int4,
+        table_id -> Int4,
+        #[max_length = 255]
+        name -> Varchar,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     tables (id) {
         id -> Int4,
@@ -59,6 +205,10 @@ diesel::table! {
         is_deleted -> Bool,
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
+        version -> Int4,
+        deleted_at -> Nullable<Timestamp>,
+        audit -> Bool,
+        history -> Bool,
     }
 }
 
@@ -72,6 +222,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    user_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 20]
+        purpose -> Varchar,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     users (id) {
         id -> Int4,
@@ -87,6 +251,8 @@ diesel::table! {
         created_at -> Nullable<Timestamp>,
         updated_at -> Nullable<Timestamp>,
         picture -> Nullable<Text>,
+        email_verified_at -> Nullable<Timestamp>,
+        auto_blocked_at -> Nullable<Timestamp>,
     }
 }
 
@@ -100,17 +266,49 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    users_roles (user_id, role_id) {
+        user_id -> Int4,
+        role_id -> Int4,
+    }
+}
+
+diesel::joinable!(api_keys -> users (user_id));
 diesel::joinable!(customizations -> tables (table_id));
+diesel::joinable!(field_permissions -> fields (field_id));
+diesel::joinable!(field_permissions -> roles (role_id));
 diesel::joinable!(fields -> tables (table_id));
+diesel::joinable!(idempotency_keys -> users (user_id));
+diesel::joinable!(refresh_tokens -> users (user_id));
+diesel::joinable!(role_grants -> roles (role_id));
+diesel::joinable!(row_history -> tables (table_id));
+diesel::joinable!(row_policies -> tables (table_id));
+diesel::joinable!(table_aliases -> tables (table_id));
 diesel::joinable!(tables_permissions -> tables (table_id));
+diesel::joinable!(user_tokens -> users (user_id));
 diesel::joinable!(users_permissions -> users (user_id));
+diesel::joinable!(users_roles -> roles (role_id));
+diesel::joinable!(users_roles -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
     customizations,
+    field_permissions,
     fields,
+    idempotency_keys,
+    login_attempts,
     posts,
+    refresh_tokens,
+    revoked_tokens,
+    role_grants,
+    roles,
+    row_history,
+    row_policies,
+    table_aliases,
     tables,
     tables_permissions,
+    user_tokens,
     users,
     users_permissions,
+    users_roles,
 );
@@ -1,17 +1,42 @@
 use crate::controller::fields::field_controller::FieldController;
+use crate::controller::fields::field_permission_controller::FieldPermissionController;
 use crate::controller::fields::structs::CreateField;
+use crate::controller::fields::structs::SetFieldPermission;
 use crate::controller::fields::structs::UpdateField;
+use crate::controller::login::auth_controller::Claims;
 use crate::controller::QueryParams;
+use crate::controller::Versioned;
 use crate::routes::utils::reponses::ReturnError;
 use crate::utils::get_body::get_body;
 use actix_web::web;
 use actix_web::web::Payload;
+use actix_web::HttpMessage;
+use actix_web::HttpRequest;
 use actix_web::HttpResponse;
 use actix_web::Responder;
 use actix_web::Result;
 
 pub struct FieldRoute;
 
+fn is_admin(req: &HttpRequest) -> bool {
+    req.extensions()
+        .get::<Claims>()
+        .is_some_and(|claims| claims.admin_rights)
+}
+
+fn admin_required() -> HttpResponse {
+    HttpResponse::Forbidden().json(ReturnError::without_value(
+        "Admin rights required".to_string(),
+    ))
+}
+
+fn permission_error(err: ReturnError) -> HttpResponse {
+    if err.to_string().to_lowercase().contains("not found") {
+        return HttpResponse::NotFound().json(err);
+    }
+    HttpResponse::BadRequest().json(err)
+}
+
 impl FieldRoute {
     // Fields routes
     pub async fn create(path: web::Path<(String,)>, payload: Payload) -> Result<impl Responder> {
@@ -92,12 +117,51 @@ impl FieldRoute {
 
         Ok(result)
     }
+    pub async fn find_permissions(path: web::Path<(String, String)>) -> Result<impl Responder> {
+        let (table_name, field_name) = path.into_inner();
+        match FieldPermissionController::find_all(&table_name, &field_name) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(permission_error(err)),
+        }
+    }
+    /// Field rules bind everyone but admins, so only admins manage them
+    pub async fn set_permission(
+        req: HttpRequest,
+        path: web::Path<(String, String)>,
+        payload: Payload,
+    ) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        let (table_name, field_name) = path.into_inner();
+        let permission = match get_body::<SetFieldPermission>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+        match FieldPermissionController::set(&table_name, &field_name, permission) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(permission_error(err)),
+        }
+    }
+    pub async fn delete_permission(
+        req: HttpRequest,
+        path: web::Path<(String, String, i32)>,
+    ) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        let (table_name, field_name, role_id) = path.into_inner();
+        match FieldPermissionController::delete(&table_name, &field_name, role_id) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(permission_error(err)),
+        }
+    }
     pub async fn update(
         field_id: web::Path<(String, i32)>,
         payload: web::Payload,
     ) -> Result<impl Responder> {
         let (_, field_id) = field_id.into_inner();
-        let field = match get_body::<UpdateField>(payload).await {
+        let field = match get_body::<Versioned<UpdateField>>(payload).await {
             Ok(res) => res,
             Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
         };
@@ -107,6 +171,9 @@ impl FieldRoute {
                 return Ok(HttpResponse::Ok().json(res));
             }
             Err(err) => {
+                if err.to_string().to_lowercase().contains("version conflict") {
+                    return Ok(HttpResponse::Conflict().json(err));
+                }
                 let not_found = err.to_string().to_lowercase().contains("not found");
                 let val = err.values.clone().unwrap();
                 if not_found {
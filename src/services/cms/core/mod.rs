@@ -1,2 +1,3 @@
 pub mod field;
+pub mod schema;
 pub mod table;
\ No newline at end of file
@@ -0,0 +1,43 @@
+use actix_web::web;
+use actix_web::HttpResponse;
+use actix_web::Responder;
+use actix_web::Result;
+
+use crate::controller::schema::schema_controller::SchemaController;
+use crate::controller::schema::structs::ExportQuery;
+use crate::controller::schema::structs::SchemaImportRequest;
+use crate::utils::get_body::get_body;
+
+pub struct SchemaRoute;
+
+impl SchemaRoute {
+    pub async fn export(query: web::Query<ExportQuery>) -> Result<impl Responder> {
+        match SchemaController::export(query.names()) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => {
+                let not_found = err.to_string().to_lowercase().contains("not found");
+                if not_found {
+                    return Ok(HttpResponse::NotFound().json(err));
+                }
+                Ok(HttpResponse::BadRequest().json(err))
+            }
+        }
+    }
+    pub async fn import(payload: web::Payload) -> Result<impl Responder> {
+        let request = match get_body::<SchemaImportRequest>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        match SchemaController::import(request) {
+            Ok(res) => Ok(HttpResponse::Created().json(res)),
+            Err(err) => {
+                let conflict = err.to_string().to_lowercase().contains("already exists");
+                if conflict {
+                    return Ok(HttpResponse::Conflict().json(err));
+                }
+                Ok(HttpResponse::BadRequest().json(err))
+            }
+        }
+    }
+}
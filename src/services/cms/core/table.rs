@@ -1,10 +1,22 @@
 use actix_web::web;
+use actix_web::HttpMessage;
+use actix_web::HttpRequest;
 use actix_web::HttpResponse;
 use actix_web::Responder;
 use actix_web::Result;
 
+use crate::controller::login::auth_controller::Claims;
+use crate::controller::tables::clone_controller::CloneController;
+use crate::controller::tables::drift_controller::DriftController;
+use crate::controller::tables::import_controller::ImportController;
+use crate::controller::tables::row_policy_controller::RowPolicyController;
+use crate::controller::tables::structs::CloneTableRequest;
+use crate::controller::tables::structs::CreateRowPolicy;
 use crate::controller::tables::structs::CreateTableRequest;
+use crate::controller::tables::structs::ImportTableRequest;
+use crate::controller::tables::structs::RepairDriftRequest;
 use crate::controller::tables::table_controller::TableController;
+use crate::controller::tables::trash_controller::TrashController;
 use crate::controller::Controller;
 use crate::controller::GenericValue;
 use crate::routes::utils::reponses::ReturnError;
@@ -12,9 +24,22 @@ use crate::utils::get_body::get_body;
 
 use crate::controller::tables::structs::Update;
 use crate::controller::QueryParams;
+use crate::controller::Versioned;
 
 pub struct TableRoute;
 
+fn is_admin(req: &HttpRequest) -> bool {
+    req.extensions()
+        .get::<Claims>()
+        .is_some_and(|claims| claims.admin_rights)
+}
+
+fn admin_required() -> HttpResponse {
+    HttpResponse::Forbidden().json(ReturnError::without_value(
+        "Admin rights required".to_string(),
+    ))
+}
+
 impl TableRoute {
     pub async fn create(payload: web::Payload) -> Result<impl Responder> {
         let table = match get_body::<CreateTableRequest>(payload).await {
@@ -31,20 +56,177 @@ impl TableRoute {
             }
         }
     }
+    pub async fn import(payload: web::Payload) -> Result<impl Responder> {
+        let request = match get_body::<ImportTableRequest>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        match ImportController::import(request) {
+            Ok(res) => {
+                return Ok(HttpResponse::Created().json(res));
+            }
+            Err(err) => {
+                let not_found = err.to_string().to_lowercase().contains("not found");
+                if not_found {
+                    return Ok(HttpResponse::NotFound().json(err));
+                }
+                return Ok(HttpResponse::BadRequest().json(err));
+            }
+        }
+    }
+    pub async fn clone(name: web::Path<String>, payload: web::Payload) -> Result<impl Responder> {
+        let request = match get_body::<CloneTableRequest>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        match CloneController::clone_table(name.into_inner(), request) {
+            Ok(res) => Ok(HttpResponse::Created().json(res)),
+            Err(err) => {
+                let message = err.to_string().to_lowercase();
+                if message.contains("not found") {
+                    return Ok(HttpResponse::NotFound().json(err));
+                }
+                if message.contains("already exists") {
+                    return Ok(HttpResponse::Conflict().json(err));
+                }
+                Ok(HttpResponse::BadRequest().json(err))
+            }
+        }
+    }
+    pub async fn find_policies(name: web::Path<String>) -> Result<impl Responder> {
+        match RowPolicyController::find_all(&name.into_inner()) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(HttpResponse::NotFound().json(err)),
+        }
+    }
+    /// Policies bind everyone but admins, so only admins manage them
+    pub async fn create_policy(
+        req: HttpRequest,
+        name: web::Path<String>,
+        payload: web::Payload,
+    ) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        let policy = match get_body::<CreateRowPolicy>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        match RowPolicyController::create(&name.into_inner(), policy) {
+            Ok(res) => Ok(HttpResponse::Created().json(res)),
+            Err(err) => {
+                let message = err.to_string().to_lowercase();
+                if message.contains("not found") {
+                    return Ok(HttpResponse::NotFound().json(err));
+                }
+                if message.contains("already exists") {
+                    return Ok(HttpResponse::Conflict().json(err));
+                }
+                Ok(HttpResponse::BadRequest().json(err))
+            }
+        }
+    }
+    pub async fn delete_policy(
+        req: HttpRequest,
+        path: web::Path<(String, i32)>,
+    ) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        let (name, id) = path.into_inner();
+        match RowPolicyController::delete(&name, id) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => {
+                let not_found = err.to_string().to_lowercase().contains("not found");
+                if not_found {
+                    return Ok(HttpResponse::NotFound().json(err));
+                }
+                Ok(HttpResponse::BadRequest().json(err))
+            }
+        }
+    }
+    pub async fn drift(req: HttpRequest) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        match DriftController::report() {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(HttpResponse::BadRequest().json(err)),
+        }
+    }
+    pub async fn repair_drift(req: HttpRequest, payload: web::Payload) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        let request = match get_body::<RepairDriftRequest>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        match DriftController::repair(request) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => {
+                let not_found = err.to_string().to_lowercase().contains("not found");
+                if not_found {
+                    return Ok(HttpResponse::NotFound().json(err));
+                }
+                Ok(HttpResponse::BadRequest().json(err))
+            }
+        }
+    }
+    pub async fn find_trash() -> Result<impl Responder> {
+        match TrashController::find_all() {
+            Ok(results) => Ok(HttpResponse::Ok().json(results)),
+            Err(err) => Ok(HttpResponse::BadRequest().json(err)),
+        }
+    }
+    pub async fn restore(table_id: web::Path<i32>) -> Result<impl Responder> {
+        match TrashController::restore(table_id.into_inner()) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => {
+                let message = err.to_string().to_lowercase();
+                if message.contains("not found") {
+                    return Ok(HttpResponse::NotFound().json(err));
+                }
+                if message.contains("already exists") {
+                    return Ok(HttpResponse::Conflict().json(err));
+                }
+                Ok(HttpResponse::BadRequest().json(err))
+            }
+        }
+    }
+    pub async fn purge(table_id: web::Path<i32>) -> Result<impl Responder> {
+        match TrashController::purge(table_id.into_inner()) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => {
+                let not_found = err.to_string().to_lowercase().contains("not found");
+                if not_found {
+                    return Ok(HttpResponse::NotFound().json(err));
+                }
+                Ok(HttpResponse::BadRequest().json(err))
+            }
+        }
+    }
     pub async fn update(post_id: web::Path<i32>, payload: web::Payload) -> Result<impl Responder> {
         let post_id = post_id.into_inner();
-        let mut table = match get_body::<Update>(payload).await {
+        let mut table = match get_body::<Versioned<Update>>(payload).await {
             Ok(res) => res,
             Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
         };
 
-        table.updated_at = Some(chrono::Utc::now().naive_utc()); // update the updated_at field with the current time
+        table.data.updated_at = Some(chrono::Utc::now().naive_utc()); // update the updated_at field with the current time
 
         match TableController::update(post_id, GenericValue::from(&table).unwrap()) {
             Ok(res) => {
                 return Ok(HttpResponse::Ok().json(res));
             }
             Err(err) => {
+                if err.to_string().to_lowercase().contains("version conflict") {
+                    return Ok(HttpResponse::Conflict().json(err));
+                }
                 let not_found = err.to_string().to_lowercase().contains("not found");
                 let val = err.values.clone().unwrap();
                 if not_found {
@@ -1,4 +1,7 @@
+use actix_web::http::header;
 use actix_web::web;
+use actix_web::HttpMessage;
+use actix_web::HttpRequest;
 use actix_web::HttpResponse;
 use actix_web::Responder;
 use actix_web::Result;
@@ -8,26 +11,92 @@ use serde_json::Value;
 use crate::models::db::connection::DbPool;
 use crate::utils::get_body::get_body;
 
-use crate::controller::custom::custom_controller::CustomController;
+use crate::controller::custom::batch_controller::{BatchController, BatchRequest};
+use crate::controller::custom::custom_controller::{CustomController, ManyRequest};
+use crate::controller::custom::etag::Etag;
+use crate::controller::custom::history_controller::HistoryController;
+use crate::controller::custom::patch::RowUpdate;
+use crate::controller::login::auth_controller::Claims;
+use crate::controller::login::current_user::CurrentUser;
+use crate::controller::tables::row_policy_controller::Viewer;
 use crate::controller::QueryParams;
+use crate::routes::utils::reponses::ReturnError;
 
 pub struct CustomRoute;
 
+/// Inactive tables answer 423 and full ones 507, anything else is a bad request
+fn error_response(err: ReturnError) -> HttpResponse {
+    let message = err.to_string().to_lowercase();
+    if message.contains("is inactive") {
+        return HttpResponse::Locked().json(err);
+    }
+    if message.contains("quota exceeded") {
+        return HttpResponse::InsufficientStorage().json(err);
+    }
+    HttpResponse::BadRequest().json(err)
+}
+
+/// User behind the request token, if any
+fn current_user(req: &HttpRequest) -> Option<i32> {
+    req.extensions().get::<CurrentUser>().map(|user| user.0.id)
+}
+
+fn header(req: &HttpRequest, name: header::HeaderName) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|x| x.to_str().ok())
+        .map(|x| x.to_string())
+}
+
+fn is_admin(req: &HttpRequest) -> bool {
+    req.extensions()
+        .get::<Claims>()
+        .is_some_and(|claims| claims.admin_rights)
+}
+
+/// Row policies of the table are applied for this viewer
+fn viewer(req: &HttpRequest) -> Viewer {
+    Viewer {
+        user: current_user(req),
+        admin: is_admin(req),
+    }
+}
+
+/// Missing rows answer 404 and stale `If-Match` headers 412, the rest goes through `error_response`
+fn row_error_response(err: ReturnError) -> HttpResponse {
+    let message = err.to_string().to_lowercase();
+    if message.contains("not found") {
+        return HttpResponse::NotFound().json(err);
+    }
+    if message.contains("precondition failed") {
+        return HttpResponse::PreconditionFailed().json(err);
+    }
+    error_response(err)
+}
+
 impl CustomRoute {
     pub async fn find_all(
+        req: HttpRequest,
         _pool: web::Data<DbPool>,
         table_name: web::Path<String>,
         query_params: web::Query<QueryParams>,
     ) -> Result<impl Responder> {
-        match CustomController::find_all(table_name.into_inner(), query_params.into_inner()).await {
+        match CustomController::find_all(
+            table_name.into_inner(),
+            query_params.into_inner(),
+            viewer(&req),
+        )
+        .await
+        {
             Ok(results) => return Ok(HttpResponse::Ok().json(results)),
             Err(err) => {
-                return Ok(HttpResponse::BadRequest().json(err));
+                return Ok(error_response(err));
             }
         }
     }
 
     pub async fn find_one(
+        req: HttpRequest,
         pool: web::Data<DbPool>,
         path: web::Path<(String, String)>,
         query_params: web::Query<QueryParams>,
@@ -36,17 +105,45 @@ impl CustomRoute {
         let pool = pool.into_inner();
         let controller = CustomController(pool);
         match controller
-            .find_one(table_name, id, query_params.into_inner())
+            .find_one(table_name, id, query_params.into_inner(), viewer(&req))
             .await
         {
-            Ok(results) => return Ok(HttpResponse::Ok().json(results)),
+            Ok((results, Some(etag))) => {
+                let if_none_match = header(&req, header::IF_NONE_MATCH);
+                if if_none_match.is_some_and(|x| Etag::matches(&x, &etag, true)) {
+                    return Ok(HttpResponse::NotModified()
+                        .insert_header((header::ETAG, etag))
+                        .finish());
+                }
+                return Ok(HttpResponse::Ok()
+                    .insert_header((header::ETAG, etag))
+                    .json(results));
+            }
+            Ok((results, None)) => return Ok(HttpResponse::Ok().json(results)),
             Err(err) => {
-                return Ok(HttpResponse::BadRequest().json(err));
+                return Ok(error_response(err));
             }
         }
     }
 
+    pub async fn find_many(
+        req: HttpRequest,
+        path: web::Path<(String,)>,
+        payload: web::Payload,
+    ) -> Result<impl Responder> {
+        let (table_name,) = path.into_inner();
+        let request = match get_body::<ManyRequest>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+        match CustomController::find_many(table_name, request.ids, viewer(&req)) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(error_response(err)),
+        }
+    }
+
     pub async fn create(
+        req: HttpRequest,
         path: web::Path<(String,)>,
         payload: web::Payload,
         query_params: web::Query<QueryParams>,
@@ -58,13 +155,97 @@ impl CustomRoute {
             Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
         };
 
-        match CustomController::create(table_name, table, query_params.into_inner()).await {
+        match CustomController::create(table_name, table, query_params.into_inner(), viewer(&req))
+            .await
+        {
             Ok(res) => {
                 return Ok(HttpResponse::Created().json(res));
             }
             Err(err) => {
-                return Ok(HttpResponse::BadRequest().json(err));
+                return Ok(error_response(err));
+            }
+        }
+    }
+
+    pub async fn update(
+        req: HttpRequest,
+        path: web::Path<(String, String)>,
+        payload: web::Payload,
+    ) -> Result<impl Responder> {
+        let (table_name, id) = path.into_inner();
+
+        let values = match get_body::<Value>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        let update =
+            RowUpdate::from_content_type(header(&req, header::CONTENT_TYPE).as_deref(), values);
+        let if_match = header(&req, header::IF_MATCH);
+        match CustomController::update(table_name, id, update, viewer(&req), if_match).await {
+            Ok((res, Some(etag))) => Ok(HttpResponse::Ok()
+                .insert_header((header::ETAG, etag))
+                .json(res)),
+            Ok((res, None)) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(row_error_response(err)),
+        }
+    }
+
+    pub async fn delete(
+        req: HttpRequest,
+        path: web::Path<(String, String)>,
+    ) -> Result<impl Responder> {
+        let (table_name, id) = path.into_inner();
+        let if_match = header(&req, header::IF_MATCH);
+        match CustomController::delete(table_name, id, viewer(&req), if_match).await {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(row_error_response(err)),
+        }
+    }
+
+    pub async fn batch(req: HttpRequest, payload: web::Payload) -> Result<impl Responder> {
+        let request = match get_body::<BatchRequest>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        let extensions = req.extensions();
+        match BatchController::run(request, extensions.get::<Claims>()) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => {
+                let message = err.to_string().to_lowercase();
+                if message.contains("not authorized") || message.contains("token missing") {
+                    return Ok(HttpResponse::Unauthorized().json(err));
+                }
+                Ok(row_error_response(err))
             }
         }
     }
+
+    pub async fn history(
+        req: HttpRequest,
+        path: web::Path<(String, String)>,
+    ) -> Result<impl Responder> {
+        let (table_name, id) = path.into_inner();
+        match CustomController::find_versions(table_name, id, viewer(&req)) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(row_error_response(err)),
+        }
+    }
+
+    pub async fn restore(
+        req: HttpRequest,
+        path: web::Path<(String, String, i32)>,
+    ) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(HttpResponse::Forbidden().json(ReturnError::without_value(
+                "Admin rights required".to_string(),
+            )));
+        }
+        let (table_name, id, version) = path.into_inner();
+        match HistoryController::restore(table_name, id, version, current_user(&req)) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(row_error_response(err)),
+        }
+    }
 }
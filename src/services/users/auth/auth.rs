@@ -1,62 +1,182 @@
 use std::env;
+use std::net::{IpAddr, SocketAddr};
 
-use actix_web::{web, HttpResponse, Responder, Result};
+use actix_web::{http::header, rt, web, HttpRequest, HttpResponse, Responder, Result};
 use dotenvy::dotenv;
-use jsonwebtoken::{encode, EncodingKey, Header};
-use serde::{Deserialize, Serialize};
+use log::error;
 
 use crate::{
-    controller::login::auth_controller::{AuthController, Claims, LoginData},
-    models::users::users_model::User,
+    controller::login::{
+        account_controller::{
+            AccountController, ForgotPasswordData, ResetPasswordData, VerifyEmailData,
+        },
+        auth_controller::{AuthController, LoginData},
+        jwt_keys::JwtKeys,
+        token_controller::{RefreshData, TokenController},
+    },
+    routes::utils::reponses::ReturnError,
     utils::get_body::get_body,
 };
 
-#[derive(Serialize, Deserialize)]
+pub struct AuthService;
 
-struct TokenReturn {
-    token: String,
+/// Address failed logins are counted for. `X-Forwarded-For` and `Forwarded` are only
+/// read with `TRUST_PROXY=true`, clients could forge them otherwise.
+fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    dotenv().ok();
+    if env::var("TRUST_PROXY").is_ok_and(|x| x == "true") {
+        let forwarded = req.connection_info().realip_remote_addr().and_then(|x| {
+            x.parse::<SocketAddr>()
+                .map(|x| x.ip())
+                .or(x.parse::<IpAddr>())
+                .ok()
+        });
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+    req.peer_addr().map(|x| x.ip())
 }
-pub struct AuthService;
 
 impl AuthService {
-    pub async fn login(payload: web::Payload) -> Result<impl Responder> {
+    pub async fn login(req: HttpRequest, payload: web::Payload) -> Result<impl Responder> {
         let login_data = match get_body::<LoginData>(payload).await {
             Ok(res) => res,
             Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
         };
 
-        let user = AuthController::login(login_data).await;
+        let user = AuthController::login(login_data, client_ip(&req)).await;
         let user = match user {
             Ok(user) => user,
+            Err(err) if err.to_string().contains("Too many") => {
+                let retry_after = err
+                    .values
+                    .as_ref()
+                    .and_then(|x| x["retryAfter"].as_i64())
+                    .unwrap_or(1);
+                return Ok(HttpResponse::TooManyRequests()
+                    .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                    .json(err));
+            }
             Err(err) => return Ok(HttpResponse::NotFound().json(err)),
         };
 
-        let res = HttpResponse::Accepted().json(TokenReturn {
-            token: generate_jwt(user),
-        });
+        let res = match TokenController::issue(&user) {
+            Ok(tokens) => HttpResponse::Accepted().json(tokens),
+            Err(err) => HttpResponse::InternalServerError().json(err),
+        };
 
         Ok(res)
     }
-}
 
-fn generate_jwt(user_data: User) -> String {
-    dotenv().ok();
-    let secret = env::var("SECRET").expect("SALT must be set");
+    pub async fn refresh(payload: web::Payload) -> Result<impl Responder> {
+        let refresh_data = match get_body::<RefreshData>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        let res = match TokenController::refresh(&refresh_data.refresh_token) {
+            Ok(tokens) => HttpResponse::Accepted().json(tokens),
+            Err(err) if err.to_string().contains("blocked") => HttpResponse::Forbidden().json(err),
+            Err(err) => HttpResponse::Unauthorized().json(err),
+        };
+
+        Ok(res)
+    }
+
+    /// Revokes the request token, the refresh token in the body is optional
+    pub async fn logout(req: HttpRequest, body: web::Bytes) -> Result<impl Responder> {
+        let token = req
+            .headers()
+            .get("Authorization")
+            .and_then(|x| x.to_str().ok())
+            .map(String::from);
+        let claims = match token.map(AuthController::verify_jwt) {
+            Some((true, Some(claims))) => claims,
+            _ => {
+                return Ok(HttpResponse::Unauthorized()
+                    .json(ReturnError::without_value("Token invalid".to_string())))
+            }
+        };
+
+        let refresh_data = if body.is_empty() {
+            None
+        } else {
+            match serde_json::from_slice::<RefreshData>(&body) {
+                Ok(res) => Some(res),
+                Err(err) => {
+                    return Ok(HttpResponse::BadRequest()
+                        .json(ReturnError::without_value(err.to_string())))
+                }
+            }
+        };
+
+        let res = match TokenController::logout(
+            &claims,
+            refresh_data.as_ref().map(|x| x.refresh_token.as_str()),
+        ) {
+            Ok(_) => HttpResponse::NoContent().finish(),
+            Err(err) if err.to_string().contains("invalid") => {
+                HttpResponse::Unauthorized().json(err)
+            }
+            Err(err) => HttpResponse::InternalServerError().json(err),
+        };
+
+        Ok(res)
+    }
 
-    let now = chrono::Utc::now() + chrono::Duration::days(1);
+    /// Answers the same whether or not the email belongs to a user. The token is issued and
+    /// mailed in the background, so the answer does not take longer for existing accounts.
+    pub async fn forgot_password(payload: web::Payload) -> Result<impl Responder> {
+        let data = match get_body::<ForgotPasswordData>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
 
-    let my_claims = Claims {
-        exp: now.timestamp() as usize,
-        api_rights: user_data.api_rights,
-        admin_rights: user_data.admin,
-    };
+        rt::spawn(async move {
+            let sent = rt::task::spawn_blocking(move || AccountController::forgot_password(data));
+            match sent.await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => error!("Cannot issue a password reset: {}", err),
+                Err(err) => error!("Password reset task failed: {}", err),
+            }
+        });
 
-    let token = encode(
-        &Header::default(),
-        &my_claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    )
-    .unwrap();
+        Ok(HttpResponse::Accepted().json(serde_json::json!({
+            "message": "If the email belongs to an account, a reset link has been sent"
+        })))
+    }
 
-    token
+    pub async fn reset_password(payload: web::Payload) -> Result<impl Responder> {
+        let data = match get_body::<ResetPasswordData>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        let res = match AccountController::reset_password(data) {
+            Ok(_) => HttpResponse::NoContent().finish(),
+            Err(err) => HttpResponse::BadRequest().json(err),
+        };
+
+        Ok(res)
+    }
+
+    pub async fn verify_email(payload: web::Payload) -> Result<impl Responder> {
+        let data = match get_body::<VerifyEmailData>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        let res = match AccountController::verify_email(data) {
+            Ok(_) => HttpResponse::NoContent().finish(),
+            Err(err) => HttpResponse::BadRequest().json(err),
+        };
+
+        Ok(res)
+    }
+
+    /// Public keys other services verify our tokens with
+    pub async fn jwks() -> Result<impl Responder> {
+        Ok(HttpResponse::Ok().json(JwtKeys::get().jwks()))
+    }
 }
@@ -0,0 +1,71 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder, Result};
+
+use crate::controller::login::auth_controller::Claims;
+use crate::controller::login::current_user::CurrentUser;
+use crate::controller::users::api_key_controller::ApiKeyController;
+use crate::controller::users::structs::CreateApiKey;
+use crate::routes::utils::reponses::ReturnError;
+use crate::utils::get_body::get_body;
+
+/// API keys of the logged in user
+pub struct ApiKeysRoute;
+
+/// Keys are managed with a login token, a key can not mint or revoke keys
+fn from_api_key(req: &HttpRequest) -> Option<HttpResponse> {
+    req.extensions()
+        .get::<Claims>()
+        .is_some_and(|claims| claims.scopes.is_some())
+        .then(|| {
+            HttpResponse::Forbidden().json(ReturnError::without_value(
+                "API keys can not manage API keys".to_string(),
+            ))
+        })
+}
+
+impl ApiKeysRoute {
+    pub async fn find_all(req: HttpRequest, user: CurrentUser) -> Result<impl Responder> {
+        if let Some(res) = from_api_key(&req) {
+            return Ok(res);
+        }
+        match ApiKeyController::find_all(user.0.id) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(HttpResponse::BadRequest().json(err)),
+        }
+    }
+
+    pub async fn create(
+        req: HttpRequest,
+        user: CurrentUser,
+        payload: web::Payload,
+    ) -> Result<impl Responder> {
+        if let Some(res) = from_api_key(&req) {
+            return Ok(res);
+        }
+        let new_key = match get_body::<CreateApiKey>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        match ApiKeyController::create(user.0.id, new_key) {
+            Ok(res) => Ok(HttpResponse::Created().json(res)),
+            Err(err) => Ok(HttpResponse::BadRequest().json(err)),
+        }
+    }
+
+    pub async fn revoke(
+        req: HttpRequest,
+        user: CurrentUser,
+        id: web::Path<i32>,
+    ) -> Result<impl Responder> {
+        if let Some(res) = from_api_key(&req) {
+            return Ok(res);
+        }
+        match ApiKeyController::revoke(user.0.id, id.into_inner()) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) if err.to_string().to_lowercase().contains("not found") => {
+                Ok(HttpResponse::NotFound().json(err))
+            }
+            Err(err) => Ok(HttpResponse::BadRequest().json(err)),
+        }
+    }
+}
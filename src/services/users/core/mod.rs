@@ -1 +1,3 @@
+pub mod api_keys;
+pub mod roles;
 pub mod users;
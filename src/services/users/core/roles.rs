@@ -0,0 +1,121 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder, Result};
+
+use crate::controller::login::auth_controller::Claims;
+use crate::controller::users::role_controller::RoleController;
+use crate::controller::users::structs::{CreateRole, UpdateRole};
+use crate::routes::utils::reponses::ReturnError;
+use crate::utils::get_body::get_body;
+
+/// Roles are managed by admins only
+pub struct RolesRoute;
+
+fn is_admin(req: &HttpRequest) -> bool {
+    req.extensions()
+        .get::<Claims>()
+        .is_some_and(|claims| claims.admin_rights)
+}
+
+fn admin_required() -> HttpResponse {
+    HttpResponse::Forbidden().json(ReturnError::without_value(
+        "Admin rights required".to_string(),
+    ))
+}
+
+fn error_response(err: ReturnError) -> HttpResponse {
+    let message = err.to_string().to_lowercase();
+    if message.contains("not found") {
+        return HttpResponse::NotFound().json(err);
+    }
+    if message.contains("already exists") {
+        return HttpResponse::Conflict().json(err);
+    }
+    HttpResponse::BadRequest().json(err)
+}
+
+impl RolesRoute {
+    pub async fn find_all(req: HttpRequest) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        match RoleController::find_all() {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(error_response(err)),
+        }
+    }
+
+    pub async fn find(req: HttpRequest, id: web::Path<i32>) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        match RoleController::find(id.into_inner()) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(error_response(err)),
+        }
+    }
+
+    pub async fn create(req: HttpRequest, payload: web::Payload) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        let new_role = match get_body::<CreateRole>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        match RoleController::create(new_role) {
+            Ok(res) => Ok(HttpResponse::Created().json(res)),
+            Err(err) => Ok(error_response(err)),
+        }
+    }
+
+    pub async fn update(
+        req: HttpRequest,
+        id: web::Path<i32>,
+        payload: web::Payload,
+    ) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        let changes = match get_body::<UpdateRole>(payload).await {
+            Ok(res) => res,
+            Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
+        };
+
+        match RoleController::update(id.into_inner(), changes) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(error_response(err)),
+        }
+    }
+
+    pub async fn delete(req: HttpRequest, id: web::Path<i32>) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        match RoleController::delete(id.into_inner()) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(error_response(err)),
+        }
+    }
+
+    pub async fn assign(req: HttpRequest, path: web::Path<(i32, i32)>) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        let (role_id, user_id) = path.into_inner();
+        match RoleController::assign(role_id, user_id) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(error_response(err)),
+        }
+    }
+
+    pub async fn unassign(req: HttpRequest, path: web::Path<(i32, i32)>) -> Result<impl Responder> {
+        if !is_admin(&req) {
+            return Ok(admin_required());
+        }
+        let (role_id, user_id) = path.into_inner();
+        match RoleController::unassign(role_id, user_id) {
+            Ok(res) => Ok(HttpResponse::Ok().json(res)),
+            Err(err) => Ok(error_response(err)),
+        }
+    }
+}
@@ -11,9 +11,23 @@ use crate::controller::users::structs::Create;
 use crate::controller::QueryParams;
 use crate::controller::users::structs::Update;
 use crate::controller::users::user_controller::UserController;
+use crate::controller::login::account_controller::AccountController;
+use crate::controller::login::current_user::CurrentUser;
+use crate::controller::login::throttle_controller::ThrottleController;
+use log::error;
 
 pub struct UsersRoute;
 
+/// Only admins give or take admin rights, API rights or the blocked flag
+fn privileges_denied(current: &CurrentUser, changes: [Option<bool>; 3]) -> Option<HttpResponse> {
+    if current.0.admin || changes.iter().all(|x| x.is_none()) {
+        return None;
+    }
+    Some(HttpResponse::Forbidden().json(ReturnError::without_value(
+        "Admin rights required to set admin, apiRights or blocked".to_string(),
+    )))
+}
+
 impl UsersRoute {
     pub async fn delete(user_id: web::Path<i32>) -> Result<impl Responder> {
         // body is loaded, now we can deserialize serde-json
@@ -37,14 +51,24 @@ impl UsersRoute {
             }
         }
     }
-    pub async fn create(payload: web::Payload) -> Result<impl Responder> {
+    pub async fn create(current: CurrentUser, payload: web::Payload) -> Result<impl Responder> {
         let new_user = match get_body::<Create>(payload).await {
             Ok(res) => res,
             Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
         };
+        let privileges = [new_user.admin, new_user.api_rights, new_user.blocked];
+        if let Some(denied) = privileges_denied(&current, privileges) {
+            return Ok(denied);
+        }
 
         match UserController::create(new_user) {
             Ok(res) => {
+                if let Err(err) = AccountController::send_verification(&res) {
+                    error!(
+                        "Cannot mail the email verification of user {}: {}",
+                        res.id, err
+                    );
+                }
                 return Ok(HttpResponse::Created().json(res));
             }
             Err(err) => {
@@ -52,16 +76,44 @@ impl UsersRoute {
             }
         }
     }
-    pub async fn update(user_id: web::Path<i32>, payload: web::Payload) -> Result<impl Responder> {
+    pub async fn update(
+        current: CurrentUser,
+        user_id: web::Path<i32>,
+        payload: web::Payload,
+    ) -> Result<impl Responder> {
         let user_id = user_id.into_inner();
         let mut new_user = match get_body::<Update>(payload).await {
             Ok(res) => res,
             Err(err) => return Ok(HttpResponse::BadRequest().json(err)),
         };
+        let privileges = [new_user.admin, new_user.api_rights, new_user.blocked];
+        if let Some(denied) = privileges_denied(&current, privileges) {
+            return Ok(denied);
+        }
 
         new_user.updated_at = Some(chrono::Utc::now().naive_utc());
+        if new_user.blocked.is_some() {
+            new_user.auto_blocked_at = Some(None);
+        }
+        let unblocked = new_user.blocked == Some(false);
+        let previous_email = UserController::find(user_id).ok().map(|x| x.email);
         match UserController::update(user_id, new_user) {
-            Ok(res) => {
+            Ok(mut res) => {
+                // Failures left from before the unblock would block the account again
+                if unblocked {
+                    if let Err(err) = ThrottleController::record_success(&res.email) {
+                        error!("Cannot reset the failed logins of user {}: {}", res.id, err);
+                    }
+                }
+                if previous_email.is_some_and(|x| x != res.email) {
+                    match AccountController::email_changed(&res) {
+                        Ok(_) => res.email_verified_at = None,
+                        Err(err) => error!(
+                            "Cannot reset the email verification of user {}: {}",
+                            res.id, err
+                        ),
+                    }
+                }
                 return Ok(HttpResponse::Ok().json(res));
             }
             Err(err) => {
@@ -0,0 +1,209 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use dotenvy::dotenv;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use log::info;
+
+use crate::routes::utils::reponses::ReturnError;
+
+pub const DEFAULT_MAIL_FROM: &str = "no-reply@localhost";
+pub const DEFAULT_MAIL_DIR: &str = "mails";
+/// Prefixes of the one-time tokens put in mails by `AccountController`
+const TOKEN_MARKERS: [&str; 2] = ["token=", "Token: "];
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Loaded on first use, changing the mailer needs a restart
+static MAILER: LazyLock<Box<dyn Mailer>> =
+    LazyLock::new(|| load().unwrap_or_else(|err| panic!("Invalid mailer: {}", err)));
+
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Where the mails of the server go, picked by `MAILER`
+pub trait Mailer: Send + Sync {
+    fn send(&self, mail: &Mail) -> Result<(), ReturnError>;
+}
+
+/// Mailer configured by `MAILER`: `file` (default), `log` or `smtp`
+pub fn mailer() -> &'static dyn Mailer {
+    MAILER.as_ref()
+}
+
+fn load() -> Result<Box<dyn Mailer>, ReturnError> {
+    dotenv().ok();
+    let from = env::var("MAIL_FROM").unwrap_or(DEFAULT_MAIL_FROM.to_string());
+    check_address(&from)?;
+    // Mails carry live tokens, they only reach the log when asked for
+    match env::var("MAILER").unwrap_or("file".to_string()).as_str() {
+        "log" => Ok(Box::new(LogMailer)),
+        "file" => Ok(Box::new(FileMailer {
+            dir: PathBuf::from(env::var("MAIL_DIR").unwrap_or(DEFAULT_MAIL_DIR.to_string())),
+            from,
+        })),
+        "smtp" => Ok(Box::new(SmtpMailer::from_env(&from)?)),
+        other => Err(ReturnError::without_value(format!(
+            "Unknown MAILER `{}`, expected `log`, `file` or `smtp`",
+            other
+        ))),
+    }
+}
+
+/// Addresses end up in mail headers, line breaks would inject new ones
+fn check_address(address: &str) -> Result<(), ReturnError> {
+    if address.is_empty() || address.contains(['\r', '\n', '<', '>']) {
+        return Err(ReturnError::new(
+            "Invalid mail address".to_string(),
+            address,
+        ));
+    }
+    Ok(())
+}
+
+/// RFC 5322 message, the subject is on a single line
+fn message(from: &str, mail: &Mail) -> String {
+    let subject = mail.subject.replace(['\r', '\n'], " ");
+    let body = mail.body.replace("\r\n", "\n").replace('\n', "\r\n");
+    format!(
+        "From: <{}>\r\nTo: <{}>\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+        from,
+        mail.to,
+        subject,
+        chrono::Utc::now().to_rfc2822(),
+        body
+    )
+}
+
+/// Writes mails to the server log, for development. Tokens are redacted, `file` keeps them
+pub struct LogMailer;
+
+impl LogMailer {
+    fn redact(body: &str) -> String {
+        let mut body = body.to_string();
+        for marker in TOKEN_MARKERS {
+            let mut from = 0;
+            while let Some(found) = body[from..].find(marker) {
+                let start = from + found + marker.len();
+                let end = body[start..]
+                    .find(|x: char| x.is_whitespace() || x == '&')
+                    .map_or(body.len(), |x| start + x);
+                body.replace_range(start..end, "[redacted]");
+                from = start;
+            }
+        }
+        body
+    }
+}
+
+impl Mailer for LogMailer {
+    fn send(&self, mail: &Mail) -> Result<(), ReturnError> {
+        check_address(&mail.to)?;
+        info!(
+            "Mail to {}: {}\n{}",
+            mail.to,
+            mail.subject,
+            Self::redact(&mail.body)
+        );
+        Ok(())
+    }
+}
+
+/// Writes each mail to an `.eml` file of `MAIL_DIR`
+pub struct FileMailer {
+    pub dir: PathBuf,
+    pub from: String,
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, mail: &Mail) -> Result<(), ReturnError> {
+        check_address(&mail.to)?;
+        let error =
+            |err: std::io::Error| ReturnError::new(format!("Cannot write mail: {}", err), &mail.to);
+        fs::create_dir_all(&self.dir).map_err(error)?;
+        let name = format!(
+            "{}-{:08x}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            rand::random::<u32>()
+        );
+        fs::write(self.dir.join(name), message(&self.from, mail)).map_err(error)
+    }
+}
+
+/// SMTP through `lettre`, `SMTP_TLS` picks `starttls` (default), `tls` or `none`.
+/// `SMTP_USERNAME` and `SMTP_PASSWORD` log in when both are set.
+pub struct SmtpMailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    fn from_env(from: &str) -> Result<SmtpMailer, ReturnError> {
+        let error = |err: lettre::transport::smtp::Error| {
+            ReturnError::without_value(format!("SMTP error: {}", err))
+        };
+        let host = env::var("SMTP_HOST").map_err(|_| {
+            ReturnError::without_value("SMTP_HOST is required by the smtp mailer".to_string())
+        })?;
+        let mut builder = match env::var("SMTP_TLS")
+            .unwrap_or("starttls".to_string())
+            .as_str()
+        {
+            "starttls" => SmtpTransport::starttls_relay(&host).map_err(error)?,
+            "tls" => SmtpTransport::relay(&host).map_err(error)?,
+            "none" => SmtpTransport::builder_dangerous(&host),
+            other => {
+                return Err(ReturnError::without_value(format!(
+                    "Unknown SMTP_TLS `{}`, expected `starttls`, `tls` or `none`",
+                    other
+                )))
+            }
+        };
+        // Each mode has its own default port
+        if let Some(port) = env::var("SMTP_PORT")
+            .ok()
+            .and_then(|x| x.parse::<u16>().ok())
+        {
+            builder = builder.port(port);
+        }
+        let username = env::var("SMTP_USERNAME").ok().filter(|x| !x.is_empty());
+        if let (Some(username), Ok(password)) = (username, env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+        Ok(SmtpMailer {
+            transport: builder.timeout(Some(SMTP_TIMEOUT)).build(),
+            from: mailbox(from)?,
+        })
+    }
+}
+
+fn mailbox(address: &str) -> Result<Mailbox, ReturnError> {
+    address
+        .parse::<Mailbox>()
+        .map_err(|_| ReturnError::new("Invalid mail address".to_string(), address))
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, mail: &Mail) -> Result<(), ReturnError> {
+        check_address(&mail.to)?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(mailbox(&mail.to)?)
+            .subject(&mail.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(mail.body.clone())
+            .map_err(|err| ReturnError::new(format!("Invalid mail: {}", err), &mail.to))?;
+        self.transport
+            .send(&message)
+            .map(|_| ())
+            .map_err(|err| ReturnError::without_value(format!("SMTP error: {}", err)))
+    }
+}
@@ -1,3 +1,4 @@
 pub mod get_body;
+pub mod mailer;
 pub mod sql;
 pub mod string_utils;
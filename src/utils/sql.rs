@@ -10,6 +10,9 @@ use crate::controller::{
 
 use super::string_utils::to_snake_case;
 
+/// Columns added to tables created with `audit`, in creation order
+pub const AUDIT_COLUMNS: [&str; 4] = ["created_at", "updated_at", "created_by", "updated_by"];
+
 pub struct TableQueryBuilder {
     pub table: Create,
     pub fields: Vec<CreateField>,
@@ -37,9 +40,30 @@ impl TableQueryBuilder {
         str_table.push_str(&format!("CREATE TABLE {}", self.table.name));
         str_table.push_str("(\n");
         str_table.push_str(&self.build_fields());
+        if self.table.is_audited() {
+            str_table.push_str(",\n");
+            str_table.push_str(&Self::build_audit_fields());
+        }
         str_table.push_str(");");
         str_table
     }
+
+    fn build_audit_fields() -> String {
+        [
+            "\tcreated_at TIMESTAMP NOT NULL DEFAULT now()",
+            "\tupdated_at TIMESTAMP NOT NULL DEFAULT now()",
+            "\tcreated_by INTEGER DEFAULT NULL",
+            "\tupdated_by INTEGER DEFAULT NULL",
+        ]
+        .join(",\n")
+    }
+
+    /// Keeps `updated_at` current through the trigger from the initial migration
+    pub fn build_audit_trigger(&self) -> Option<String> {
+        self.table
+            .is_audited()
+            .then(|| format!("SELECT diesel_manage_updated_at('{}');", self.table.name))
+    }
     pub fn build_update_table(&self) -> String {
         let mut str_table = String::new();
 
@@ -57,6 +81,56 @@ impl TableQueryBuilder {
         format!("DROP TABLE IF EXISTS {} CASCADE;", name)
     }
 
+    pub fn rename_table(name: &str, new_name: &str) -> String {
+        format!("ALTER TABLE IF EXISTS {} RENAME TO {};", name, new_name)
+    }
+
+    pub fn rename_sequence(name: &str, new_name: &str) -> String {
+        format!("ALTER SEQUENCE IF EXISTS {} RENAME TO {};", name, new_name)
+    }
+
+    pub fn rename_index(name: &str, new_name: &str) -> String {
+        format!("ALTER INDEX IF EXISTS {} RENAME TO {};", name, new_name)
+    }
+
+    pub fn rename_constraint(table: &str, name: &str, new_name: &str) -> String {
+        format!(
+            "ALTER TABLE {} RENAME CONSTRAINT {} TO {};",
+            table, name, new_name
+        )
+    }
+
+    /// `method` is the part of an index definition after `USING`, the index name is left to Postgres
+    pub fn create_index(name: &str, is_unique: bool, method: &str) -> String {
+        let unique = if is_unique { "UNIQUE " } else { "" };
+        format!("CREATE {}INDEX ON {} USING {};", unique, name, method)
+    }
+
+    /// Copies rows between tables, `conditions` is appended as is and may hold bind placeholders
+    pub fn copy_rows(
+        source: &str,
+        target: &str,
+        source_columns: &[String],
+        target_columns: &[String],
+        conditions: &str,
+    ) -> String {
+        format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {} {};",
+            target,
+            target_columns.join(", "),
+            source_columns.join(", "),
+            source,
+            conditions
+        )
+    }
+
+    /// Moves the sequence of a serial column past the values copied into it
+    pub fn reset_sequence(name: &str, column: &str) -> String {
+        format!(
+            "SELECT setval(pg_get_serial_sequence('{name}', '{column}'), COALESCE(MAX({column}), 1), MAX({column}) IS NOT NULL) FROM {name};"
+        )
+    }
+
     pub fn delete_fields(name: &str) -> String {
         format!(
             "Delete from fields where table_id in (Select id from tables where name='{}')",
@@ -431,6 +505,26 @@ impl FieldQueryBuilder {
 
         str_field
     }
+    pub fn alter_type<S: AsRef<str>>(table: S, column: S, field_type: FieldType) -> String {
+        let pg_type = field_type.to_pg_type().to_uppercase();
+        format!(
+            "ALTER TABLE {}\nALTER COLUMN {} TYPE {} USING {}::{};",
+            table.as_ref(),
+            column.as_ref(),
+            pg_type,
+            column.as_ref(),
+            pg_type
+        )
+    }
+
+    pub fn alter_not_null<S: AsRef<str>>(table: S, column: S, not_null: bool) -> String {
+        format!(
+            "ALTER TABLE {}\nALTER COLUMN {} {} NOT NULL;",
+            table.as_ref(),
+            column.as_ref(),
+            if not_null { "SET" } else { "DROP" }
+        )
+    }
     pub fn get_field_constraints(field: &CreateField) -> String {
         let mut str_constraints = String::new();
 
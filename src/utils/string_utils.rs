@@ -30,3 +30,13 @@ pub fn to_camel_case(s: &str) -> String {
 
     camel_case
 }
+
+/// Lowercase identifiers can be used unquoted in the generated SQL
+pub fn is_plain_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_lowercase() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}